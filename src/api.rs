@@ -1,7 +1,9 @@
 use actix_web::{get, post, web, HttpResponse, Responder};
 use actix_session::Session;
-use crate::{auth, models::*, storage::Storage, totp_manager};
-use log::{info, warn, error};
+use crate::{auth, export, formats, migration, models::*, storage::{blocking, Storage}, totp_manager};
+use base64::{Engine as _, engine::general_purpose};
+use log::{debug, info, warn, error};
+use zeroize::Zeroizing;
 
 // 辅助宏：处理数据库锁定错误
 macro_rules! handle_storage_result {
@@ -14,6 +16,13 @@ macro_rules! handle_storage_result {
                     message: "Database is locked. Please unlock first.".to_string(),
                 });
             }
+            Err(e @ crate::error::AppError::ModifiedExternally) => {
+                warn!("Storage error: {}", e);
+                return HttpResponse::Conflict().json(ApiResponse {
+                    success: false,
+                    message: e.to_string(),
+                });
+            }
             Err(e) => {
                 error!("Storage error: {}", e);
                 return HttpResponse::InternalServerError().json(ApiResponse {
@@ -32,15 +41,16 @@ async fn unlock_database(
 ) -> impl Responder {
     info!("Database unlock requested");
     
-    match storage.unlock(&data.master_password).await {
-        Ok(true) => {
+    match storage.unlock(data.master_password.expose_secret()).await {
+        Ok(recovery_key) => {
             info!("Database unlocked successfully");
-            HttpResponse::Ok().json(ApiResponse {
+            HttpResponse::Ok().json(UnlockResponse {
                 success: true,
                 message: "Database unlocked successfully".to_string(),
+                recovery_key: recovery_key.map(|key| key.as_str().into()),
             })
         }
-        Ok(false) => {
+        Err(crate::error::AppError::InvalidMasterPassword) => {
             warn!("Invalid master password attempt");
             HttpResponse::Ok().json(ApiResponse {
                 success: false,
@@ -60,16 +70,335 @@ async fn unlock_database(
 #[get("/lock-status")]
 async fn get_lock_status(storage: web::Data<Storage>) -> impl Responder {
     HttpResponse::Ok().json(serde_json::json!({
-        "locked": !storage.is_unlocked()
+        "locked": !storage.is_unlocked().await,
+        "auto_lock_in": storage.auto_lock_remaining().await.map(|d| d.as_secs()),
+        "save": storage.save_status()
     }))
 }
 
+#[post("/lock")]
+async fn lock_database(
+    session: Session,
+    query: web::Query<LockQuery>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    if let Err(e) = storage.lock(query.discard_unsaved).await {
+        error!("Failed to save before locking: {}", e);
+        if let crate::error::AppError::ModifiedExternally = e {
+            return HttpResponse::Conflict().json(ApiResponse {
+                success: false,
+                message: "Data was modified by another process. Lock with discard_unsaved=true to discard unsaved changes and reload".to_string(),
+            });
+        }
+        return HttpResponse::InternalServerError().json(ApiResponse {
+            success: false,
+            message: "Failed to save changes, database not locked".to_string(),
+        });
+    }
+    HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Database locked".to_string(),
+    })
+}
+
+// 完整性检查需要登录并提供主密码；锁定后会话仍然有效，锁定时也可以使用
+#[post("/integrity-check")]
+async fn check_integrity(
+    session: Session,
+    data: web::Json<UnlockRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    info!("Integrity check requested");
+    
+    let result = storage.check_integrity(data.master_password.expose_secret()).await;
+    if let Err(crate::error::AppError::InvalidMasterPassword) = result {
+        warn!("Integrity check rejected: invalid master password");
+        return HttpResponse::Ok().json(ApiResponse {
+            success: false,
+            message: "Invalid master password".to_string(),
+        });
+    }
+    
+    let report = handle_storage_result!(result);
+    if !report.ok {
+        warn!("Integrity check found problems: {:?} {:?}", report.vault_error, report.issues);
+    }
+    HttpResponse::Ok().json(report)
+}
+
+#[post("/integrity-check/recover")]
+async fn recover_from_backup(
+    session: Session,
+    data: web::Json<UnlockRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    if storage.is_unlocked().await {
+        return HttpResponse::Conflict().json(ApiResponse {
+            success: false,
+            message: "Lock the database before recovering from a backup".to_string(),
+        });
+    }
+    
+    let result = storage.recover(data.master_password.expose_secret()).await;
+    if let Err(crate::error::AppError::InvalidMasterPassword) = result {
+        warn!("Recovery rejected: invalid master password");
+        return HttpResponse::Ok().json(ApiResponse {
+            success: false,
+            message: "Invalid master password".to_string(),
+        });
+    }
+    
+    let report = handle_storage_result!(result);
+    if let Some(id) = &report.recovered_from {
+        info!("Vault recovered from backup {}", id);
+    }
+    HttpResponse::Ok().json(report)
+}
+
+#[post("/change-master-password")]
+async fn change_master_password(
+    session: Session,
+    data: web::Json<ChangeMasterPasswordRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    if data.new_master_password.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse {
+            success: false,
+            message: "New master password must not be empty".to_string(),
+        });
+    }
+    
+    let result = storage.change_master_password(
+        data.old_master_password.expose_secret(),
+        data.new_master_password.expose_secret(),
+    ).await;
+    if let Err(crate::error::AppError::InvalidMasterPassword) = result {
+        warn!("Invalid master password on change attempt");
+        return HttpResponse::Ok().json(ApiResponse {
+            success: false,
+            message: "Invalid old master password".to_string(),
+        });
+    }
+    handle_storage_result!(result);
+    
+    info!("Master password changed");
+    HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Master password changed successfully".to_string(),
+    })
+}
+
+// 密钥槽管理：需要登录，并用主密码（或其他密钥槽的秘密）确认
+#[get("/key-slots")]
+async fn list_key_slots(
+    session: Session,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let slots = handle_storage_result!(storage.list_key_slots().await);
+    HttpResponse::Ok().json(slots)
+}
+
+#[post("/key-slots/password")]
+async fn add_password_slot(
+    session: Session,
+    data: web::Json<AddPasswordSlotRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    if data.new_password.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse {
+            success: false,
+            message: "New password must not be empty".to_string(),
+        });
+    }
+    
+    let result = storage.add_password_slot(
+        data.master_password.expose_secret(),
+        data.new_password.expose_secret(),
+    ).await;
+    if let Err(crate::error::AppError::InvalidMasterPassword) = result {
+        warn!("Key slot change rejected: invalid master password");
+        return HttpResponse::Ok().json(ApiResponse {
+            success: false,
+            message: "Invalid master password".to_string(),
+        });
+    }
+    let id = handle_storage_result!(result);
+    
+    HttpResponse::Ok().json(KeySlotCreatedResponse {
+        success: true,
+        id,
+        recovery_key: None,
+    })
+}
+
+#[post("/key-slots/recovery-key")]
+async fn add_recovery_key_slot(
+    session: Session,
+    data: web::Json<AddRecoveryKeySlotRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let result = storage.add_recovery_key_slot(data.master_password.expose_secret()).await;
+    if let Err(crate::error::AppError::InvalidMasterPassword) = result {
+        warn!("Key slot change rejected: invalid master password");
+        return HttpResponse::Ok().json(ApiResponse {
+            success: false,
+            message: "Invalid master password".to_string(),
+        });
+    }
+    let (id, recovery_key) = handle_storage_result!(result);
+    
+    HttpResponse::Ok().json(KeySlotCreatedResponse {
+        success: true,
+        id,
+        recovery_key: Some(recovery_key.as_str().into()),
+    })
+}
+
+#[post("/key-slots/key-file")]
+async fn add_key_file_slot(
+    session: Session,
+    data: web::Json<AddKeyFileSlotRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    if data.path.trim().is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse {
+            success: false,
+            message: "Key file path must not be empty".to_string(),
+        });
+    }
+    
+    let result = storage.add_key_file_slot(data.master_password.expose_secret(), data.path.trim()).await;
+    match result {
+        Err(crate::error::AppError::InvalidMasterPassword) => {
+            warn!("Key slot change rejected: invalid master password");
+            return HttpResponse::Ok().json(ApiResponse {
+                success: false,
+                message: "Invalid master password".to_string(),
+            });
+        }
+        Err(e @ (crate::error::AppError::InvalidKeyFilePath(_) | crate::error::AppError::InsecureKeyFile(_))) => {
+            warn!("Key file rejected: {}", e);
+            return HttpResponse::BadRequest().json(ApiResponse {
+                success: false,
+                message: e.to_string(),
+            });
+        }
+        _ => {}
+    }
+    let id = handle_storage_result!(result);
+    
+    HttpResponse::Ok().json(KeySlotCreatedResponse {
+        success: true,
+        id,
+        recovery_key: None,
+    })
+}
+
+#[post("/key-slots/remove")]
+async fn remove_key_slot(
+    session: Session,
+    data: web::Json<RemoveKeySlotRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let result = storage.remove_key_slot(data.master_password.expose_secret(), &data.id).await;
+    match result {
+        Err(crate::error::AppError::InvalidMasterPassword) => {
+            warn!("Key slot change rejected: invalid master password");
+            return HttpResponse::Ok().json(ApiResponse {
+                success: false,
+                message: "Invalid master password".to_string(),
+            });
+        }
+        Err(e @ crate::error::AppError::LastKeySlot) => {
+            return HttpResponse::BadRequest().json(ApiResponse {
+                success: false,
+                message: e.to_string(),
+            });
+        }
+        _ => {}
+    }
+    
+    if !handle_storage_result!(result) {
+        return HttpResponse::NotFound().json(ApiResponse {
+            success: false,
+            message: "Key slot not found".to_string(),
+        });
+    }
+    HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Key slot removed".to_string(),
+    })
+}
+
 #[post("/check-user-2fa")]
 async fn check_user_2fa(
     data: web::Json<CheckUser2FARequest>,
     storage: web::Data<Storage>,
 ) -> impl Responder {
-    let user = handle_storage_result!(storage.get_user());
+    let user = handle_storage_result!(storage.get_user().await);
     
     if data.username == user.username {
         return HttpResponse::Ok().json(serde_json::json!({
@@ -88,7 +417,7 @@ async fn login(
     data: web::Json<LoginRequest>,
     storage: web::Data<Storage>,
 ) -> impl Responder {
-    let user = handle_storage_result!(storage.get_user());
+    let user = handle_storage_result!(storage.get_user().await);
     
     if data.username != user.username {
         return HttpResponse::Ok().json(LoginResponse {
@@ -98,7 +427,7 @@ async fn login(
         });
     }
     
-    if !verify_password(&data.password, &user.password_hash) {
+    if !verify_password(data.password.expose_secret(), &user.password_hash) {
         return HttpResponse::Ok().json(LoginResponse {
             success: false,
             message: "Invalid username or password".to_string(),
@@ -109,7 +438,7 @@ async fn login(
     if user.two_fa_enabled {
         if let Some(ref totp_code) = data.totp_code {
             if let Some(ref secret) = user.two_fa_secret {
-                match totp_manager::verify_totp_code(secret, totp_code) {
+                match totp_manager::verify_totp_code(secret.expose_secret(), totp_code) {
                     Ok(true) => {
                         if auth::set_session(&session, &user.username).is_ok() {
                             return HttpResponse::Ok().json(LoginResponse {
@@ -183,19 +512,19 @@ async fn change_password(
         });
     }
     
-    let user = handle_storage_result!(storage.get_user());
+    let user = handle_storage_result!(storage.get_user().await);
     
-    if !verify_password(&data.old_password, &user.password_hash) {
+    if !verify_password(data.old_password.expose_secret(), &user.password_hash) {
         return HttpResponse::Ok().json(ApiResponse {
             success: false,
             message: "Invalid old password".to_string(),
         });
     }
     
-    let new_hash = hash_password(&data.new_password);
+    let new_hash = hash_password(data.new_password.expose_secret());
     if let Err(e) = storage.update_user(|u| {
         u.password_hash = new_hash;
-    }) {
+    }).await {
         error!("Failed to update password: {}", e);
         return HttpResponse::InternalServerError().json(ApiResponse {
             success: false,
@@ -241,14 +570,11 @@ async fn enable_2fa(
         }
     };
     
-    let otpauth_url = format!(
-        "otpauth://totp/WebTOTP:admin?secret={}&issuer=WebTOTP",
-        secret
-    );
+    let otpauth_url = totp_manager::generate_otpauth_url(&secret, "admin", "WebTOTP");
     
     if let Err(e) = storage.update_user(|u| {
-        u.two_fa_secret = Some(secret.clone());
-    }) {
+        u.two_fa_secret = Some(secret.clone().into());
+    }).await {
         error!("Failed to update 2FA secret: {}", e);
         return HttpResponse::InternalServerError().json(ApiResponse {
             success: false,
@@ -256,14 +582,12 @@ async fn enable_2fa(
         });
     }
     
-    if let Err(e) = storage.save().await {
-        error!("Failed to save: {}", e);
-    }
+    storage.schedule_save();
     
     HttpResponse::Ok().json(TwoFaSetupResponse {
-        secret,
+        secret: secret.into(),
         qr_code,
-        otpauth_url,
+        otpauth_url: otpauth_url.into(),
     })
 }
 
@@ -280,14 +604,14 @@ async fn verify_2fa(
         });
     }
     
-    let user = handle_storage_result!(storage.get_user());
+    let user = handle_storage_result!(storage.get_user().await);
     
     if let Some(ref secret) = user.two_fa_secret {
-        match totp_manager::verify_totp_code(secret, &data.code) {
+        match totp_manager::verify_totp_code(secret.expose_secret(), &data.code) {
             Ok(true) => {
                 if let Err(e) = storage.update_user(|u| {
                     u.two_fa_enabled = true;
-                }) {
+                }).await {
                     error!("Failed to enable 2FA: {}", e);
                     return HttpResponse::InternalServerError().json(ApiResponse {
                         success: false,
@@ -297,6 +621,10 @@ async fn verify_2fa(
                 
                 if let Err(e) = storage.save().await {
                     error!("Failed to save: {}", e);
+                    return HttpResponse::InternalServerError().json(ApiResponse {
+                        success: false,
+                        message: "Failed to save changes".to_string(),
+                    });
                 }
                 
                 return HttpResponse::Ok().json(ApiResponse {
@@ -332,9 +660,9 @@ async fn disable_2fa(
         });
     }
     
-    let user = handle_storage_result!(storage.get_user());
+    let user = handle_storage_result!(storage.get_user().await);
     
-    if !verify_password(&data.password, &user.password_hash) {
+    if !verify_password(data.password.expose_secret(), &user.password_hash) {
         return HttpResponse::Ok().json(ApiResponse {
             success: false,
             message: "Invalid password".to_string(),
@@ -343,7 +671,7 @@ async fn disable_2fa(
     
     if user.two_fa_enabled {
         if let Some(ref secret) = user.two_fa_secret {
-            match totp_manager::verify_totp_code(secret, &data.code) {
+            match totp_manager::verify_totp_code(secret.expose_secret(), &data.code) {
                 Ok(true) => {}
                 _ => {
                     return HttpResponse::Ok().json(ApiResponse {
@@ -358,7 +686,7 @@ async fn disable_2fa(
     if let Err(e) = storage.update_user(|u| {
         u.two_fa_enabled = false;
         u.two_fa_secret = None;
-    }) {
+    }).await {
         error!("Failed to disable 2FA: {}", e);
         return HttpResponse::InternalServerError().json(ApiResponse {
             success: false,
@@ -368,6 +696,10 @@ async fn disable_2fa(
     
     if let Err(e) = storage.save().await {
         error!("Failed to save: {}", e);
+        return HttpResponse::InternalServerError().json(ApiResponse {
+            success: false,
+            message: "Failed to save changes".to_string(),
+        });
     }
     
     HttpResponse::Ok().json(ApiResponse {
@@ -388,7 +720,7 @@ async fn get_2fa_status(
         });
     }
     
-    let user = handle_storage_result!(storage.get_user());
+    let user = handle_storage_result!(storage.get_user().await);
     HttpResponse::Ok().json(serde_json::json!({
         "enabled": user.two_fa_enabled
     }))
@@ -407,13 +739,26 @@ async fn add_totp_entry(
         });
     }
     
-    let entry = TotpEntry::new(
+    let mut entry = TotpEntry::new(
         data.name.clone(),
         data.issuer.clone(),
-        data.secret.clone(),
+        data.secret.expose_secret().to_string(),
     );
+    entry.algorithm = data.algorithm;
+    entry.digits = data.digits;
+    entry.period = data.period;
+    entry.otp_type = data.otp_type;
+    entry.counter = data.counter;
+    
+    if let Err(e) = totp_manager::validate_entry(&entry) {
+        warn!("Rejected TOTP entry: {}", e);
+        return HttpResponse::BadRequest().json(ApiResponse {
+            success: false,
+            message: e.to_string(),
+        });
+    }
     
-    if let Err(e) = storage.add_totp_entry(entry.clone()) {
+    if let Err(e) = storage.add_totp_entry(entry.clone()).await {
         error!("Failed to add TOTP entry: {}", e);
         return HttpResponse::InternalServerError().json(ApiResponse {
             success: false,
@@ -421,16 +766,81 @@ async fn add_totp_entry(
         });
     }
     
-    if let Err(e) = storage.save().await {
-        error!("Failed to save: {}", e);
-    }
+    storage.schedule_save();
     
     HttpResponse::Ok().json(entry)
 }
 
-#[get("/totp/list")]
-async fn list_totp_entries(
+// 批量导入：跳过与已有条目（或本批次中更早条目）重复的项，最后统一保存一次
+// dry_run 时只返回预览结果，不修改数据
+async fn import_entries(
+    storage: &Storage,
+    parsed: Vec<crate::error::Result<TotpEntry>>,
+    dry_run: bool,
+) -> crate::error::Result<ImportResponse> {
+    let mut existing = storage.get_totp_entries().await?;
+    let mut results = Vec::with_capacity(parsed.len());
+    
+    for (index, entry) in parsed.into_iter().enumerate() {
+        match entry {
+            Ok(entry) if existing.iter().any(|e| totp_manager::is_duplicate(e, &entry)) => {
+                results.push(ImportResult {
+                    index,
+                    success: false,
+                    duplicate: true,
+                    message: "Duplicate of an existing entry".to_string(),
+                    entry: None,
+                });
+            }
+            Ok(entry) => {
+                if !dry_run {
+                    storage.add_totp_entry(entry.clone()).await?;
+                }
+                results.push(ImportResult {
+                    index,
+                    success: true,
+                    duplicate: false,
+                    message: if dry_run { "Ready to import" } else { "Imported" }.to_string(),
+                    entry: Some(EntrySummary::from(&entry)),
+                });
+                existing.push(entry);
+            }
+            Err(e) => {
+                warn!("Failed to import entry #{}: {}", index, e);
+                results.push(ImportResult {
+                    index,
+                    success: false,
+                    duplicate: false,
+                    message: e.to_string(),
+                    entry: None,
+                });
+            }
+        }
+    }
+    
+    let imported = results.iter().filter(|r| r.success).count();
+    let duplicates = results.iter().filter(|r| r.duplicate).count();
+    if dry_run {
+        debug!("Import preview: {} of {} entries importable", imported, results.len());
+    } else {
+        if imported > 0 {
+            storage.schedule_save();
+        }
+        info!("Imported {} of {} entries ({} duplicates)", imported, results.len(), duplicates);
+    }
+    Ok(ImportResponse {
+        imported,
+        duplicates,
+        failed: results.len() - imported - duplicates,
+        results,
+        missing_batches: Vec::new(),
+    })
+}
+
+#[post("/totp/import-uri")]
+async fn import_otpauth_uris(
     session: Session,
+    data: web::Json<ImportUriRequest>,
     storage: web::Data<Storage>,
 ) -> impl Responder {
     if !auth::check_auth(&session) {
@@ -440,14 +850,22 @@ async fn list_totp_entries(
         });
     }
     
-    let entries = handle_storage_result!(storage.get_totp_entries());
-    HttpResponse::Ok().json(entries)
+    // 每个字符串可以包含多行 URI
+    let parsed = data.uris.iter()
+        .flat_map(|u| u.lines())
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+        .map(totp_manager::parse_otpauth_uri)
+        .collect();
+    
+    let response = handle_storage_result!(import_entries(&storage, parsed, false).await);
+    HttpResponse::Ok().json(response)
 }
 
-#[post("/totp/delete")]
-async fn delete_totp_entry(
+#[post("/totp/import-migration")]
+async fn import_migration(
     session: Session,
-    data: web::Json<DeleteTotpRequest>,
+    data: web::Json<ImportUriRequest>,
     storage: web::Data<Storage>,
 ) -> impl Responder {
     if !auth::check_auth(&session) {
@@ -457,12 +875,132 @@ async fn delete_totp_entry(
         });
     }
     
-    let deleted = handle_storage_result!(storage.delete_totp_entry(&data.id));
-    if deleted {
-        if let Err(e) = storage.save().await {
-            error!("Failed to save: {}", e);
+    // 多批次导出的每个二维码对应一个 URI，按顺序展开为条目列表
+    let mut batches = Vec::new();
+    for uri in data.uris.iter().flat_map(|u| u.lines()).map(str::trim).filter(|u| !u.is_empty()) {
+        match migration::parse_migration_uri(uri) {
+            Ok(batch) => {
+                info!(
+                    "Decoded migration batch {}/{} (id {}) with {} entries",
+                    batch.batch_index + 1,
+                    batch.batch_size,
+                    batch.batch_id,
+                    batch.entries.len()
+                );
+                batches.push(batch);
+            }
+            Err(e) => {
+                return HttpResponse::BadRequest().json(ApiResponse {
+                    success: false,
+                    message: e.to_string(),
+                });
+            }
         }
-        HttpResponse::Ok().json(ApiResponse {
+    }
+    
+    // 缺少的批次在响应中返回，由用户补充扫描；已提交批次中的条目照常导入
+    let missing_batches = migration::missing_batches(&batches);
+    for missing in &missing_batches {
+        warn!(
+            "Migration export {} is incomplete: {} of {} batches provided",
+            missing.batch_id,
+            missing.received,
+            missing.batch_size
+        );
+    }
+    
+    let parsed = batches.into_iter().flat_map(|batch| batch.entries).collect();
+    let mut response = handle_storage_result!(import_entries(&storage, parsed, false).await);
+    response.missing_batches = missing_batches;
+    HttpResponse::Ok().json(response)
+}
+
+// 二维码内容展开为条目：otpauth 对应一个条目，otpauth-migration 对应一批条目
+fn parse_qr_content(content: &str) -> Vec<crate::error::Result<TotpEntry>> {
+    if content.trim().to_lowercase().starts_with("otpauth-migration:") {
+        match migration::parse_migration_uri(content) {
+            Ok(batch) => batch.entries,
+            Err(e) => vec![Err(e)],
+        }
+    } else {
+        vec![totp_manager::parse_otpauth_uri(content)]
+    }
+}
+
+#[post("/totp/scan-qr")]
+async fn scan_qr_image(
+    session: Session,
+    query: web::Query<ScanQrQuery>,
+    body: web::Bytes,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let uris = match totp_manager::decode_qr_codes(&body) {
+        Ok(uris) if uris.is_empty() => {
+            return HttpResponse::Ok().json(ApiResponse {
+                success: false,
+                message: "No QR code found in image".to_string(),
+            });
+        }
+        Ok(uris) => uris,
+        Err(e) => {
+            warn!("Failed to scan QR image: {}", e);
+            return HttpResponse::BadRequest().json(ApiResponse {
+                success: false,
+                message: e.to_string(),
+            });
+        }
+    };
+    
+    let parsed = uris.iter().flat_map(|u| parse_qr_content(u)).collect();
+    let result = handle_storage_result!(import_entries(&storage, parsed, !query.commit).await);
+    
+    HttpResponse::Ok().json(ScanQrResponse {
+        committed: query.commit,
+        uris,
+        result,
+    })
+}
+
+#[get("/totp/list")]
+async fn list_totp_entries(
+    session: Session,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let entries = handle_storage_result!(storage.get_totp_entries().await);
+    HttpResponse::Ok().json(entries)
+}
+
+#[post("/totp/delete")]
+async fn delete_totp_entry(
+    session: Session,
+    data: web::Json<DeleteTotpRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let deleted = handle_storage_result!(storage.delete_totp_entry(&data.id).await);
+    if deleted {
+        storage.schedule_save();
+        HttpResponse::Ok().json(ApiResponse {
             success: true,
             message: "Entry deleted successfully".to_string(),
         })
@@ -474,6 +1012,64 @@ async fn delete_totp_entry(
     }
 }
 
+#[post("/totp/export/{id}")]
+async fn export_totp_entry(
+    session: Session,
+    id: web::Path<String>,
+    data: web::Json<ExportEntryRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    // 导出会暴露密钥，需要再次确认登录密码
+    let user = handle_storage_result!(storage.get_user().await);
+    if !verify_password(data.password.expose_secret(), &user.password_hash) {
+        warn!("Entry export rejected: invalid password");
+        return HttpResponse::Ok().json(ApiResponse {
+            success: false,
+            message: "Invalid password".to_string(),
+        });
+    }
+    
+    let entry = match handle_storage_result!(storage.get_totp_entry(&id).await) {
+        Some(entry) => entry,
+        None => {
+            return HttpResponse::NotFound().json(ApiResponse {
+                success: false,
+                message: "Entry not found".to_string(),
+            });
+        }
+    };
+    
+    let otpauth_uri = totp_manager::build_otpauth_uri(&entry);
+    let qr_code = match data.format {
+        QrImageFormat::Svg => totp_manager::render_qr_svg(&otpauth_uri),
+        QrImageFormat::Png => totp_manager::render_qr_png(&otpauth_uri),
+    };
+    
+    match qr_code {
+        Ok(qr_code) => {
+            info!("Exported entry {}", entry.id);
+            HttpResponse::Ok().json(ExportEntryResponse {
+                otpauth_uri: otpauth_uri.into(),
+                qr_code,
+            })
+        }
+        Err(e) => {
+            error!("Failed to generate QR code: {}", e);
+            HttpResponse::InternalServerError().json(ApiResponse {
+                success: false,
+                message: "Failed to generate QR code".to_string(),
+            })
+        }
+    }
+}
+
 #[get("/totp/generate/{id}")]
 async fn generate_totp_code(
     session: Session,
@@ -487,30 +1083,434 @@ async fn generate_totp_code(
         });
     }
     
-    match handle_storage_result!(storage.get_totp_entry(&id)) {
-        Some(entry) => {
-            match totp_manager::generate_totp_code(&entry.secret) {
-                Ok((code, remaining)) => {
-                    HttpResponse::Ok().json(TotpCodeResponse {
-                        code,
-                        remaining_seconds: remaining,
-                    })
-                }
-                Err(e) => {
-                    error!("Failed to generate TOTP code: {}", e);
-                    HttpResponse::InternalServerError().json(ApiResponse {
-                        success: false,
-                        message: "Failed to generate code".to_string(),
-                    })
-                }
-            }
+    let entry = match handle_storage_result!(storage.get_totp_entry(&id).await) {
+        Some(entry) => entry,
+        None => {
+            return HttpResponse::NotFound().json(ApiResponse {
+                success: false,
+                message: "Entry not found".to_string(),
+            });
+        }
+    };
+    
+    if entry.otp_type == OtpType::Hotp {
+        return generate_hotp_code(&entry.id, &storage).await;
+    }
+    
+    match totp_manager::generate_totp_code(&entry) {
+        Ok((code, remaining)) => {
+            HttpResponse::Ok().json(TotpCodeResponse {
+                code,
+                otp_type: OtpType::Totp,
+                remaining_seconds: Some(remaining),
+                period: Some(entry.period),
+                counter: None,
+            })
         }
+        Err(e) => {
+            error!("Failed to generate TOTP code: {}", e);
+            HttpResponse::InternalServerError().json(ApiResponse {
+                success: false,
+                message: "Failed to generate code".to_string(),
+            })
+        }
+    }
+}
+
+// HOTP：在锁内取出并递增计数器，持久化成功后才返回验证码
+async fn generate_hotp_code(id: &str, storage: &Storage) -> HttpResponse {
+    let taken = handle_storage_result!(storage.update_totp_entry(id, |e| {
+        let counter = e.counter;
+        e.counter += 1;
+        (e.clone(), counter)
+    }).await);
+    let (entry, counter) = match taken {
+        Some(taken) => taken,
         None => {
-            HttpResponse::NotFound().json(ApiResponse {
+            return HttpResponse::NotFound().json(ApiResponse {
                 success: false,
                 message: "Entry not found".to_string(),
+            });
+        }
+    };
+    
+    if let Err(e) = storage.save().await {
+        error!("Failed to save HOTP counter: {}", e);
+        return HttpResponse::InternalServerError().json(ApiResponse {
+            success: false,
+            message: "Failed to save changes".to_string(),
+        });
+    }
+    
+    match totp_manager::generate_hotp_code(&entry, counter) {
+        Ok(code) => {
+            HttpResponse::Ok().json(TotpCodeResponse {
+                code,
+                otp_type: OtpType::Hotp,
+                remaining_seconds: None,
+                period: None,
+                counter: Some(counter),
+            })
+        }
+        Err(e) => {
+            error!("Failed to generate HOTP code: {}", e);
+            HttpResponse::InternalServerError().json(ApiResponse {
+                success: false,
+                message: "Failed to generate code".to_string(),
+            })
+        }
+    }
+}
+
+// HOTP 计数器重同步的前向搜索窗口
+const HOTP_RESYNC_WINDOW: u64 = 100;
+
+#[post("/totp/resync")]
+async fn resync_hotp_counter(
+    session: Session,
+    data: web::Json<ResyncHotpRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let entry = match handle_storage_result!(storage.get_totp_entry(&data.id).await) {
+        Some(entry) if entry.otp_type == OtpType::Hotp => entry,
+        Some(_) => {
+            return HttpResponse::BadRequest().json(ApiResponse {
+                success: false,
+                message: "Entry is not an HOTP entry".to_string(),
+            });
+        }
+        None => {
+            return HttpResponse::NotFound().json(ApiResponse {
+                success: false,
+                message: "Entry not found".to_string(),
+            });
+        }
+    };
+    
+    let counter = match totp_manager::find_hotp_counter(
+        &entry,
+        data.code1.trim(),
+        data.code2.trim(),
+        HOTP_RESYNC_WINDOW,
+    ) {
+        Ok(Some(counter)) => counter,
+        Ok(None) => {
+            return HttpResponse::Ok().json(ApiResponse {
+                success: false,
+                message: "Codes do not match any counter in the resync window".to_string(),
+            });
+        }
+        Err(e) => {
+            error!("Failed to resync HOTP counter: {}", e);
+            return HttpResponse::InternalServerError().json(ApiResponse {
+                success: false,
+                message: "Failed to resync counter".to_string(),
+            });
+        }
+    };
+    
+    // 两个验证码都已被令牌使用，下一个可用的计数器是 counter + 2
+    let next_counter = counter + 2;
+    handle_storage_result!(storage.update_totp_entry(&entry.id, |e| {
+        e.counter = next_counter;
+    }).await);
+    
+    if let Err(e) = storage.save().await {
+        error!("Failed to save: {}", e);
+        return HttpResponse::InternalServerError().json(ApiResponse {
+            success: false,
+            message: "Failed to save changes".to_string(),
+        });
+    }
+    
+    info!("HOTP counter resynchronised for entry {}", entry.id);
+    HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: format!("Counter resynchronised to {}", next_counter),
+    })
+}
+
+#[post("/vault/export")]
+async fn export_vault(
+    session: Session,
+    data: web::Json<VaultExportRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let user = handle_storage_result!(storage.get_user().await);
+    if !verify_password(data.password.expose_secret(), &user.password_hash) {
+        warn!("Vault export rejected: invalid password");
+        return HttpResponse::Ok().json(ApiResponse {
+            success: false,
+            message: "Invalid password".to_string(),
+        });
+    }
+    
+    if data.export_password.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse {
+            success: false,
+            message: "Export password must not be empty".to_string(),
+        });
+    }
+    
+    let entries = handle_storage_result!(storage.get_totp_entries().await);
+    let params = handle_storage_result!(storage.select_kdf_params().await);
+    let count = entries.len();
+    let export_password = data.export_password.clone();
+    match blocking(move || export::export_entries(&entries, export_password.expose_secret(), params)).await {
+        Ok(file) => {
+            info!("Exported {} entries", count);
+            let filename = format!("web-totp-export-{}.json", chrono::Utc::now().format("%Y%m%d-%H%M%S"));
+            HttpResponse::Ok()
+                .content_type("application/json")
+                .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", filename)))
+                .body(file)
+        }
+        Err(e) => {
+            error!("Failed to export vault: {}", e);
+            HttpResponse::InternalServerError().json(ApiResponse {
+                success: false,
+                message: "Failed to export vault".to_string(),
+            })
+        }
+    }
+}
+
+#[post("/vault/import")]
+async fn import_vault(
+    session: Session,
+    data: web::Json<VaultImportRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let user = handle_storage_result!(storage.get_user().await);
+    if !verify_password(data.password.expose_secret(), &user.password_hash) {
+        warn!("Vault import rejected: invalid password");
+        return HttpResponse::Ok().json(ApiResponse {
+            success: false,
+            message: "Invalid password".to_string(),
+        });
+    }
+    
+    let (file, export_password) = (data.data.clone(), data.export_password.clone());
+    let incoming = match blocking(move || export::import_entries(&file, export_password.expose_secret())).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to read export file: {}", e);
+            return HttpResponse::BadRequest().json(ApiResponse {
+                success: false,
+                message: e.to_string(),
+            });
+        }
+    };
+    
+    let existing = handle_storage_result!(storage.get_totp_entries().await);
+    let plan = export::plan_import(&existing, incoming, data.mode);
+    
+    if !data.dry_run {
+        match data.mode {
+            VaultImportMode::Merge => {
+                for entry in plan.to_add {
+                    handle_storage_result!(storage.add_totp_entry(entry).await);
+                }
+            }
+            VaultImportMode::Replace => {
+                handle_storage_result!(storage.replace_totp_entries(plan.to_add).await);
+            }
+        }
+        
+        if let Err(e) = storage.save().await {
+            error!("Failed to save: {}", e);
+            return HttpResponse::InternalServerError().json(ApiResponse {
+                success: false,
+                message: "Failed to save changes".to_string(),
+            });
+        }
+        info!(
+            "Vault import ({:?}): {} added, {} duplicates, {} conflicts, {} removed",
+            data.mode,
+            plan.added.len(),
+            plan.duplicates.len(),
+            plan.conflicts.len(),
+            plan.removed
+        );
+    }
+    
+    HttpResponse::Ok().json(VaultImportReport {
+        dry_run: data.dry_run,
+        mode: data.mode,
+        added: plan.added,
+        duplicates: plan.duplicates,
+        conflicts: plan.conflicts,
+        removed: plan.removed,
+    })
+}
+
+#[post("/backup/import")]
+async fn import_backup(
+    session: Session,
+    data: web::Json<BackupImportRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let bytes = Zeroizing::new(if data.base64 {
+        match general_purpose::STANDARD.decode(data.data.expose_secret().trim()) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(ApiResponse {
+                    success: false,
+                    message: format!("Invalid Base64 data: {}", e),
+                });
+            }
+        }
+    } else {
+        data.data.expose_secret().as_bytes().to_vec()
+    });
+    
+    let format = data.format;
+    let password = data.backup_password.clone().filter(|p| !p.is_empty());
+    let parsed = match blocking(move || formats::import(format, &bytes, password.as_ref().map(|p| p.expose_secret()))).await {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            warn!("Failed to read {:?} backup: {}", data.format, e);
+            return HttpResponse::BadRequest().json(ApiResponse {
+                success: false,
+                message: e.to_string(),
+            });
+        }
+    };
+    
+    let response = handle_storage_result!(import_entries(&storage, parsed, data.dry_run).await);
+    HttpResponse::Ok().json(response)
+}
+
+#[post("/backup/export")]
+async fn export_backup(
+    session: Session,
+    data: web::Json<BackupExportRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let user = handle_storage_result!(storage.get_user().await);
+    if !verify_password(data.password.expose_secret(), &user.password_hash) {
+        warn!("Backup export rejected: invalid password");
+        return HttpResponse::Ok().json(ApiResponse {
+            success: false,
+            message: "Invalid password".to_string(),
+        });
+    }
+    
+    let format = data.format;
+    let password = data.backup_password.clone().filter(|p| !p.is_empty());
+    let encrypted = password.is_some();
+    let entries = handle_storage_result!(storage.get_totp_entries().await);
+    let count = entries.len();
+    match blocking(move || formats::export(format, &entries, password.as_ref().map(|p| p.expose_secret()))).await {
+        Ok(file) => {
+            info!("Exported {} entries as {:?} backup", count, format);
+            let filename = formats::file_name(format, encrypted);
+            let content_type = if filename.ends_with(".aes") || filename.ends_with(".kdbx") {
+                "application/octet-stream"
+            } else {
+                "application/json"
+            };
+            HttpResponse::Ok()
+                .content_type(content_type)
+                .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", filename)))
+                .body(file)
+        }
+        Err(e) => {
+            warn!("Failed to export {:?} backup: {}", data.format, e);
+            HttpResponse::BadRequest().json(ApiResponse {
+                success: false,
+                message: e.to_string(),
             })
         }
     }
 }
 
+#[get("/backups")]
+async fn list_vault_backups(
+    session: Session,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let backups = handle_storage_result!(storage.list_backups().await);
+    HttpResponse::Ok().json(backups)
+}
+
+#[post("/backups/restore")]
+async fn restore_vault_backup(
+    session: Session,
+    data: web::Json<RestoreBackupRequest>,
+    storage: web::Data<Storage>,
+) -> impl Responder {
+    if !auth::check_auth(&session) {
+        return HttpResponse::Unauthorized().json(ApiResponse {
+            success: false,
+            message: "Not authenticated".to_string(),
+        });
+    }
+    
+    let backup_password = data.backup_password.as_ref()
+        .filter(|p| !p.is_empty())
+        .map(|p| p.expose_secret());
+    let result = storage.restore_backup(&data.id, data.master_password.expose_secret(), backup_password).await;
+    if let Err(crate::error::AppError::InvalidMasterPassword) = result {
+        warn!("Backup restore rejected: invalid master password");
+        return HttpResponse::Ok().json(ApiResponse {
+            success: false,
+            message: "Invalid master password".to_string(),
+        });
+    }
+    
+    if !handle_storage_result!(result) {
+        return HttpResponse::NotFound().json(ApiResponse {
+            success: false,
+            message: "Backup not found".to_string(),
+        });
+    }
+    
+    info!("Vault restored from backup {}", data.id);
+    HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Backup restored successfully".to_string(),
+    })
+}
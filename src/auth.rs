@@ -1,4 +1,12 @@
-use actix_session::Session;
+use actix_session::{Session, SessionExt};
+use actix_web::HttpRequest;
+use crate::storage::Storage;
+
+// 前端定时刷新验证码的请求带有此请求头，不算作用户活动
+pub const AUTO_REFRESH_HEADER: &str = "X-Auto-Refresh";
+
+// 只查询状态的接口，不算作用户活动
+const STATUS_PATHS: &[&str] = &["/api/lock-status", "/api/check-session"];
 
 pub fn check_auth(session: &Session) -> bool {
     session.get::<String>("username").unwrap_or(None).is_some()
@@ -12,3 +20,13 @@ pub fn clear_session(session: &Session) {
     session.purge();
 }
 
+
+// 已登录用户的请求重置自动锁定的空闲计时（请求处理完后检查，登录请求本身也算活动）
+pub fn track_activity(req: &HttpRequest, storage: &Storage) {
+    if req.headers().contains_key(AUTO_REFRESH_HEADER) || STATUS_PATHS.contains(&req.path()) {
+        return;
+    }
+    if check_auth(&req.get_session()) {
+        storage.touch();
+    }
+}
@@ -0,0 +1,332 @@
+// 单个加密文件（data.enc），备份保存在 data.enc.backups/ 目录
+//
+// 打开时对 data.enc.lock 加锁，同一个数据文件只能被一个进程使用。
+// 读取或写入后记录文件的长度、修改时间和 SHA-256，写入前确认文件没有被其他程序改动过，
+// 避免覆盖更新的数据
+
+use super::lockfile::LockFile;
+use super::{decrypt_vault, encrypt_vault, quarantine_path, verify_vault, Decrypted, StorageBackend};
+use crate::backup::{self, BackupPolicy};
+use crate::crypto::MasterKey;
+use crate::error::{AppError, Result};
+use crate::file_format;
+use crate::models::{AppData, BackupInfo};
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+const BACKUP_EXTENSION: &str = ".enc";
+
+// 最近一次读取或写入时的文件状态
+struct Fingerprint {
+    len: u64,
+    modified: Option<SystemTime>,
+    hash: [u8; 32],
+}
+
+impl Fingerprint {
+    fn of(path: &str, contents: &[u8]) -> Self {
+        let modified = fs::metadata(path).ok().and_then(|m| m.modified().ok());
+        Self {
+            len: contents.len() as u64,
+            modified,
+            hash: Sha256::digest(contents).into(),
+        }
+    }
+}
+
+pub struct FileBackend {
+    path: String,
+    backup_policy: BackupPolicy,
+    _lock: LockFile,
+    // 尚未读取或写入过时为 None
+    fingerprint: Mutex<Option<Fingerprint>>,
+}
+
+impl FileBackend {
+    pub fn new(path: &str, backup_policy: BackupPolicy) -> Result<Self> {
+        let lock = LockFile::acquire(path)?;
+        if fs::metadata(path).is_err() {
+            info!("Data file not found, will create new one");
+        }
+
+        // 上次写入中途中断留下的临时文件，data.enc 仍是中断前的完整版本
+        let backend = Self {
+            path: path.to_string(),
+            backup_policy,
+            _lock: lock,
+            fingerprint: Mutex::new(None),
+        };
+        let tmp_path = backend.tmp_path();
+        if fs::metadata(&tmp_path).is_ok() {
+            warn!("Removing incomplete temporary file from an interrupted write: {}", tmp_path);
+            fs::remove_file(&tmp_path)
+                .map_err(|e| AppError::Storage(format!("Failed to remove temporary file: {}", e)))?;
+        }
+        Ok(backend)
+    }
+
+    fn tmp_path(&self) -> String {
+        format!("{}.tmp", self.path)
+    }
+
+    // 数据文件不存在时返回 None
+    fn read(&self) -> Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(AppError::Storage(format!("Failed to read data file: {}", e))),
+        }
+    }
+
+    // 文件在上次读取或写入之后被其他程序修改（或在新建前已被创建）时返回 ModifiedExternally。
+    // 长度和修改时间都没变时不重新计算哈希
+    fn check_unmodified(&self) -> Result<()> {
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return Ok(());
+        };
+        let mut fingerprint = self.fingerprint.lock().unwrap();
+        let Some(known) = fingerprint.as_mut() else {
+            return Err(AppError::ModifiedExternally);
+        };
+        let modified = metadata.modified().ok();
+        if metadata.len() == known.len && modified.is_some() && modified == known.modified {
+            return Ok(());
+        }
+
+        let contents = fs::read(&self.path)
+            .map_err(|e| AppError::Storage(format!("Failed to read data file: {}", e)))?;
+        if <[u8; 32]>::from(Sha256::digest(&contents)) != known.hash {
+            warn!("{} was modified by another process, refusing to overwrite it", self.path);
+            return Err(AppError::ModifiedExternally);
+        }
+        known.modified = modified;
+        Ok(())
+    }
+
+    // 崩溃安全的写入：
+    // 1. 写入同目录下的临时文件并 fsync
+    // 2. 读回临时文件，确认能用本次的密钥解密出完整数据
+    // 3. rename 覆盖 data.enc（同一文件系统内为原子操作），再 fsync 所在目录
+    // 任何一步失败都会删除临时文件，原来的 data.enc 保持不变
+    fn write_file(&self, encrypted_data: &[u8], key: &MasterKey) -> Result<()> {
+        debug!("Saving encrypted data");
+        let tmp_path = self.tmp_path();
+
+        if let Err(e) = Self::write_and_verify(&tmp_path, encrypted_data, key) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+        if let Err(e) = fs::rename(&tmp_path, &self.path) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(AppError::Storage(format!("Failed to replace data file: {}", e)));
+        }
+        self.sync_parent_dir()?;
+
+        info!("Data saved successfully");
+        Ok(())
+    }
+
+    fn write_and_verify(tmp_path: &str, encrypted_data: &[u8], key: &MasterKey) -> Result<()> {
+        let mut file = fs::File::create(tmp_path)
+            .map_err(|e| AppError::Storage(format!("Failed to create temporary file: {}", e)))?;
+        file.write_all(encrypted_data)
+            .map_err(|e| AppError::Storage(format!("Failed to write file: {}", e)))?;
+        file.sync_all()
+            .map_err(|e| AppError::Storage(format!("Failed to sync file: {}", e)))?;
+        drop(file);
+
+        let written = fs::read(tmp_path)
+            .map_err(|e| AppError::Storage(format!("Failed to read back temporary file: {}", e)))?;
+        verify_vault(&written, key)
+            .map_err(|e| AppError::Storage(format!("Written data failed verification: {}", e)))
+    }
+
+    // rename 本身只有在目录项落盘后才算持久化
+    #[cfg(unix)]
+    fn sync_parent_dir(&self) -> Result<()> {
+        let parent = Path::new(&self.path)
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let dir = fs::File::open(parent)
+            .map_err(|e| AppError::Storage(format!("Failed to open data directory: {}", e)))?;
+        dir.sync_all()
+            .map_err(|e| AppError::Storage(format!("Failed to sync data directory: {}", e)))
+    }
+
+    #[cfg(not(unix))]
+    fn sync_parent_dir(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl StorageBackend for FileBackend {
+    fn describe(&self) -> String {
+        format!("file {}", self.path)
+    }
+
+    fn load(&self, password: &str) -> Result<Option<Decrypted>> {
+        let Some(encrypted_data) = self.read()? else {
+            *self.fingerprint.lock().unwrap() = None;
+            return Ok(None);
+        };
+        let decrypted = decrypt_vault(&encrypted_data, password)?;
+        *self.fingerprint.lock().unwrap() = Some(Fingerprint::of(&self.path, &encrypted_data));
+        Ok(Some(decrypted))
+    }
+
+    fn inspect(&self, password: &str) -> Result<Option<Decrypted>> {
+        self.read()?.map(|bytes| decrypt_vault(&bytes, password)).transpose()
+    }
+
+    fn has_vault(&self) -> Result<bool> {
+        Ok(fs::metadata(&self.path).is_ok())
+    }
+
+    fn store(&self, data: &AppData, key: &MasterKey) -> Result<()> {
+        self.check_unmodified()?;
+        let encrypted_data = encrypt_vault(data, key)?;
+        self.write_file(&encrypted_data, key)?;
+        *self.fingerprint.lock().unwrap() = Some(Fingerprint::of(&self.path, &encrypted_data));
+        Ok(())
+    }
+
+    fn create_backup(&self, force: bool) -> Result<()> {
+        backup::create(&self.path, BACKUP_EXTENSION, &self.backup_policy, force, |backup_path| {
+            fs::copy(&self.path, backup_path)?;
+            Ok(())
+        })
+    }
+
+    fn list_backups(&self) -> Result<Vec<BackupInfo>> {
+        backup::list(&self.path, BACKUP_EXTENSION)
+    }
+
+    fn load_backup(&self, id: &str, password: &str) -> Result<Option<Decrypted>> {
+        let Ok(backup_path) = backup::path_for(&self.path, id, BACKUP_EXTENSION) else {
+            return Ok(None);
+        };
+        let encrypted_backup = match fs::read(&backup_path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(AppError::Storage(format!("Failed to read backup: {}", e))),
+        };
+        Ok(Some(decrypt_vault(&encrypted_backup, password)?))
+    }
+
+    // 旧格式、旧结构或 KDF 参数过弱的文件重写前保留一份副本
+    fn preserve_before_upgrade(&self) -> Result<()> {
+        let backup_path = format!("{}.pre-upgrade.bak", self.path);
+        fs::copy(&self.path, &backup_path)
+            .map_err(|e| AppError::Storage(format!("Failed to back up data file: {}", e)))?;
+        info!("Upgrading data file to format version {}, previous file kept at {}",
+            file_format::CURRENT_VERSION, backup_path);
+        Ok(())
+    }
+
+    fn quarantine(&self) -> Result<Option<String>> {
+        *self.fingerprint.lock().unwrap() = None;
+        if fs::metadata(&self.path).is_err() {
+            return Ok(None);
+        }
+        let target = quarantine_path(&self.path);
+        fs::rename(&self.path, &target)
+            .map_err(|e| AppError::Storage(format!("Failed to move damaged data file: {}", e)))?;
+        self.sync_parent_dir()?;
+        warn!("Moved damaged data file to {}", target);
+        Ok(Some(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SlotKind;
+    use crate::testutil::{sample_entry, TempDir, NO_BACKUPS, TEST_PARAMS};
+
+    // 写入只有一个条目的保险库，返回写入后 data.enc 的内容
+    fn store_sample(backend: &FileBackend) -> (AppData, MasterKey, Vec<u8>) {
+        let mut data = AppData::default();
+        data.totp_entries.push(sample_entry("alice"));
+        let mut key = MasterKey::generate();
+        key.add_slot(SlotKind::Password, "", "pw", TEST_PARAMS).unwrap();
+        backend.store(&data, &key).unwrap();
+        (data, key, fs::read(&backend.path).unwrap())
+    }
+
+    fn assert_unchanged(backend: &FileBackend, original: &[u8]) {
+        assert_eq!(fs::read(&backend.path).unwrap(), original);
+        assert!(fs::metadata(backend.tmp_path()).is_err());
+        let loaded = backend.load("pw").unwrap().unwrap();
+        assert_eq!(loaded.data.totp_entries.len(), 1);
+    }
+
+    #[test]
+    fn store_replaces_data_file() {
+        let dir = TempDir::new();
+        let backend = FileBackend::new(&dir.file("data.enc"), NO_BACKUPS).unwrap();
+        let (mut data, key, original) = store_sample(&backend);
+
+        data.totp_entries.clear();
+        backend.store(&data, &key).unwrap();
+        assert_ne!(fs::read(&backend.path).unwrap(), original);
+        assert!(fs::metadata(backend.tmp_path()).is_err());
+        assert!(backend.load("pw").unwrap().unwrap().data.totp_entries.is_empty());
+    }
+
+    #[test]
+    fn failed_verification_keeps_data_file() {
+        let dir = TempDir::new();
+        let backend = FileBackend::new(&dir.file("data.enc"), NO_BACKUPS).unwrap();
+        let (mut data, key, original) = store_sample(&backend);
+        data.totp_entries.clear();
+
+        // 写出的内容损坏
+        let mut corrupted = encrypt_vault(&data, &key).unwrap();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(matches!(backend.write_file(&corrupted, &key), Err(AppError::Storage(_))));
+        assert_unchanged(&backend, &original);
+
+        // 写出的内容无法用本次的密钥解密
+        let encrypted = encrypt_vault(&data, &key).unwrap();
+        assert!(matches!(backend.write_file(&encrypted, &MasterKey::generate()), Err(AppError::Storage(_))));
+        assert_unchanged(&backend, &original);
+    }
+
+    #[test]
+    fn failed_temporary_write_keeps_data_file() {
+        let dir = TempDir::new();
+        let backend = FileBackend::new(&dir.file("data.enc"), NO_BACKUPS).unwrap();
+        let (mut data, key, original) = store_sample(&backend);
+
+        // 临时文件无法创建（以 root 运行时只读权限不起作用，这里用同名目录占住路径）
+        fs::create_dir(backend.tmp_path()).unwrap();
+        data.totp_entries.clear();
+        assert!(matches!(backend.store(&data, &key), Err(AppError::Storage(_))));
+        assert_eq!(fs::read(&backend.path).unwrap(), original);
+        fs::remove_dir(backend.tmp_path()).unwrap();
+        assert_unchanged(&backend, &original);
+    }
+
+    #[test]
+    fn interrupted_write_is_discarded_on_open() {
+        let dir = TempDir::new();
+        let path = dir.file("data.enc");
+        let backend = FileBackend::new(&path, NO_BACKUPS).unwrap();
+        let (mut data, key, original) = store_sample(&backend);
+        drop(backend);
+
+        // 写到一半时进程退出，只留下不完整的临时文件
+        data.totp_entries.clear();
+        let encrypted = encrypt_vault(&data, &key).unwrap();
+        fs::write(format!("{}.tmp", path), &encrypted[..encrypted.len() / 2]).unwrap();
+
+        let backend = FileBackend::new(&path, NO_BACKUPS).unwrap();
+        assert_unchanged(&backend, &original);
+    }
+}
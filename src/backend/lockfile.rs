@@ -0,0 +1,72 @@
+// 进程间互斥：打开数据文件时对 <数据文件>.lock 加排他的建议锁，并写入当前进程号。
+// 锁随 LockFile 一起释放（包括进程异常退出），锁文件本身保留，不需要手动清理
+
+use crate::error::{AppError, Result};
+use log::debug;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+pub struct LockFile {
+    _file: File,
+}
+
+impl LockFile {
+    pub fn acquire(data_path: &str) -> Result<Self> {
+        let path = format!("{}.lock", data_path);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| AppError::Storage(format!("Failed to open lock file {}: {}", path, e)))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut holder = String::new();
+                let _ = file.read_to_string(&mut holder);
+                let holder = holder.trim();
+                return Err(AppError::VaultInUse(if holder.is_empty() {
+                    data_path.to_string()
+                } else {
+                    format!("{} (pid {})", data_path, holder)
+                }));
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(AppError::Storage(format!("Failed to lock {}: {}", path, e)));
+            }
+        }
+
+        // 记录持有锁的进程，便于在另一个进程的错误信息中显示
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{}", std::process::id())?;
+        debug!("Acquired lock {}", path);
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn second_lock_fails_until_first_is_released() {
+        let dir = TempDir::new();
+        let data_path = dir.file("data.enc");
+
+        let first = LockFile::acquire(&data_path).unwrap();
+        let pid = std::fs::read_to_string(format!("{}.lock", data_path)).unwrap();
+        assert_eq!(pid, std::process::id().to_string());
+
+        match LockFile::acquire(&data_path) {
+            Err(AppError::VaultInUse(holder)) => assert!(holder.contains(&format!("(pid {})", pid)), "{}", holder),
+            other => panic!("expected VaultInUse, got {:?}", other.err()),
+        }
+
+        drop(first);
+        LockFile::acquire(&data_path).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+// 只保存在内存中的后端：内容与 data.enc 相同（加密后的数据块），进程退出即丢失。
+// 用于测试和演示，不接触文件系统
+
+use super::{decrypt_vault, encrypt_vault, verify_vault, Decrypted, StorageBackend};
+use crate::backup::{self, BackupPolicy};
+use crate::crypto::MasterKey;
+use crate::error::Result;
+use crate::models::{AppData, BackupInfo};
+use chrono::{DateTime, Utc};
+use std::sync::Mutex;
+
+struct MemoryBackup {
+    id: String,
+    created_at: DateTime<Utc>,
+    data: Vec<u8>,
+}
+
+pub struct MemoryBackend {
+    vault: Mutex<Option<Vec<u8>>>,
+    // 从新到旧排列
+    backups: Mutex<Vec<MemoryBackup>>,
+    backup_policy: BackupPolicy,
+}
+
+impl MemoryBackend {
+    pub fn new(backup_policy: BackupPolicy) -> Self {
+        Self {
+            vault: Mutex::new(None),
+            backups: Mutex::new(Vec::new()),
+            backup_policy,
+        }
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn describe(&self) -> String {
+        "memory (not persisted)".to_string()
+    }
+
+    fn load(&self, password: &str) -> Result<Option<Decrypted>> {
+        let vault = self.vault.lock().unwrap().clone();
+        vault.map(|bytes| decrypt_vault(&bytes, password)).transpose()
+    }
+
+    fn inspect(&self, password: &str) -> Result<Option<Decrypted>> {
+        self.load(password)
+    }
+
+    fn has_vault(&self) -> Result<bool> {
+        Ok(self.vault.lock().unwrap().is_some())
+    }
+
+    fn store(&self, data: &AppData, key: &MasterKey) -> Result<()> {
+        let encrypted_data = encrypt_vault(data, key)?;
+        verify_vault(&encrypted_data, key)?;
+        *self.vault.lock().unwrap() = Some(encrypted_data);
+        Ok(())
+    }
+
+    fn create_backup(&self, force: bool) -> Result<()> {
+        let Some(current) = self.vault.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        let now = Utc::now();
+        let mut backups = self.backups.lock().unwrap();
+        if !self.backup_policy.is_due(backups.first().map(|b| b.created_at), now, force) {
+            return Ok(());
+        }
+
+        // 与 file 后端一样，创建时间取 ID 中的时间戳（精确到毫秒）
+        let id = backup::new_id(now);
+        let created_at = backup::parse_id(&id).unwrap_or(now);
+        backups.insert(0, MemoryBackup { id, created_at, data: current });
+        let mut index = 0;
+        backups.retain(|b| {
+            index += 1;
+            !self.backup_policy.should_remove(index - 1, b.created_at, now)
+        });
+        Ok(())
+    }
+
+    fn list_backups(&self) -> Result<Vec<BackupInfo>> {
+        Ok(self.backups.lock().unwrap().iter().map(|b| BackupInfo {
+            id: b.id.clone(),
+            created_at: b.created_at.to_rfc3339(),
+            size: b.data.len() as u64,
+        }).collect())
+    }
+
+    fn load_backup(&self, id: &str, password: &str) -> Result<Option<Decrypted>> {
+        let backup = self.backups.lock().unwrap()
+            .iter()
+            .find(|b| b.id == id)
+            .map(|b| b.data.clone());
+        backup.map(|bytes| decrypt_vault(&bytes, password)).transpose()
+    }
+
+    // 进程退出后数据不保留，升级前不需要留副本
+    fn preserve_before_upgrade(&self) -> Result<()> {
+        Ok(())
+    }
+
+    // 没有地方可以保留，直接丢弃
+    fn quarantine(&self) -> Result<Option<String>> {
+        self.vault.lock().unwrap().take();
+        Ok(None)
+    }
+}
@@ -0,0 +1,342 @@
+// 存储后端：保存加密后的保险库以及它的备份
+//
+// Storage 负责解锁状态、内存中的数据和写入时机，后端只负责持久化：
+//   load(password)              读取并用主密码（或其他密钥槽的秘密）解密；还没有保险库时返回 None
+//   inspect(password)           与 load 相同，但不记录读取状态，用于完整性检查
+//   has_vault()                 是否已经有保险库（不需要密码）
+//   store(data, key)            用 key 加密并完整写入
+//   create_backup(force)        覆盖前保留一份当前内容（按保留策略和备份间隔，force 时忽略间隔）
+//   list_backups / load_backup  列出和读取备份
+//   preserve_before_upgrade()   升级旧格式前保留原始内容
+//   quarantine()                把损坏的数据移到一边（不删除），之后按新保险库写入
+//
+// 后端方法都是同步的，由 Storage 放到阻塞线程池中调用。
+// 持久化的后端在打开时加进程间锁，store 发现数据在读取后被其他程序修改时返回 ModifiedExternally。
+// 通过 WEB_TOTP_STORAGE 选择后端（见 config.rs）：
+//   file    单个加密文件 data.enc（默认）
+//   sqlite  SQLite 数据库 data.db，每个条目单独加密为一行
+//   memory  只保存在内存中，进程退出即丢失，用于测试和演示
+
+mod file;
+mod lockfile;
+mod memory;
+mod sqlite;
+
+pub use file::FileBackend;
+pub use memory::MemoryBackend;
+pub use sqlite::SqliteBackend;
+
+use crate::backup::BackupPolicy;
+use crate::crypto::{self, KeySlot, MasterKey, KEY_SIZE};
+use crate::error::{AppError, Result};
+use crate::file_format::{self, FileHeader, KeySource};
+use crate::models::{AppData, BackupInfo, SCHEMA_VERSION};
+use log::{debug, info};
+use serde_json::Value;
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+pub trait StorageBackend: Send + Sync {
+    // 用于日志，例如数据文件路径
+    fn describe(&self) -> String;
+
+    fn load(&self, password: &str) -> Result<Option<Decrypted>>;
+
+    fn inspect(&self, password: &str) -> Result<Option<Decrypted>>;
+
+    fn has_vault(&self) -> Result<bool>;
+
+    fn store(&self, data: &AppData, key: &MasterKey) -> Result<()>;
+
+    fn create_backup(&self, force: bool) -> Result<()>;
+
+    fn list_backups(&self) -> Result<Vec<BackupInfo>>;
+
+    // 备份不存在或 ID 无效时返回 None
+    fn load_backup(&self, id: &str, password: &str) -> Result<Option<Decrypted>>;
+
+    fn preserve_before_upgrade(&self) -> Result<()>;
+
+    // 返回损坏数据移动到的位置；没有可移动的内容时返回 None
+    fn quarantine(&self) -> Result<Option<String>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    File,
+    Sqlite,
+    Memory,
+}
+
+impl BackendKind {
+    // 未配置 WEB_TOTP_DATA_PATH 时使用的路径
+    fn default_path(self) -> &'static str {
+        match self {
+            BackendKind::File | BackendKind::Memory => "data.enc",
+            BackendKind::Sqlite => "data.db",
+        }
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "file" => Ok(BackendKind::File),
+            "sqlite" => Ok(BackendKind::Sqlite),
+            "memory" => Ok(BackendKind::Memory),
+            _ => Err(AppError::Storage(format!("Unknown storage backend: {}", s))),
+        }
+    }
+}
+
+pub fn open(kind: BackendKind, data_path: Option<&str>, backup_policy: BackupPolicy) -> Result<Box<dyn StorageBackend>> {
+    let data_path = data_path.unwrap_or(kind.default_path());
+    Ok(match kind {
+        BackendKind::File => Box::new(FileBackend::new(data_path, backup_policy)?),
+        BackendKind::Sqlite => Box::new(SqliteBackend::new(data_path, backup_policy)?),
+        BackendKind::Memory => Box::new(MemoryBackend::new(backup_policy)),
+    })
+}
+
+// 解密结果；needs_upgrade 表示格式或结构版本低于当前版本，解锁后需要重写
+pub struct Decrypted {
+    pub data: AppData,
+    pub key: MasterKey,
+    pub needs_upgrade: bool,
+    // 迁移前的结构版本
+    pub schema_version: u32,
+    // 解开数据密钥的密钥槽
+    pub slot_id: String,
+}
+
+// 用 password 取得数据密钥后的结果
+struct OpenedKey {
+    // 解密现有数据使用的密钥：版本 3 即数据密钥，旧版本为主密码派生的密钥
+    data_key: Zeroizing<[u8; KEY_SIZE]>,
+    // 之后写入使用的数据密钥和密钥槽
+    key: MasterKey,
+    slot_id: String,
+    legacy: bool,
+}
+
+impl OpenedKey {
+    // 数据密钥已经由密钥槽认证，数据解密失败说明数据本身损坏；
+    // 旧版本无法区分主密码错误和数据损坏
+    fn decrypt_error(&self, what: &str) -> AppError {
+        if self.legacy {
+            AppError::InvalidMasterPassword
+        } else {
+            AppError::AuthenticationFailed(what.to_string())
+        }
+    }
+}
+
+// 版本 3 依次尝试密钥槽；旧版本由主密码派生，并转换为带密码槽的新数据密钥，
+// 下次写入时按当前格式保存
+fn open_key(header: &FileHeader, slots: Vec<KeySlot>, password: &str) -> Result<OpenedKey> {
+    match &header.key_source {
+        KeySource::Derived { kdf_params, salt, .. } => {
+            let derived = Zeroizing::new(crypto::derive_key(password, salt, kdf_params)?);
+            let (key, slot_id) = MasterKey::from_legacy(&derived, salt, *kdf_params)?;
+            Ok(OpenedKey { data_key: derived, key, slot_id, legacy: true })
+        }
+        KeySource::Slots { key_id } => {
+            let (key, slot_id) = MasterKey::open(*key_id, slots, password)?
+                .ok_or(AppError::InvalidMasterPassword)?;
+            Ok(OpenedKey { data_key: Zeroizing::new(*key.key()), key, slot_id, legacy: false })
+        }
+    }
+}
+
+// 损坏的数据移动到 <数据文件>.damaged-<时间戳>
+fn quarantine_path(data_path: &str) -> String {
+    format!("{}.damaged-{}", data_path, chrono::Utc::now().format("%Y%m%dT%H%M%S"))
+}
+
+// AppData JSON 结构迁移：MIGRATIONS[i] 把版本 i + 1 升级到 i + 2
+//
+// 版本 1：最初的结构，条目只有 id / name / issuer / secret / created_at，没有 schema_version
+// 版本 2：条目增加 algorithm / digits / period / otp_type / counter，顶层增加 schema_version
+type Migration = fn(&mut Value) -> Result<()>;
+
+const MIGRATIONS: [Migration; 1] = [migrate_v1_to_v2];
+
+const _: () = assert!(MIGRATIONS.len() as u32 + 1 == SCHEMA_VERSION);
+
+fn migrate_v1_to_v2(data: &mut Value) -> Result<()> {
+    let entries = data.get_mut("totp_entries")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| AppError::Storage("Missing totp_entries in data file".to_string()))?;
+
+    for entry in entries {
+        let entry = entry.as_object_mut()
+            .ok_or_else(|| AppError::Storage("Invalid TOTP entry in data file".to_string()))?;
+        entry.entry("algorithm").or_insert_with(|| "SHA1".into());
+        entry.entry("digits").or_insert_with(|| 6.into());
+        entry.entry("period").or_insert_with(|| 30.into());
+        entry.entry("otp_type").or_insert_with(|| "totp".into());
+        entry.entry("counter").or_insert_with(|| 0.into());
+    }
+    Ok(())
+}
+
+// 依次执行迁移，返回升级后的数据和原始结构版本
+fn migrate(mut data: Value) -> Result<(AppData, u32)> {
+    let version = match data.get("schema_version") {
+        None => 1,
+        Some(v) => v.as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v >= 1)
+            .ok_or_else(|| AppError::Storage(format!("Invalid schema_version: {}", v)))?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(AppError::Storage(format!(
+            "Data schema version {} is newer than supported version {}",
+            version, SCHEMA_VERSION
+        )));
+    }
+
+    for (index, step) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        step(&mut data)?;
+        data["schema_version"] = (index as u32 + 2).into();
+        info!("Migrated data schema from version {} to {}", index + 1, index + 2);
+    }
+
+    let app_data = serde_json::from_value(data)
+        .map_err(|e| AppError::Storage(format!("Deserialization failed: {}", e)))?;
+    Ok((app_data, version))
+}
+
+// 整个保险库加密为一个 data.enc 格式的数据块（见 file_format.rs），file 和 memory 后端共用
+pub fn encrypt_vault(data: &AppData, key: &MasterKey) -> Result<Vec<u8>> {
+    // 文件头记录数据密钥 ID，密钥槽写在文件头之后
+    let header = FileHeader::new(key.key_id);
+
+    // 序列化数据（明文 JSON 含全部密钥，用完即清零）
+    let json = Zeroizing::new(serde_json::to_vec(data)
+        .map_err(|e| AppError::Storage(format!("Serialization failed: {}", e)))?);
+
+    // 使用 AES-256-GCM 加密，文件头作为附加数据参与认证
+    let aad = header.to_aad();
+    let (nonce_bytes, ciphertext) = crypto::encrypt_with_aad(key.key(), &json, &aad)?;
+
+    debug!("Data encrypted successfully");
+    FileHeader::encode(&aad, &key.slots, &nonce_bytes, &ciphertext)
+}
+
+pub fn decrypt_vault(encrypted_data: &[u8], password: &str) -> Result<Decrypted> {
+    let file = file_format::parse(encrypted_data)?;
+
+    // 解开数据密钥并解密
+    let opened = open_key(&file.header, file.slots, password)?;
+    let plaintext = Zeroizing::new(crypto::decrypt_with_aad(&opened.data_key, &file.nonce, file.ciphertext, file.aad)
+        .map_err(|_| opened.decrypt_error("vault data"))?);
+
+    // 反序列化并升级到当前结构版本
+    let json: Value = serde_json::from_slice(&plaintext)
+        .map_err(|e| AppError::Storage(format!("Deserialization failed: {}", e)))?;
+    let (data, schema_version) = migrate(json)?;
+
+    if file.header.is_legacy() {
+        info!("Data file uses format version {}, will upgrade", file.header.version);
+    }
+    debug!("Data decrypted successfully");
+    Ok(Decrypted {
+        data,
+        key: opened.key,
+        needs_upgrade: file.header.is_legacy() || schema_version < SCHEMA_VERSION,
+        schema_version,
+        slot_id: opened.slot_id,
+    })
+}
+
+// 用写入时的密钥解密刚写出的数据块（不重新派生密钥），确认内容完整
+pub fn verify_vault(written: &[u8], key: &MasterKey) -> Result<()> {
+    let file = file_format::parse(written)?;
+    let plaintext = Zeroizing::new(crypto::decrypt_with_aad(key.key(), &file.nonce, file.ciphertext, file.aad)?);
+    serde_json::from_slice::<AppData>(&plaintext)
+        .map_err(|e| AppError::Storage(format!("Deserialization failed: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{verify_password, OtpAlgorithm, OtpType};
+
+    // tests/fixtures/generate.py 按各历史版本写出的 data.enc，都只有一个 GitHub 条目
+    const V1: &[u8] = include_bytes!("../../tests/fixtures/data-v1-headerless.enc");
+    const V2: &[u8] = include_bytes!("../../tests/fixtures/data-v2-kdf-header.enc");
+    const PASSWORD: &str = "master-test";
+
+    fn assert_sample_user(data: &AppData) {
+        assert_eq!(data.schema_version, SCHEMA_VERSION);
+        assert_eq!(data.user.username, "admin");
+        assert!(verify_password("admin", &data.user.password_hash));
+        assert_eq!(data.totp_entries.len(), 1);
+        let entry = &data.totp_entries[0];
+        assert_eq!((entry.name.as_str(), entry.issuer.as_str()), ("alice@example.com", "GitHub"));
+        assert_eq!(entry.secret.expose_secret(), "JBSWY3DPEHPK3PXP");
+        assert_eq!(entry.created_at, "2024-01-02T03:04:05+00:00");
+    }
+
+    // 升级后按当前格式写出，再次打开时不需要升级且内容不变
+    fn assert_upgrades(decrypted: Decrypted) {
+        let written = encrypt_vault(&decrypted.data, &decrypted.key).unwrap();
+        verify_vault(&written, &decrypted.key).unwrap();
+        assert_eq!(file_format::parse(&written).unwrap().header.version, file_format::CURRENT_VERSION);
+
+        let reopened = decrypt_vault(&written, PASSWORD).unwrap();
+        assert!(!reopened.needs_upgrade);
+        assert_eq!(reopened.slot_id, decrypted.slot_id);
+        assert_eq!(
+            serde_json::to_value(&reopened.data).unwrap(),
+            serde_json::to_value(&decrypted.data).unwrap(),
+        );
+    }
+
+    #[test]
+    fn opens_headerless_v1_with_schema_v1() {
+        let decrypted = decrypt_vault(V1, PASSWORD).unwrap();
+        assert!(decrypted.needs_upgrade);
+        assert_eq!(decrypted.schema_version, 1);
+        assert_sample_user(&decrypted.data);
+
+        // 结构版本 1 没有的字段按默认值补齐
+        let entry = &decrypted.data.totp_entries[0];
+        assert_eq!(entry.algorithm, OtpAlgorithm::SHA1);
+        assert_eq!((entry.digits, entry.period, entry.counter), (6, 30, 0));
+        assert_eq!(entry.otp_type, OtpType::Totp);
+
+        assert_upgrades(decrypted);
+    }
+
+    #[test]
+    fn opens_v2_kdf_header() {
+        let decrypted = decrypt_vault(V2, PASSWORD).unwrap();
+        assert!(decrypted.needs_upgrade);
+        assert_eq!(decrypted.schema_version, 2);
+        assert_sample_user(&decrypted.data);
+
+        let entry = &decrypted.data.totp_entries[0];
+        assert_eq!(entry.algorithm, OtpAlgorithm::SHA256);
+        assert_eq!((entry.digits, entry.period), (8, 60));
+
+        assert_upgrades(decrypted);
+    }
+
+    #[test]
+    fn rejects_wrong_password_for_old_formats() {
+        for file in [V1, V2] {
+            assert!(matches!(decrypt_vault(file, "wrong"), Err(AppError::InvalidMasterPassword)));
+        }
+    }
+
+    #[test]
+    fn rejects_newer_schema() {
+        let data = serde_json::json!({ "schema_version": SCHEMA_VERSION + 1, "totp_entries": [] });
+        assert!(matches!(migrate(data), Err(AppError::Storage(_))));
+    }
+}
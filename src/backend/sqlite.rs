@@ -0,0 +1,540 @@
+// SQLite 后端：每个条目单独加密为一行，增删改条目时只写入变化的行，适合条目很多的保险库
+//
+//   vault      只有一行：header（与 data.enc 文件头相同的字节，记录数据密钥 ID）、
+//              nonce、ciphertext（除条目外的 AppData，即 schema_version 和 user）
+//   entries    每个条目一行：id、position（列表顺序，递增但不要求连续）、
+//              meta_nonce / meta（不含密钥的条目字段，用于列表）、secret_nonce / secret（条目密钥）
+//   key_slots  每个密钥槽一行，slot 为 file_format::encode_slot 的编码。
+//              增删密钥槽只改这张表，条目不需要重新加密
+//
+// 所有密文都用 AES-256-GCM 加密，附加数据为 header || 用途 || 条目 id，
+// 把密文挪到其他条目或其他列都会导致解密失败。
+// 与 data.enc 的整体认证不同，整行被删除或回滚到旧版本无法从密文本身发现
+//
+// 与 file 后端一样对 data.db.lock 加锁；写入前用 PRAGMA data_version 确认数据库
+// 在上次读取或写入之后没有被其他连接（例如 sqlite3 命令行）修改过
+
+use super::lockfile::LockFile;
+use super::{migrate, open_key, quarantine_path, Decrypted, StorageBackend};
+use crate::backup::{self, BackupPolicy};
+use crate::crypto::{self, MasterKey, KEY_SIZE, NONCE_SIZE};
+use crate::error::{AppError, Result};
+use crate::file_format::{self, FileHeader, KeySource};
+use crate::models::{AppData, BackupInfo, User, SCHEMA_VERSION};
+use log::{debug, info, warn};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use zeroize::{Zeroize, Zeroizing};
+
+const BACKUP_EXTENSION: &str = ".db";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS vault (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        header BLOB NOT NULL,
+        nonce BLOB NOT NULL,
+        ciphertext BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS entries (
+        id TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        meta_nonce BLOB NOT NULL,
+        meta BLOB NOT NULL,
+        secret_nonce BLOB NOT NULL,
+        secret BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS key_slots (
+        id TEXT PRIMARY KEY,
+        slot BLOB NOT NULL
+    );
+";
+
+// 附加数据中区分用途的标签
+const VAULT_AAD: &[u8] = b"vault";
+const ENTRY_META_AAD: &[u8] = b"entry-meta";
+const ENTRY_SECRET_AAD: &[u8] = b"entry-secret";
+
+fn aad(header: &[u8], purpose: &[u8], entry_id: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(header.len() + purpose.len() + 1 + entry_id.len());
+    out.extend_from_slice(header);
+    out.extend_from_slice(purpose);
+    out.push(0);
+    out.extend_from_slice(entry_id.as_bytes());
+    out
+}
+
+// vault 行的明文
+#[derive(Serialize)]
+struct VaultRecord<'a> {
+    schema_version: u32,
+    user: &'a User,
+}
+
+// 一个条目行的明文
+#[derive(PartialEq)]
+struct EntryRow {
+    position: i64,
+    meta: Zeroizing<Vec<u8>>,
+    secret: Zeroizing<Vec<u8>>,
+}
+
+// 数据库中当前内容的明文，保存时与新数据比较，只重写不同的行
+struct Snapshot {
+    header: Vec<u8>,
+    // 编码后的密钥槽，按 ID 排列
+    slots: Vec<(String, Vec<u8>)>,
+    vault: Zeroizing<Vec<u8>>,
+    entries: HashMap<String, EntryRow>,
+}
+
+impl Snapshot {
+    // previous 为数据库中当前的内容（数据密钥相同时）。position 只需要保持递增：
+    // 条目尽量沿用原来的 position，删除条目不会改动其他行，追加的条目排在最后；
+    // 只有顺序变化时才需要更新被移动的条目
+    fn from_data(data: &AppData, key: &MasterKey, previous: Option<&Snapshot>) -> Result<Self> {
+        let header = FileHeader::new(key.key_id).to_aad();
+        let mut slots = key.slots.iter()
+            .map(|slot| Ok((slot.id.clone(), file_format::encode_slot(slot)?)))
+            .collect::<Result<Vec<_>>>()?;
+        slots.sort();
+
+        let record = VaultRecord { schema_version: data.schema_version, user: &data.user };
+        let vault = Zeroizing::new(serde_json::to_vec(&record)
+            .map_err(|e| AppError::Storage(format!("Serialization failed: {}", e)))?);
+
+        let mut entries = HashMap::with_capacity(data.totp_entries.len());
+        let mut last_position: Option<i64> = None;
+        for entry in &data.totp_entries {
+            let position = match previous.and_then(|p| p.entries.get(&entry.id)).map(|row| row.position) {
+                Some(position) if last_position.is_none_or(|last| position > last) => position,
+                _ => last_position.map_or(0, |last| last + 1),
+            };
+            last_position = Some(position);
+            let mut meta = serde_json::to_value(entry)
+                .map_err(|e| AppError::Storage(format!("Serialization failed: {}", e)))?;
+            let secret = match meta.as_object_mut().and_then(|m| m.remove("secret")) {
+                Some(Value::String(mut secret)) => {
+                    let bytes = Zeroizing::new(secret.as_bytes().to_vec());
+                    secret.zeroize();
+                    bytes
+                }
+                _ => return Err(AppError::Storage(format!("Entry {} has no secret", entry.id))),
+            };
+            let row = EntryRow {
+                position,
+                meta: Zeroizing::new(meta.to_string().into_bytes()),
+                secret,
+            };
+            if entries.insert(entry.id.clone(), row).is_some() {
+                return Err(AppError::Storage(format!("Duplicate entry id: {}", entry.id)));
+            }
+        }
+        Ok(Self { header, slots, vault, entries })
+    }
+}
+
+pub struct SqliteBackend {
+    path: String,
+    backup_policy: BackupPolicy,
+    _lock: LockFile,
+    conn: Mutex<Connection>,
+    // 最近一次读取或写入后的内容；未知（尚未解锁或上次写入失败）时为 None，下次保存整体重写
+    snapshot: Mutex<Option<Snapshot>>,
+    // 最近一次读取或写入后的 data_version，其他连接提交修改后会变化
+    data_version: Mutex<Option<i64>>,
+}
+
+impl SqliteBackend {
+    pub fn new(path: &str, backup_policy: BackupPolicy) -> Result<Self> {
+        let lock = LockFile::acquire(path)?;
+        if fs::metadata(path).is_err() {
+            info!("Database file not found, will create new one");
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(Self {
+            path: path.to_string(),
+            backup_policy,
+            _lock: lock,
+            conn: Mutex::new(conn),
+            snapshot: Mutex::new(None),
+            data_version: Mutex::new(None),
+        })
+    }
+
+    // 用 VACUUM INTO 写出一致的数据库副本（目标文件不能已存在）
+    fn copy_to(&self, target: &Path) -> Result<()> {
+        let target = target.to_str()
+            .ok_or_else(|| AppError::Storage(format!("Invalid backup path: {}", target.display())))?;
+        self.conn.lock().unwrap().execute("VACUUM INTO ?1", [target])?;
+        Ok(())
+    }
+}
+
+fn data_version(conn: &Connection) -> Result<i64> {
+    Ok(conn.query_row("PRAGMA data_version", [], |r| r.get(0))?)
+}
+
+fn nonce_from(bytes: &[u8]) -> Result<[u8; NONCE_SIZE]> {
+    bytes.try_into()
+        .map_err(|_| AppError::InvalidHeader(format!("unsupported nonce length {}", bytes.len())))
+}
+
+fn decrypt_row(key: &[u8; KEY_SIZE], nonce: &[u8], ciphertext: &[u8], aad: &[u8], what: &str) -> Result<Zeroizing<Vec<u8>>> {
+    crypto::decrypt_with_aad(key, &nonce_from(nonce)?, ciphertext, aad)
+        .map(Zeroizing::new)
+        .map_err(|_| AppError::AuthenticationFailed(what.to_string()))
+}
+
+// 读取并解密整个数据库；vault 行不存在（新数据库）时返回 None
+fn read_vault(conn: &Connection, password: &str) -> Result<Option<(Decrypted, Snapshot)>> {
+    let row = conn.query_row(
+        "SELECT header, nonce, ciphertext FROM vault WHERE id = 1",
+        [],
+        |r| Ok((r.get::<_, Vec<u8>>(0)?, r.get::<_, Vec<u8>>(1)?, r.get::<_, Vec<u8>>(2)?)),
+    ).optional()?;
+    let Some((header_bytes, nonce, ciphertext)) = row else {
+        return Ok(None);
+    };
+    let header = file_format::parse_header(&header_bytes)?;
+
+    let mut slots = Vec::new();
+    if let KeySource::Slots { .. } = header.key_source {
+        let mut stmt = conn.prepare("SELECT id, slot FROM key_slots ORDER BY id")?;
+        let mut rows = stmt.query([])?;
+        while let Some(r) = rows.next()? {
+            slots.push((r.get::<_, String>(0)?, r.get::<_, Vec<u8>>(1)?));
+        }
+    }
+    let parsed = slots.iter()
+        .map(|(_, bytes)| file_format::parse_slot(bytes))
+        .collect::<Result<Vec<_>>>()?;
+
+    // 解开数据密钥；旧版本（主密码直接派生）vault 行解密失败视为主密码错误
+    let opened = open_key(&header, parsed, password)?;
+    let key = &opened.data_key;
+    let vault = Zeroizing::new(crypto::decrypt_with_aad(
+        key, &nonce_from(&nonce)?, &ciphertext, &aad(&header_bytes, VAULT_AAD, ""),
+    ).map_err(|_| opened.decrypt_error("vault row"))?);
+
+    let mut snapshot = Snapshot {
+        header: header_bytes,
+        slots,
+        vault,
+        entries: HashMap::new(),
+    };
+    let mut document: Value = serde_json::from_slice(&snapshot.vault)
+        .map_err(|e| AppError::Storage(format!("Deserialization failed: {}", e)))?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, position, meta_nonce, meta, secret_nonce, secret FROM entries ORDER BY position",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut entries = Vec::new();
+    while let Some(r) = rows.next()? {
+        let id: String = r.get(0)?;
+        let meta = decrypt_row(key, &r.get::<_, Vec<u8>>(2)?, &r.get::<_, Vec<u8>>(3)?,
+            &aad(&snapshot.header, ENTRY_META_AAD, &id), &format!("Entry {}", id))?;
+        let secret = decrypt_row(key, &r.get::<_, Vec<u8>>(4)?, &r.get::<_, Vec<u8>>(5)?,
+            &aad(&snapshot.header, ENTRY_SECRET_AAD, &id), &format!("Secret of entry {}", id))?;
+
+        let mut entry: Value = serde_json::from_slice(&meta)
+            .map_err(|e| AppError::Storage(format!("Deserialization failed: {}", e)))?;
+        let secret_str = std::str::from_utf8(&secret)
+            .map_err(|e| AppError::Storage(format!("Invalid secret of entry {}: {}", id, e)))?;
+        entry["secret"] = Value::String(secret_str.to_string());
+        entries.push(entry);
+
+        snapshot.entries.insert(id, EntryRow { position: r.get(1)?, meta, secret });
+    }
+    document["totp_entries"] = Value::Array(entries);
+
+    let (data, schema_version) = migrate(document)?;
+    debug!("Database decrypted successfully ({} entries)", data.totp_entries.len());
+    let decrypted = Decrypted {
+        data,
+        key: opened.key,
+        needs_upgrade: header.is_legacy() || schema_version < SCHEMA_VERSION,
+        schema_version,
+        slot_id: opened.slot_id,
+    };
+    Ok(Some((decrypted, snapshot)))
+}
+
+impl StorageBackend for SqliteBackend {
+    fn describe(&self) -> String {
+        format!("sqlite {}", self.path)
+    }
+
+    fn load(&self, password: &str) -> Result<Option<Decrypted>> {
+        let conn = self.conn.lock().unwrap();
+        let loaded = read_vault(&conn, password)?;
+        *self.data_version.lock().unwrap() = Some(data_version(&conn)?);
+        Ok(loaded.map(|(decrypted, snapshot)| {
+            *self.snapshot.lock().unwrap() = Some(snapshot);
+            decrypted
+        }))
+    }
+
+    fn inspect(&self, password: &str) -> Result<Option<Decrypted>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(read_vault(&conn, password)?.map(|(decrypted, _)| decrypted))
+    }
+
+    fn has_vault(&self) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.query_row("SELECT 1 FROM vault WHERE id = 1", [], |_| Ok(())).optional()?.is_some())
+    }
+
+    // 在一个事务中只写入与上次不同的行；数据密钥变化（文件头不同）时整体重写，
+    // 密钥槽变化时只重写 key_slots
+    fn store(&self, data: &AppData, key: &MasterKey) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let mut known_version = self.data_version.lock().unwrap();
+        if known_version.is_some() && *known_version != Some(data_version(&conn)?) {
+            warn!("{} was modified by another connection, refusing to overwrite it", self.path);
+            return Err(AppError::ModifiedExternally);
+        }
+        let mut snapshot = self.snapshot.lock().unwrap();
+        let header = FileHeader::new(key.key_id).to_aad();
+        let previous = snapshot.take().filter(|s| s.header == header);
+        let next = match Snapshot::from_data(data, key, previous.as_ref()) {
+            Ok(next) => next,
+            Err(e) => {
+                *snapshot = previous;
+                return Err(e);
+            }
+        };
+
+        let tx = conn.transaction()?;
+        let (mut written, mut removed) = (0, 0);
+        if previous.is_none() {
+            tx.execute("DELETE FROM entries", [])?;
+        }
+        if previous.as_ref().is_none_or(|p| p.slots != next.slots) {
+            tx.execute("DELETE FROM key_slots", [])?;
+            for (id, slot) in &next.slots {
+                tx.execute("INSERT INTO key_slots (id, slot) VALUES (?1, ?2)", params![id, slot])?;
+                written += 1;
+            }
+        }
+        if previous.as_ref().is_none_or(|p| p.vault != next.vault) {
+            let (nonce, ciphertext) = crypto::encrypt_with_aad(key.key(), &next.vault, &aad(&next.header, VAULT_AAD, ""))?;
+            tx.execute(
+                "INSERT OR REPLACE INTO vault (id, header, nonce, ciphertext) VALUES (1, ?1, ?2, ?3)",
+                params![next.header, nonce.as_slice(), ciphertext],
+            )?;
+            written += 1;
+        }
+        for (id, row) in &next.entries {
+            match previous.as_ref().and_then(|p| p.entries.get(id)) {
+                Some(old) if old == row => {}
+                Some(old) if old.meta == row.meta && old.secret == row.secret => {
+                    tx.execute("UPDATE entries SET position = ?1 WHERE id = ?2", params![row.position, id])?;
+                }
+                _ => {
+                    let (meta_nonce, meta) = crypto::encrypt_with_aad(key.key(), &row.meta, &aad(&next.header, ENTRY_META_AAD, id))?;
+                    let (secret_nonce, secret) = crypto::encrypt_with_aad(key.key(), &row.secret, &aad(&next.header, ENTRY_SECRET_AAD, id))?;
+                    tx.execute(
+                        "INSERT OR REPLACE INTO entries (id, position, meta_nonce, meta, secret_nonce, secret)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                        params![id, row.position, meta_nonce.as_slice(), meta, secret_nonce.as_slice(), secret],
+                    )?;
+                    written += 1;
+                }
+            }
+        }
+        if let Some(previous) = &previous {
+            for id in previous.entries.keys().filter(|id| !next.entries.contains_key(*id)) {
+                tx.execute("DELETE FROM entries WHERE id = ?1", [id])?;
+                removed += 1;
+            }
+        }
+        tx.commit()?;
+
+        *known_version = Some(data_version(&conn)?);
+        *snapshot = Some(next);
+        info!("Data saved successfully ({} rows written, {} removed)", written, removed);
+        Ok(())
+    }
+
+    // VACUUM INTO 复制整个数据库，默认按备份间隔（见 config.rs）而不是每次保存都备份
+    fn create_backup(&self, force: bool) -> Result<()> {
+        if !self.has_vault()? {
+            return Ok(());
+        }
+        backup::create(&self.path, BACKUP_EXTENSION, &self.backup_policy, force, |backup_path| self.copy_to(backup_path))
+    }
+
+    fn list_backups(&self) -> Result<Vec<BackupInfo>> {
+        backup::list(&self.path, BACKUP_EXTENSION)
+    }
+
+    fn load_backup(&self, id: &str, password: &str) -> Result<Option<Decrypted>> {
+        let Ok(backup_path) = backup::path_for(&self.path, id, BACKUP_EXTENSION) else {
+            return Ok(None);
+        };
+        if fs::metadata(&backup_path).is_err() {
+            return Ok(None);
+        }
+        let conn = Connection::open_with_flags(&backup_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(read_vault(&conn, password)?.map(|(decrypted, _)| decrypted))
+    }
+
+    fn preserve_before_upgrade(&self) -> Result<()> {
+        let backup_path = format!("{}.pre-upgrade.bak", self.path);
+        if fs::metadata(&backup_path).is_ok() {
+            fs::remove_file(&backup_path)
+                .map_err(|e| AppError::Storage(format!("Failed to replace previous upgrade backup: {}", e)))?;
+        }
+        self.copy_to(Path::new(&backup_path))?;
+        info!("Upgrading database, previous version kept at {}", backup_path);
+        Ok(())
+    }
+
+    // 关闭连接后移走数据库文件，再在原路径新建空数据库
+    fn quarantine(&self) -> Result<Option<String>> {
+        let mut conn = self.conn.lock().unwrap();
+        *self.snapshot.lock().unwrap() = None;
+        *self.data_version.lock().unwrap() = None;
+        if fs::metadata(&self.path).is_err() {
+            return Ok(None);
+        }
+
+        *conn = Connection::open_in_memory()?;
+        let target = quarantine_path(&self.path);
+        let moved = fs::rename(&self.path, &target)
+            .map_err(|e| AppError::Storage(format!("Failed to move damaged database: {}", e)));
+        *conn = Connection::open(&self.path)?;
+        conn.execute_batch(SCHEMA)?;
+        moved?;
+        warn!("Moved damaged database to {}", target);
+        Ok(Some(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SlotKind;
+    use crate::testutil::{sample_entry, TempDir, NO_BACKUPS, TEST_PARAMS};
+
+    // 每行的 (nonce, 密文)，按表和条目 ID 排列，用于比较两次保存之间哪些行发生了变化
+    type Rows = HashMap<String, Vec<u8>>;
+
+    fn rows(path: &str) -> Rows {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+        let mut rows = HashMap::new();
+        let queries = [
+            ("vault", "SELECT 'vault', nonce, ciphertext FROM vault"),
+            ("entry", "SELECT id, meta_nonce, meta, secret_nonce, secret FROM entries"),
+            ("slot", "SELECT id, slot FROM key_slots"),
+        ];
+        for (table, sql) in queries {
+            let mut stmt = conn.prepare(sql).unwrap();
+            let columns = stmt.column_count();
+            let mut result = stmt.query([]).unwrap();
+            while let Some(r) = result.next().unwrap() {
+                let bytes = (1..columns).flat_map(|i| r.get::<_, Vec<u8>>(i).unwrap()).collect();
+                rows.insert(format!("{}:{}", table, r.get::<_, String>(0).unwrap()), bytes);
+            }
+        }
+        rows
+    }
+
+    // 两次保存之间新增、删除和改变的行
+    fn diff(before: &Rows, after: &Rows) -> (Vec<String>, Vec<String>, Vec<String>) {
+        let mut added: Vec<_> = after.keys().filter(|k| !before.contains_key(*k)).cloned().collect();
+        let mut removed: Vec<_> = before.keys().filter(|k| !after.contains_key(*k)).cloned().collect();
+        let mut changed: Vec<_> = after.iter()
+            .filter(|(k, v)| before.get(*k).is_some_and(|old| old != *v))
+            .map(|(k, _)| k.clone())
+            .collect();
+        added.sort();
+        removed.sort();
+        changed.sort();
+        (added, removed, changed)
+    }
+
+    fn sample_vault(backend: &SqliteBackend) -> (AppData, MasterKey) {
+        let mut data = AppData::default();
+        for name in ["alice", "bob", "carol"] {
+            data.totp_entries.push(sample_entry(name));
+        }
+        let mut key = MasterKey::generate();
+        key.add_slot(SlotKind::Password, "", "pw", TEST_PARAMS).unwrap();
+        backend.store(&data, &key).unwrap();
+        (data, key)
+    }
+
+    #[test]
+    fn adding_or_removing_an_entry_touches_one_row() {
+        let dir = TempDir::new();
+        let path = dir.file("data.db");
+        let backend = SqliteBackend::new(&path, NO_BACKUPS).unwrap();
+        let (mut data, key) = sample_vault(&backend);
+        let before = rows(&path);
+
+        let added = sample_entry("dave");
+        data.totp_entries.push(added.clone());
+        backend.store(&data, &key).unwrap();
+        let after_add = rows(&path);
+        assert_eq!(diff(&before, &after_add), (vec![format!("entry:{}", added.id)], vec![], vec![]));
+
+        let removed = data.totp_entries.remove(1);
+        backend.store(&data, &key).unwrap();
+        let after_remove = rows(&path);
+        assert_eq!(diff(&after_add, &after_remove), (vec![], vec![format!("entry:{}", removed.id)], vec![]));
+
+        let loaded = backend.load("pw").unwrap().unwrap();
+        let names: Vec<_> = loaded.data.totp_entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["alice", "carol", "dave"]);
+    }
+
+    // 附加数据包含条目 ID，把两行的密文互换后无法解密
+    #[test]
+    fn swapped_rows_fail_authentication() {
+        let dir = TempDir::new();
+        let path = dir.file("data.db");
+        let backend = SqliteBackend::new(&path, NO_BACKUPS).unwrap();
+        let (data, _) = sample_vault(&backend);
+        drop(backend);
+
+        let (a, b) = (&data.totp_entries[0].id, &data.totp_entries[1].id);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(&format!(
+            "UPDATE entries SET id = 'swap' WHERE id = '{a}';
+             UPDATE entries SET id = '{a}' WHERE id = '{b}';
+             UPDATE entries SET id = '{b}' WHERE id = 'swap';"
+        )).unwrap();
+        drop(conn);
+
+        let backend = SqliteBackend::new(&path, NO_BACKUPS).unwrap();
+        assert!(matches!(backend.load("pw"), Err(AppError::AuthenticationFailed(_))));
+    }
+
+    #[test]
+    fn external_write_is_detected() {
+        let dir = TempDir::new();
+        let path = dir.file("data.db");
+        let backend = SqliteBackend::new(&path, NO_BACKUPS).unwrap();
+        let (mut data, key) = sample_vault(&backend);
+        backend.load("pw").unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        conn.execute("DELETE FROM entries WHERE id = ?1", [&data.totp_entries[0].id]).unwrap();
+        drop(conn);
+
+        data.totp_entries.pop();
+        assert!(matches!(backend.store(&data, &key), Err(AppError::ModifiedExternally)));
+        // 重新读取后可以正常写入
+        backend.load("pw").unwrap();
+        backend.store(&data, &key).unwrap();
+    }
+}
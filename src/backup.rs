@@ -0,0 +1,246 @@
+// 数据文件的自动备份：每次保存前把当前文件复制到 <数据文件>.backups/ 目录，
+// 文件名为 UTC 时间戳加扩展名（如 data.enc 的备份 20261018T055043.123Z.enc），内容仍然是加密的。
+// 扩展名和复制方式由存储后端决定（file 后端直接复制，sqlite 后端用 VACUUM INTO）
+//
+// 保留策略：最多保留 count 份，超过 max_age 的也会删除；最新的一份始终保留。
+// 设置了 min_interval 时，距离最新一份备份不到该时长的保存不再备份（sqlite 后端默认每小时一份，
+// 避免每次保存都用 VACUUM INTO 复制整个数据库）；恢复备份等会丢弃当前数据的操作不受限制
+//
+// 由存储后端在阻塞线程池中调用，这里都是同步 IO
+
+use crate::error::{AppError, Result};
+use crate::models::BackupInfo;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ID_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+#[derive(Debug, Clone)]
+pub struct BackupPolicy {
+    // 为 0 时不做自动备份
+    pub count: usize,
+    pub max_age: Option<Duration>,
+    // 两次自动备份之间的最短间隔，None 表示每次保存前都备份
+    pub min_interval: Option<Duration>,
+}
+
+impl BackupPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.count > 0
+    }
+
+    // newest 为最新一份备份的创建时间；force 时忽略最短间隔
+    pub fn is_due(&self, newest: Option<DateTime<Utc>>, now: DateTime<Utc>, force: bool) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+        force || match (self.min_interval, newest) {
+            (Some(interval), Some(newest)) => now - newest >= interval,
+            _ => true,
+        }
+    }
+
+    // index 为按创建时间从新到旧排列的序号
+    pub fn should_remove(&self, index: usize, created_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        let too_many = index >= self.count;
+        let too_old = self.max_age.is_some_and(|max_age| now - created_at > max_age);
+        index > 0 && (too_many || too_old)
+    }
+}
+
+// 新备份的 ID（当前 UTC 时间戳）
+pub fn new_id(now: DateTime<Utc>) -> String {
+    now.format(ID_FORMAT).to_string()
+}
+
+struct Generation {
+    id: String,
+    created_at: DateTime<Utc>,
+    path: PathBuf,
+}
+
+pub fn backup_dir(data_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.backups", data_path))
+}
+
+// 备份 ID 只接受本模块生成的时间戳格式，避免路径穿越
+pub fn parse_id(id: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(id, ID_FORMAT)
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+pub fn path_for(data_path: &str, id: &str, extension: &str) -> Result<PathBuf> {
+    parse_id(id).ok_or_else(|| AppError::Storage(format!("Invalid backup id: {}", id)))?;
+    Ok(backup_dir(data_path).join(format!("{}{}", id, extension)))
+}
+
+// 按创建时间从新到旧排列
+fn generations(dir: &Path, extension: &str) -> Result<Vec<Generation>> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(AppError::Storage(format!("Failed to read backup directory: {}", e))),
+    };
+
+    let mut generations = Vec::new();
+    for entry in read_dir {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(id) = file_name.to_str().and_then(|n| n.strip_suffix(extension)) else {
+            continue;
+        };
+        if let Some(created_at) = parse_id(id) {
+            generations.push(Generation {
+                id: id.to_string(),
+                created_at,
+                path: entry.path(),
+            });
+        }
+    }
+    generations.sort_by_key(|g| std::cmp::Reverse(g.created_at));
+    Ok(generations)
+}
+
+pub fn list(data_path: &str, extension: &str) -> Result<Vec<BackupInfo>> {
+    let mut backups = Vec::new();
+    for generation in generations(&backup_dir(data_path), extension)? {
+        let size = fs::metadata(&generation.path)?.len();
+        backups.push(BackupInfo {
+            id: generation.id,
+            created_at: generation.created_at.to_rfc3339(),
+            size,
+        });
+    }
+    Ok(backups)
+}
+
+// 用 write_backup 把当前数据文件写到新备份的路径，然后按策略清理旧备份；
+// 数据文件不存在或还没到备份间隔（force 除外）时什么都不做
+pub fn create<F>(data_path: &str, extension: &str, policy: &BackupPolicy, force: bool, write_backup: F) -> Result<()>
+where
+    F: FnOnce(&Path) -> Result<()>,
+{
+    if !policy.is_enabled() || fs::metadata(data_path).is_err() {
+        return Ok(());
+    }
+
+    let dir = backup_dir(data_path);
+    let newest = generations(&dir, extension)?.first().map(|g| g.created_at);
+    if !policy.is_due(newest, Utc::now(), force) {
+        debug!("Skipping backup, newest backup is more recent than the backup interval");
+        return Ok(());
+    }
+    fs::create_dir_all(&dir)
+        .map_err(|e| AppError::Storage(format!("Failed to create backup directory: {}", e)))?;
+
+    let id = new_id(Utc::now());
+    let path = dir.join(format!("{}{}", id, extension));
+    write_backup(&path)
+        .map_err(|e| AppError::Storage(format!("Failed to create backup: {}", e)))?;
+    debug!("Created backup {}", id);
+
+    prune(&dir, extension, policy)
+}
+
+fn prune(dir: &Path, extension: &str, policy: &BackupPolicy) -> Result<()> {
+    let now = Utc::now();
+    for (index, generation) in generations(dir, extension)?.into_iter().enumerate() {
+        if policy.should_remove(index, generation.created_at, now) {
+            match fs::remove_file(&generation.path) {
+                Ok(()) => info!("Removed old backup {}", generation.id),
+                Err(e) => warn!("Failed to remove old backup {}: {}", generation.id, e),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn policy(count: usize, max_age: Option<Duration>, min_interval: Option<Duration>) -> BackupPolicy {
+        BackupPolicy { count, max_age, min_interval }
+    }
+
+    #[test]
+    fn is_due_respects_min_interval() {
+        let now = Utc::now();
+        let hourly = policy(3, None, Some(Duration::hours(1)));
+        assert!(hourly.is_due(None, now, false));
+        assert!(!hourly.is_due(Some(now - Duration::minutes(10)), now, false));
+        assert!(hourly.is_due(Some(now - Duration::minutes(10)), now, true));
+        assert!(hourly.is_due(Some(now - Duration::hours(1)), now, false));
+
+        assert!(policy(3, None, None).is_due(Some(now), now, false));
+        assert!(!policy(0, None, None).is_due(None, now, true));
+    }
+
+    #[test]
+    fn should_remove_by_count_and_age() {
+        let now = Utc::now();
+        let by_count = policy(2, None, None);
+        assert!(!by_count.should_remove(1, now, now));
+        assert!(by_count.should_remove(2, now, now));
+
+        let by_age = policy(10, Some(Duration::days(7)), None);
+        assert!(!by_age.should_remove(1, now - Duration::days(6), now));
+        assert!(by_age.should_remove(1, now - Duration::days(8), now));
+        // 最新的一份无论多旧都保留
+        assert!(!by_age.should_remove(0, now - Duration::days(30), now));
+        assert!(!policy(0, None, None).should_remove(0, now, now));
+    }
+
+    #[test]
+    fn path_for_rejects_ids_that_are_not_timestamps() {
+        let id = new_id(Utc::now());
+        let path = path_for("/data/data.enc", &id, ".enc").unwrap();
+        assert_eq!(path, PathBuf::from(format!("/data/data.enc.backups/{}.enc", id)));
+
+        for id in ["../data", "../../etc/passwd", "20261018T055043.123Z/../x", "", "latest"] {
+            assert!(path_for("/data/data.enc", id, ".enc").is_err(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn create_copies_data_file_and_prunes_old_backups() {
+        let dir = TempDir::new();
+        let data_path = dir.file("data.enc");
+        let keep_two = policy(2, None, None);
+        let copy = |to: &Path| -> Result<()> {
+            fs::copy(&data_path, to)?;
+            Ok(())
+        };
+
+        // 数据文件还不存在时不备份
+        create(&data_path, ".enc", &keep_two, false, copy).unwrap();
+        assert!(list(&data_path, ".enc").unwrap().is_empty());
+
+        for generation in 0..3 {
+            fs::write(&data_path, format!("generation {}", generation)).unwrap();
+            create(&data_path, ".enc", &keep_two, false, copy).unwrap();
+            // 备份 ID 精确到毫秒
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        // 其他文件不算备份
+        fs::write(backup_dir(&data_path).join("notes.txt"), "").unwrap();
+
+        let backups = list(&data_path, ".enc").unwrap();
+        assert_eq!(backups.len(), 2);
+        let newest = path_for(&data_path, &backups[0].id, ".enc").unwrap();
+        let oldest = path_for(&data_path, &backups[1].id, ".enc").unwrap();
+        assert_eq!(fs::read_to_string(newest).unwrap(), "generation 2");
+        assert_eq!(fs::read_to_string(oldest).unwrap(), "generation 1");
+
+        // 未到最短间隔时跳过，force 时仍然备份
+        let hourly = policy(5, None, Some(Duration::hours(1)));
+        create(&data_path, ".enc", &hourly, false, copy).unwrap();
+        assert_eq!(list(&data_path, ".enc").unwrap().len(), 2);
+        create(&data_path, ".enc", &hourly, true, copy).unwrap();
+        assert_eq!(list(&data_path, ".enc").unwrap().len(), 3);
+    }
+}
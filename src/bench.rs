@@ -0,0 +1,219 @@
+// 基准测试子命令
+//
+// web-totp bench-save [条目数] [保存次数]
+//
+// 在临时目录中新建数据文件（KDF 参数按当前配置选择），添加指定数量的条目后连续保存
+// （每次保存前修改一个条目），输出解锁（派生密钥）和每次保存的耗时。保存只做 AES-GCM 加密和崩溃安全的写入，
+// 不再运行 Argon2，因此保存耗时应远低于解锁耗时。
+//
+// 存储后端按 WEB_TOTP_STORAGE 选择（数据文件放在临时目录中）。
+// 基准测试不做自动备份，避免备份目录的复制和清理影响结果。
+//
+// 负载测试见本文件末尾的 tests::concurrent_requests_do_not_stall_worker。
+
+use crate::backend;
+use crate::backup::BackupPolicy;
+use crate::config::Config;
+use crate::models::TotpEntry;
+use crate::storage::Storage;
+use std::time::{Duration, Instant};
+
+const DEFAULT_ENTRIES: usize = 100;
+const DEFAULT_SAVES: usize = 50;
+const BENCH_PASSWORDS: [&str; 2] = ["web-totp-benchmark", "web-totp-benchmark-2"];
+
+pub async fn run_save_benchmark(args: &[String]) -> std::io::Result<()> {
+    let entries = args.first().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_ENTRIES);
+    let saves = args.get(1).and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_SAVES).max(1);
+
+    with_temp_dir(|file_path| async move { save_benchmark(&file_path, entries, saves).await }).await
+}
+
+async fn with_temp_dir<F, Fut, T>(f: F) -> std::io::Result<T>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: std::future::Future<Output = Result<T, crate::error::AppError>>,
+{
+    let dir = std::env::temp_dir().join(format!("web-totp-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let result = f(dir.join("data.enc").to_string_lossy().to_string()).await;
+    let _ = std::fs::remove_dir_all(&dir);
+    result.map_err(std::io::Error::other)
+}
+
+// 新建数据文件并添加 entries 个条目，返回存储和解锁耗时
+async fn prepare_storage(file_path: &str, entries: usize) -> Result<(Storage, Duration), crate::error::AppError> {
+    let config = Config::from_env();
+    let no_backups = BackupPolicy { count: 0, max_age: None, min_interval: None };
+    let backend = backend::open(config.storage_backend, Some(file_path), no_backups)?;
+    let storage = Storage::new(backend, config.kdf, config.key_file_dir, None, config.save_debounce);
+
+    let start = Instant::now();
+    storage.unlock(BENCH_PASSWORDS[0]).await?;
+    let unlock_time = start.elapsed();
+
+    for i in 0..entries {
+        storage.add_totp_entry(bench_entry(i)).await?;
+    }
+    Ok((storage, unlock_time))
+}
+
+fn bench_entry(i: usize) -> TotpEntry {
+    TotpEntry::new(
+        format!("user{}@example.com", i),
+        "Benchmark".to_string(),
+        "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP".to_string(),
+    )
+}
+
+async fn save_benchmark(file_path: &str, entries: usize, saves: usize) -> Result<(), crate::error::AppError> {
+    let (storage, unlock_time) = prepare_storage(file_path, entries).await?;
+
+    let ids: Vec<String> = storage.get_totp_entries().await?.into_iter().map(|e| e.id).collect();
+    let mut timings = Vec::with_capacity(saves);
+    for i in 0..saves {
+        if let Some(id) = ids.get(i % ids.len().max(1)) {
+            storage.update_totp_entry(id, |e| e.counter += 1).await?;
+        }
+        let start = Instant::now();
+        storage.save().await?;
+        timings.push(start.elapsed());
+    }
+    timings.sort();
+
+    let total: Duration = timings.iter().sum();
+    let percentile = |p: usize| timings[(timings.len() - 1) * p / 100];
+    println!("entries: {}, saves: {}, file size: {} bytes", entries, saves, std::fs::metadata(file_path)?.len());
+    println!("unlock (creates file, derives key): {:?}", unlock_time);
+    println!(
+        "save: mean {:?}, p50 {:?}, p95 {:?}, max {:?}",
+        total / saves as u32,
+        percentile(50),
+        percentile(95),
+        timings[timings.len() - 1]
+    );
+    Ok(())
+}
+
+
+// 负载测试：在单线程运行时中（与 actix 工作线程相同）同时运行读取任务、不断添加条目并保存的写入任务，
+// 以及反复更换主密码（每次运行 Argon2）的任务，并用 10ms 定时器测量事件循环的调度延迟。
+// Argon2 在阻塞线程池中运行，读取延迟和调度延迟应在毫秒级，而不是一次密钥派生的耗时。
+//
+// 耗时较长且依赖机器性能，默认不运行：
+//   cargo test --release -- --ignored concurrent_requests_do_not_stall_worker
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    const READERS: usize = 8;
+    const DURATION: Duration = Duration::from_secs(5);
+    const TICK: Duration = Duration::from_millis(10);
+    // 远小于一次 Argon2 派生（按默认策略约几百毫秒）
+    const MAX_LATENCY: Duration = Duration::from_millis(50);
+
+    // 延迟均为最大值
+    struct LoadReport {
+        reads: u64,
+        read_latency: Duration,
+        saves: u64,
+        rekeys: usize,
+        lag: Duration,
+    }
+
+    async fn load_test(file_path: &str) -> Result<LoadReport, AppError> {
+        let (storage, unlock_time) = prepare_storage(file_path, DEFAULT_ENTRIES).await?;
+        println!("unlock (one Argon2 derivation): {:?}", unlock_time);
+        let storage = Arc::new(storage);
+        let deadline = Instant::now() + DURATION;
+
+        // 事件循环调度延迟：定时器实际唤醒时间与预期时间之差
+        let lag = Rc::new(Cell::new(Duration::ZERO));
+        let ticker = {
+            let lag = lag.clone();
+            actix_web::rt::spawn(async move {
+                while Instant::now() < deadline {
+                    let expected = Instant::now() + TICK;
+                    tokio::time::sleep(TICK).await;
+                    lag.set(lag.get().max(Instant::now().saturating_duration_since(expected)));
+                }
+            })
+        };
+
+        let reader_tasks: Vec<_> = (0..READERS).map(|_| {
+            let storage = storage.clone();
+            actix_web::rt::spawn(async move {
+                let mut reads = 0u64;
+                let mut max_latency = Duration::ZERO;
+                while Instant::now() < deadline {
+                    let start = Instant::now();
+                    let entries = storage.get_totp_entries().await?;
+                    if let Some(entry) = entries.first() {
+                        storage.get_totp_entry(&entry.id).await?;
+                    }
+                    max_latency = max_latency.max(start.elapsed());
+                    reads += 1;
+                    tokio::task::yield_now().await;
+                }
+                Ok::<_, AppError>((reads, max_latency))
+            })
+        }).collect();
+
+        let writer = {
+            let storage = storage.clone();
+            actix_web::rt::spawn(async move {
+                let mut saves = 0u64;
+                while Instant::now() < deadline {
+                    storage.add_totp_entry(bench_entry(DEFAULT_ENTRIES + saves as usize)).await?;
+                    storage.save().await?;
+                    saves += 1;
+                }
+                Ok::<_, AppError>(saves)
+            })
+        };
+
+        let rekeyer = {
+            let storage = storage.clone();
+            actix_web::rt::spawn(async move {
+                let mut rekeys = 0usize;
+                while Instant::now() < deadline {
+                    let old = BENCH_PASSWORDS[rekeys % 2];
+                    let new = BENCH_PASSWORDS[(rekeys + 1) % 2];
+                    storage.change_master_password(old, new).await?;
+                    rekeys += 1;
+                }
+                Ok::<_, AppError>(rekeys)
+            })
+        };
+
+        let join_error = |e: tokio::task::JoinError| AppError::Storage(e.to_string());
+        let mut report = LoadReport { reads: 0, read_latency: Duration::ZERO, saves: 0, rekeys: 0, lag: Duration::ZERO };
+        for task in reader_tasks {
+            let (reads, max_latency) = task.await.map_err(join_error)??;
+            report.reads += reads;
+            report.read_latency = report.read_latency.max(max_latency);
+        }
+        report.saves = writer.await.map_err(join_error)??;
+        report.rekeys = rekeyer.await.map_err(join_error)??;
+        ticker.await.map_err(join_error)?;
+        report.lag = lag.get();
+        Ok(report)
+    }
+
+    #[actix_web::test]
+    #[ignore]
+    async fn concurrent_requests_do_not_stall_worker() {
+        let report = with_temp_dir(|file_path| async move { load_test(&file_path).await }).await.unwrap();
+
+        println!("reads: {}, max read latency: {:?}", report.reads, report.read_latency);
+        println!("saves: {}, master password changes: {}", report.saves, report.rekeys);
+        println!("max event loop lag: {:?}", report.lag);
+        assert!(report.saves > 0 && report.rekeys > 0, "writer and rekey tasks must make progress");
+        assert!(report.read_latency < MAX_LATENCY, "read latency {:?}", report.read_latency);
+        assert!(report.lag < MAX_LATENCY, "event loop lag {:?}", report.lag);
+    }
+}
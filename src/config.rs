@@ -0,0 +1,171 @@
+// 运行配置，从环境变量（以及 .env 文件）读取，未设置或无效时使用默认值
+
+use crate::backend::BackendKind;
+use crate::backup::BackupPolicy;
+use crate::crypto::{KdfParams, KdfPolicy, MIN_KDF_PARAMS};
+use log::warn;
+use std::str::FromStr;
+use std::time::Duration;
+
+const DEFAULT_KDF_TARGET_MS: u64 = 500;
+const DEFAULT_KDF_MAX_M_COST: u32 = 256 * 1024;
+const DEFAULT_BACKUP_COUNT: usize = 10;
+const DEFAULT_BACKUP_MAX_AGE_DAYS: i64 = 30;
+const DEFAULT_SQLITE_BACKUP_INTERVAL_SECS: i64 = 60 * 60;
+const DEFAULT_IDLE_LOCK_SECS: u64 = 15 * 60;
+const DEFAULT_SAVE_DEBOUNCE_MS: u64 = 500;
+
+pub struct Config {
+    pub storage_backend: BackendKind,
+    // 数据文件路径，None 时使用后端的默认路径（file 为 data.enc，sqlite 为 data.db）
+    pub data_path: Option<String>,
+    pub kdf: KdfPolicy,
+    pub backup: BackupPolicy,
+    // 空闲自动锁定时长，None 表示不自动锁定
+    pub idle_lock: Option<Duration>,
+    // 修改后等待多久再写入文件，期间的修改合并为一次保存
+    pub save_debounce: Duration,
+    // 启动时自动解锁使用的密钥文件，None 时需要通过 /api/unlock 解锁
+    pub unlock_key: Option<UnlockSource>,
+    // 通过 API 添加密钥文件槽时，密钥文件只能位于这个目录中
+    pub key_file_dir: String,
+}
+
+// 启动时读取的解锁秘密（任一密钥槽的秘密，通常是密钥文件槽的文件）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnlockSource {
+    // 直接指定的文件路径
+    KeyFile(String),
+    // systemd 凭据的名称（LoadCredential= / SetCredentialEncrypted=），
+    // 文件位于 $CREDENTIALS_DIRECTORY/<名称>
+    Credential(String),
+}
+
+impl std::fmt::Display for UnlockSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnlockSource::KeyFile(path) => write!(f, "key file {}", path),
+            UnlockSource::Credential(name) => write!(f, "credential {}", name),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        // WEB_TOTP_STORAGE    存储后端：file（默认）、sqlite 或 memory
+        // WEB_TOTP_DATA_PATH  数据文件路径，默认为当前目录下的 data.enc（sqlite 为 data.db）
+        let storage_backend = env_value("WEB_TOTP_STORAGE").unwrap_or(BackendKind::File);
+        let data_path = std::env::var("WEB_TOTP_DATA_PATH").ok();
+        Self {
+            storage_backend,
+            // WEB_TOTP_KEY_FILE_DIR  密钥文件目录，默认为数据文件所在目录下的 keys
+            key_file_dir: std::env::var("WEB_TOTP_KEY_FILE_DIR").ok()
+                .filter(|dir| !dir.trim().is_empty())
+                .unwrap_or_else(|| default_key_file_dir(data_path.as_deref())),
+            data_path,
+            kdf: kdf_policy_from_env(),
+            backup: backup_policy_from_env(storage_backend),
+            // WEB_TOTP_IDLE_LOCK_SECS  无操作多少秒后自动锁定数据库，0 表示不自动锁定
+            idle_lock: Some(env_value("WEB_TOTP_IDLE_LOCK_SECS").unwrap_or(DEFAULT_IDLE_LOCK_SECS))
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            // WEB_TOTP_SAVE_DEBOUNCE_MS  修改后延迟保存的毫秒数，0 表示尽快保存
+            save_debounce: Duration::from_millis(
+                env_value("WEB_TOTP_SAVE_DEBOUNCE_MS").unwrap_or(DEFAULT_SAVE_DEBOUNCE_MS),
+            ),
+            unlock_key: unlock_source_from_env(),
+        }
+    }
+}
+
+fn env_value<T: FromStr>(name: &str) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            warn!("Ignoring invalid value for {}: {}", name, value);
+            None
+        }
+    }
+}
+
+fn default_key_file_dir(data_path: Option<&str>) -> String {
+    let parent = data_path
+        .and_then(|path| std::path::Path::new(path).parent())
+        .unwrap_or_else(|| std::path::Path::new(""));
+    parent.join("keys").to_string_lossy().into_owned()
+}
+
+// WEB_TOTP_UNLOCK_KEY_FILE     启动时用该文件的内容解锁
+// WEB_TOTP_UNLOCK_CREDENTIAL   启动时用该名称的 systemd 凭据解锁；同时设置时使用 WEB_TOTP_UNLOCK_KEY_FILE
+fn unlock_source_from_env() -> Option<UnlockSource> {
+    let non_empty = |name| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+    let key_file = non_empty("WEB_TOTP_UNLOCK_KEY_FILE");
+    let credential = non_empty("WEB_TOTP_UNLOCK_CREDENTIAL");
+
+    match (key_file, credential) {
+        (Some(path), Some(_)) => {
+            warn!("Both WEB_TOTP_UNLOCK_KEY_FILE and WEB_TOTP_UNLOCK_CREDENTIAL are set, using the key file");
+            Some(UnlockSource::KeyFile(path))
+        }
+        (Some(path), None) => Some(UnlockSource::KeyFile(path)),
+        (None, Some(name)) => Some(UnlockSource::Credential(name.trim().to_string())),
+        (None, None) => None,
+    }
+}
+
+// WEB_TOTP_KDF_M_COST / _T_COST / _P_COST   固定的 Argon2id 参数，设置任一项即关闭基准测试
+// WEB_TOTP_KDF_TARGET_MS                    基准测试的目标解锁耗时（毫秒）
+// WEB_TOTP_KDF_MAX_M_COST                   基准测试允许使用的最大内存（KiB）
+fn kdf_policy_from_env() -> KdfPolicy {
+    let m_cost = env_value("WEB_TOTP_KDF_M_COST");
+    let t_cost = env_value("WEB_TOTP_KDF_T_COST");
+    let p_cost = env_value("WEB_TOTP_KDF_P_COST");
+
+    let fixed = match (m_cost, t_cost, p_cost) {
+        (None, None, None) => None,
+        _ => Some(KdfParams {
+            m_cost: m_cost.unwrap_or(MIN_KDF_PARAMS.m_cost),
+            t_cost: t_cost.unwrap_or(MIN_KDF_PARAMS.t_cost),
+            p_cost: p_cost.unwrap_or(MIN_KDF_PARAMS.p_cost),
+        }),
+    };
+    let fixed = fixed.filter(|p| {
+        let valid = argon2::Params::new(p.m_cost, p.t_cost, p.p_cost, None).is_ok();
+        if !valid {
+            warn!("Ignoring invalid KDF parameters {:?}, falling back to calibration", p);
+        }
+        valid
+    });
+
+    KdfPolicy {
+        fixed,
+        // 配置了固定参数时，低于该参数的数据文件会在解锁时重新加密
+        minimum: fixed.unwrap_or(MIN_KDF_PARAMS),
+        target: Duration::from_millis(env_value("WEB_TOTP_KDF_TARGET_MS").unwrap_or(DEFAULT_KDF_TARGET_MS)),
+        max_m_cost: env_value("WEB_TOTP_KDF_MAX_M_COST").unwrap_or(DEFAULT_KDF_MAX_M_COST),
+    }
+}
+
+// WEB_TOTP_BACKUP_COUNT           保留的自动备份份数，0 表示关闭自动备份
+// WEB_TOTP_BACKUP_MAX_AGE_DAYS    自动备份的最长保留天数，0 表示不按时间清理
+// WEB_TOTP_BACKUP_INTERVAL_SECS   两次自动备份的最短间隔，0 表示每次保存前都备份；
+//                                 sqlite 后端默认 3600（备份要复制整个数据库），其他后端默认 0
+fn backup_policy_from_env(storage_backend: BackendKind) -> BackupPolicy {
+    let max_age_days = env_value("WEB_TOTP_BACKUP_MAX_AGE_DAYS")
+        .filter(|days: &i64| *days >= 0)
+        .unwrap_or(DEFAULT_BACKUP_MAX_AGE_DAYS);
+    let default_interval = match storage_backend {
+        BackendKind::Sqlite => DEFAULT_SQLITE_BACKUP_INTERVAL_SECS,
+        BackendKind::File | BackendKind::Memory => 0,
+    };
+    let interval_secs = env_value("WEB_TOTP_BACKUP_INTERVAL_SECS")
+        .filter(|secs: &i64| *secs >= 0)
+        .unwrap_or(default_interval);
+
+    BackupPolicy {
+        count: env_value("WEB_TOTP_BACKUP_COUNT").unwrap_or(DEFAULT_BACKUP_COUNT),
+        max_age: (max_age_days > 0).then(|| chrono::Duration::days(max_age_days)),
+        min_interval: (interval_secs > 0).then(|| chrono::Duration::seconds(interval_secs)),
+    }
+}
@@ -0,0 +1,482 @@
+use crate::error::{AppError, Result};
+use aes_gcm::{
+    aead::{Aead, NewAead, Payload},
+    Aes256Gcm, Nonce, Key,
+};
+use log::debug;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+pub const NONCE_SIZE: usize = 12;
+pub const SALT_SIZE: usize = 16;
+pub const KEY_SIZE: usize = 32;
+// AES-GCM 认证标签长度，附加在密文末尾
+pub const TAG_SIZE: usize = 16;
+
+// Argon2id 参数（内存单位为 KiB）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    // 与 Argon2::default() 相同，data.enc 一直使用这组参数
+    fn default() -> Self {
+        Self {
+            m_cost: argon2::Params::DEFAULT_M_COST,
+            t_cost: argon2::Params::DEFAULT_T_COST,
+            p_cost: argon2::Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+// 数据文件允许的最低参数（OWASP 推荐的 Argon2id 配置：19 MiB 内存、2 次迭代）
+pub const MIN_KDF_PARAMS: KdfParams = KdfParams {
+    m_cost: 19 * 1024,
+    t_cost: 2,
+    p_cost: 1,
+};
+
+// 基准测试时迭代次数的上限
+const MAX_CALIBRATED_T_COST: u32 = 16;
+
+// 读取外部文件（导出文件和其他应用的备份）中的参数时允许的上限，避免伪造的文件让服务器耗尽内存或长时间占用 CPU。
+// 内存与基准测试默认允许的最大值相同
+pub const MAX_KDF_PARAMS: KdfParams = KdfParams {
+    m_cost: 256 * 1024,
+    t_cost: MAX_CALIBRATED_T_COST,
+    p_cost: 16,
+};
+
+impl KdfParams {
+    // 内存或迭代次数任一低于 other 即视为更弱，并行度不影响强度
+    pub fn is_weaker_than(&self, other: &KdfParams) -> bool {
+        self.m_cost < other.m_cost || self.t_cost < other.t_cost
+    }
+
+    // 每一项都在 MIN_KDF_PARAMS 和 MAX_KDF_PARAMS 之间
+    pub fn is_within_limits(&self) -> bool {
+        *self == self.clamp_to_limits()
+    }
+
+    pub fn clamp_to_limits(&self) -> KdfParams {
+        KdfParams {
+            m_cost: self.m_cost.clamp(MIN_KDF_PARAMS.m_cost, MAX_KDF_PARAMS.m_cost),
+            t_cost: self.t_cost.clamp(MIN_KDF_PARAMS.t_cost, MAX_KDF_PARAMS.t_cost),
+            p_cost: self.p_cost.clamp(MIN_KDF_PARAMS.p_cost, MAX_KDF_PARAMS.p_cost),
+        }
+    }
+}
+
+// 数据文件的 KDF 策略：
+// 配置了固定参数时直接使用；否则以 minimum 为起点做基准测试，
+// 逐步增加内存和迭代次数，使一次密钥派生耗时接近 target
+#[derive(Debug, Clone)]
+pub struct KdfPolicy {
+    pub fixed: Option<KdfParams>,
+    pub minimum: KdfParams,
+    pub target: Duration,
+    pub max_m_cost: u32,
+}
+
+impl KdfPolicy {
+    pub fn is_satisfied_by(&self, params: &KdfParams) -> bool {
+        !params.is_weaker_than(&self.minimum)
+    }
+
+    // 新建或重新加密数据文件时使用的参数
+    pub fn select_params(&self) -> Result<KdfParams> {
+        match self.fixed {
+            Some(params) => Ok(params),
+            None => calibrate(self.minimum, self.target, self.max_m_cost),
+        }
+    }
+}
+
+fn time_derivation(params: &KdfParams) -> Result<Duration> {
+    let start = Instant::now();
+    derive_key("web-totp-benchmark", &[0u8; SALT_SIZE], params)?;
+    Ok(start.elapsed())
+}
+
+// 先按倍数增加内存，再按比例增加迭代次数，结果不会低于 minimum
+pub fn calibrate(minimum: KdfParams, target: Duration, max_m_cost: u32) -> Result<KdfParams> {
+    let mut params = minimum;
+    let mut elapsed = time_derivation(&params)?;
+
+    while elapsed * 2 <= target && params.m_cost.saturating_mul(2) <= max_m_cost {
+        params.m_cost *= 2;
+        elapsed = time_derivation(&params)?;
+    }
+
+    if elapsed < target {
+        let scale = target.as_secs_f64() / elapsed.as_secs_f64().max(f64::EPSILON);
+        let t_cost = (params.t_cost as f64 * scale).floor() as u32;
+        params.t_cost = t_cost.clamp(params.t_cost, MAX_CALIBRATED_T_COST.max(params.t_cost));
+    }
+
+    debug!("KDF calibration: {:?} (last measurement {:?})", params, elapsed);
+    Ok(params)
+}
+
+pub fn random_salt() -> [u8; SALT_SIZE] {
+    let mut salt = [0u8; SALT_SIZE];
+    rand::RngCore::fill_bytes(&mut OsRng, &mut salt);
+    salt
+}
+
+// 从密码派生 AES-256 密钥
+pub fn derive_key(password: &str, salt: &[u8], params: &KdfParams) -> Result<[u8; KEY_SIZE]> {
+    use argon2::{Algorithm, Argon2, Params, Version};
+
+    let params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(KEY_SIZE))
+        .map_err(|e| AppError::Encryption(format!("Invalid KDF parameters: {}", e)))?;
+
+    let mut key = [0u8; KEY_SIZE];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| AppError::Encryption(format!("Key derivation failed: {}", e)))?;
+
+    Ok(key)
+}
+
+// 数据密钥：随机生成，加密全部数据，解锁后内存中只保留它而不保留主密码。
+// 数据文件中不保存数据密钥本身，而是由一个或多个密钥槽分别包装（见 KeySlot）；
+// 增删密钥槽或更换主密码只重新包装数据密钥，不重新加密数据。释放时清零，Debug 不输出密钥
+#[derive(Clone)]
+pub struct MasterKey {
+    key: Zeroizing<[u8; KEY_SIZE]>,
+    // 随数据密钥一起生成，写在文件头中，密钥槽包装时作为附加数据
+    pub key_id: [u8; KEY_ID_SIZE],
+    pub slots: Vec<KeySlot>,
+}
+
+pub const KEY_ID_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlotKind {
+    Password,
+    // 创建数据库时生成并只显示一次的恢复密钥
+    RecoveryKey,
+    // 保存在磁盘上的密钥文件（内容为文本）
+    KeyFile,
+}
+
+impl SlotKind {
+    pub fn id(self) -> u8 {
+        match self {
+            SlotKind::Password => 1,
+            SlotKind::RecoveryKey => 2,
+            SlotKind::KeyFile => 3,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(SlotKind::Password),
+            2 => Some(SlotKind::RecoveryKey),
+            3 => Some(SlotKind::KeyFile),
+            _ => None,
+        }
+    }
+
+    // 派生密钥前的输入处理；恢复密钥格式固定，不符合格式的输入不必尝试（返回 None）
+    fn prepare(self, secret: &str) -> Option<Zeroizing<String>> {
+        match self {
+            SlotKind::RecoveryKey => normalize_recovery_key(secret),
+            SlotKind::Password | SlotKind::KeyFile => Some(Zeroizing::new(secret.to_string())),
+        }
+    }
+}
+
+// 密钥槽：用 Argon2id(秘密, salt, kdf_params) 派生的密钥以 AES-256-GCM 包装数据密钥。
+// 包装时的附加数据为 key_id || kind || id || KDF 参数 || salt，
+// 修改参数、类型或把槽复制到其他数据文件都会导致解包失败
+#[derive(Debug, Clone)]
+pub struct KeySlot {
+    pub id: String,
+    pub kind: SlotKind,
+    // 显示用的说明，密钥文件槽为文件路径
+    pub label: String,
+    // Unix 时间戳（秒）
+    pub created_at: i64,
+    pub kdf_params: KdfParams,
+    pub salt: Vec<u8>,
+    pub nonce: [u8; NONCE_SIZE],
+    pub wrapped_key: Vec<u8>,
+}
+
+impl KeySlot {
+    fn aad(&self, key_id: &[u8; KEY_ID_SIZE]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(KEY_ID_SIZE + 2 + self.id.len() + 12 + self.salt.len());
+        out.extend_from_slice(key_id);
+        out.push(self.kind.id());
+        out.extend_from_slice(self.id.as_bytes());
+        out.push(0);
+        out.extend_from_slice(&self.kdf_params.m_cost.to_le_bytes());
+        out.extend_from_slice(&self.kdf_params.t_cost.to_le_bytes());
+        out.extend_from_slice(&self.kdf_params.p_cost.to_le_bytes());
+        out.extend_from_slice(&self.salt);
+        out
+    }
+
+    // 用已派生的槽密钥包装数据密钥
+    fn seal(&mut self, slot_key: &[u8; KEY_SIZE], master: &MasterKey) -> Result<()> {
+        let (nonce, wrapped_key) = encrypt_with_aad(slot_key, master.key(), &self.aad(&master.key_id))?;
+        self.nonce = nonce;
+        self.wrapped_key = wrapped_key;
+        Ok(())
+    }
+
+    // 秘密不匹配时返回 None
+    fn open(&self, key_id: &[u8; KEY_ID_SIZE], secret: &str) -> Result<Option<Zeroizing<[u8; KEY_SIZE]>>> {
+        let Some(secret) = self.kind.prepare(secret) else {
+            return Ok(None);
+        };
+        let slot_key = Zeroizing::new(derive_key(&secret, &self.salt, &self.kdf_params)?);
+        let Ok(key) = decrypt_with_aad(&slot_key, &self.nonce, &self.wrapped_key, &self.aad(key_id)) else {
+            return Ok(None);
+        };
+        let key = Zeroizing::new(key);
+        let key: [u8; KEY_SIZE] = key.as_slice().try_into()
+            .map_err(|_| AppError::Encryption("Invalid wrapped key length".to_string()))?;
+        Ok(Some(Zeroizing::new(key)))
+    }
+}
+
+impl MasterKey {
+    // 新的随机数据密钥，还没有密钥槽
+    pub fn generate() -> Self {
+        Self {
+            key: Zeroizing::new(rand::random()),
+            key_id: rand::random(),
+            slots: Vec::new(),
+        }
+    }
+
+    // 版本 3 之前数据直接用主密码派生的密钥加密：生成新的数据密钥，并用原来的派生结果
+    // （盐值和参数不变）包装为密码槽，升级时不需要再运行一次 Argon2。返回新密钥和密码槽 ID
+    pub fn from_legacy(derived: &[u8; KEY_SIZE], salt: &[u8], params: KdfParams) -> Result<(Self, String)> {
+        let mut master = Self::generate();
+        let id = master.push_slot(SlotKind::Password, String::new(), derived, salt.to_vec(), params)?;
+        Ok((master, id))
+    }
+
+    // 依次尝试每个密钥槽，返回数据密钥和解开的槽 ID；没有槽能用该秘密解开时返回 None
+    pub fn open(key_id: [u8; KEY_ID_SIZE], slots: Vec<KeySlot>, secret: &str) -> Result<Option<(Self, String)>> {
+        for slot in &slots {
+            if let Some(key) = slot.open(&key_id, secret)? {
+                let id = slot.id.clone();
+                return Ok(Some((Self { key, key_id, slots }, id)));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn key(&self) -> &[u8; KEY_SIZE] {
+        &self.key
+    }
+
+    pub fn slot(&self, id: &str) -> Option<&KeySlot> {
+        self.slots.iter().find(|s| s.id == id)
+    }
+
+    // 用户输入的秘密（主密码、恢复密钥或密钥文件内容）能解开的槽；
+    // 解出的数据密钥必须与当前的一致
+    pub fn find_slot(&self, secret: &str) -> Result<Option<String>> {
+        for slot in &self.slots {
+            if let Some(candidate) = slot.open(&self.key_id, secret)? {
+                let diff = candidate.iter().zip(self.key.iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b));
+                if diff == 0 {
+                    return Ok(Some(slot.id.clone()));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // 添加密钥槽（使用新的随机盐值），返回槽 ID
+    pub fn add_slot(&mut self, kind: SlotKind, label: &str, secret: &str, params: KdfParams) -> Result<String> {
+        let secret = kind.prepare(secret)
+            .ok_or_else(|| AppError::Encryption("Invalid recovery key".to_string()))?;
+        let salt = random_salt();
+        let slot_key = Zeroizing::new(derive_key(&secret, &salt, &params)?);
+        self.push_slot(kind, label.to_string(), &slot_key, salt.to_vec(), params)
+    }
+
+    // 用新的秘密、盐值和参数重新包装已有的槽，ID、类型和说明不变
+    pub fn rewrap_slot(&mut self, id: &str, secret: &str, params: KdfParams) -> Result<()> {
+        let index = self.slots.iter().position(|s| s.id == id)
+            .ok_or_else(|| AppError::Encryption(format!("Key slot {} not found", id)))?;
+        let kind = self.slots[index].kind;
+        let secret = kind.prepare(secret)
+            .ok_or_else(|| AppError::Encryption("Invalid recovery key".to_string()))?;
+        let salt = random_salt();
+        let slot_key = Zeroizing::new(derive_key(&secret, &salt, &params)?);
+
+        let mut slot = self.slots[index].clone();
+        slot.kdf_params = params;
+        slot.salt = salt.to_vec();
+        slot.seal(&slot_key, self)?;
+        self.slots[index] = slot;
+        Ok(())
+    }
+
+    // 槽不存在时返回 false
+    pub fn remove_slot(&mut self, id: &str) -> bool {
+        let before = self.slots.len();
+        self.slots.retain(|s| s.id != id);
+        self.slots.len() != before
+    }
+
+    fn push_slot(&mut self, kind: SlotKind, label: String, slot_key: &[u8; KEY_SIZE], salt: Vec<u8>, params: KdfParams) -> Result<String> {
+        let mut slot = KeySlot {
+            id: self.new_slot_id(),
+            kind,
+            label,
+            created_at: chrono::Utc::now().timestamp(),
+            kdf_params: params,
+            salt,
+            nonce: [0u8; NONCE_SIZE],
+            wrapped_key: Vec::new(),
+        };
+        slot.seal(slot_key, self)?;
+        let id = slot.id.clone();
+        self.slots.push(slot);
+        Ok(id)
+    }
+
+    fn new_slot_id(&self) -> String {
+        loop {
+            let id = hex::encode(rand::random::<[u8; 4]>());
+            if self.slot(&id).is_none() {
+                return id;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MasterKey")
+            .field("key", &"[REDACTED]")
+            .field("key_id", &hex::encode(self.key_id))
+            .field("slots", &self.slots.iter().map(|s| &s.id).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+// 恢复密钥：20 个随机字节的 Base32（32 个字符），每 4 个字符用 - 分隔，便于抄写
+pub fn generate_recovery_key() -> Zeroizing<String> {
+    let bytes = Zeroizing::new(rand::random::<[u8; 20]>());
+    let encoded = Zeroizing::new(crate::totp_manager::encode_base32(bytes.as_slice()));
+    let groups: Vec<&str> = encoded.as_bytes()
+        .chunks(4)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap())
+        .collect();
+    Zeroizing::new(groups.join("-"))
+}
+
+// 忽略大小写、空白和分隔符；不是 32 个 Base32 字符时返回 None
+fn normalize_recovery_key(input: &str) -> Option<Zeroizing<String>> {
+    let normalized: Zeroizing<String> = Zeroizing::new(input.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect());
+    let valid = normalized.len() == 32
+        && normalized.chars().all(|c| c.is_ascii_uppercase() || ('2'..='7').contains(&c));
+    valid.then_some(normalized)
+}
+
+// AES-256-GCM 加密，每次使用新的随机 nonce
+pub fn encrypt(key: &[u8; KEY_SIZE], plaintext: &[u8]) -> Result<([u8; NONCE_SIZE], Vec<u8>)> {
+    encrypt_with_aad(key, plaintext, &[])
+}
+
+// 附加数据（AAD）不加密，但与密文一起认证，用于绑定文件头等元数据
+pub fn encrypt_with_aad(key: &[u8; KEY_SIZE], plaintext: &[u8], aad: &[u8]) -> Result<([u8; NONCE_SIZE], Vec<u8>)> {
+    let nonce_bytes: [u8; NONCE_SIZE] = rand::random();
+    let cipher = Aes256Gcm::new(&Key::from(*key));
+    let ciphertext = cipher
+        .encrypt(&Nonce::from(nonce_bytes), Payload { msg: plaintext, aad })
+        .map_err(|e| AppError::Encryption(format!("Encryption failed: {}", e)))?;
+
+    Ok((nonce_bytes, ciphertext))
+}
+
+// 解密失败（密钥错误或数据被篡改）统一返回 Encryption 错误，由调用方决定如何提示
+pub fn decrypt(key: &[u8; KEY_SIZE], nonce: &[u8; NONCE_SIZE], ciphertext: &[u8]) -> Result<Vec<u8>> {
+    decrypt_with_aad(key, nonce, ciphertext, &[])
+}
+
+pub fn decrypt_with_aad(key: &[u8; KEY_SIZE], nonce: &[u8; NONCE_SIZE], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    let cipher = Aes256Gcm::new(&Key::from(*key));
+    cipher
+        .decrypt(&Nonce::from(*nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| AppError::Encryption("Decryption failed".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TEST_PARAMS;
+    use std::mem::ManuallyDrop;
+
+    #[test]
+    fn master_key_debug_redacts_key() {
+        let mut key = MasterKey::generate();
+        key.add_slot(SlotKind::Password, "", "pw", TEST_PARAMS).unwrap();
+        let debug = format!("{:?}", key);
+        assert!(debug.contains("[REDACTED]"));
+        assert!(!debug.contains(&hex::encode(key.key())));
+        assert!(!debug.contains(&format!("{:?}", key.key())));
+    }
+
+    #[test]
+    fn master_key_is_zeroized_on_drop() {
+        // ManuallyDrop 保证释放后这块内存仍然有效，可以读取密钥原来的位置
+        let mut key = ManuallyDrop::new(MasterKey::generate());
+        let ptr = key.key().as_ptr();
+        assert_ne!(unsafe { std::ptr::read_volatile(ptr as *const [u8; KEY_SIZE]) }, [0u8; KEY_SIZE]);
+        unsafe { ManuallyDrop::drop(&mut key) };
+        assert_eq!(unsafe { std::ptr::read_volatile(ptr as *const [u8; KEY_SIZE]) }, [0u8; KEY_SIZE]);
+    }
+
+    #[test]
+    fn slots_open_with_their_own_secret() {
+        let mut key = MasterKey::generate();
+        let password_slot = key.add_slot(SlotKind::Password, "", "pw", TEST_PARAMS).unwrap();
+        let recovery_key = generate_recovery_key();
+        let recovery_slot = key.add_slot(SlotKind::RecoveryKey, "", &recovery_key, TEST_PARAMS).unwrap();
+
+        let (opened, id) = MasterKey::open(key.key_id, key.slots.clone(), "pw").unwrap().unwrap();
+        assert_eq!(id, password_slot);
+        assert_eq!(opened.key(), key.key());
+
+        // 恢复密钥忽略大小写和分隔符
+        let typed = recovery_key.replace('-', "").to_lowercase();
+        let (_, id) = MasterKey::open(key.key_id, key.slots.clone(), &typed).unwrap().unwrap();
+        assert_eq!(id, recovery_slot);
+
+        assert!(MasterKey::open(key.key_id, key.slots.clone(), "wrong").unwrap().is_none());
+        // 槽绑定数据密钥 ID，复制到其他数据文件无法解开
+        assert!(MasterKey::open(rand::random(), key.slots.clone(), "pw").unwrap().is_none());
+    }
+
+    // 无论目标时长多少，结果都不低于最低参数，也不超过读取外部文件时允许的上限
+    #[test]
+    fn calibrate_stays_within_limits() {
+        for target in [Duration::ZERO, Duration::from_millis(100)] {
+            let params = calibrate(MIN_KDF_PARAMS, target, MAX_KDF_PARAMS.m_cost).unwrap();
+            assert!(params.is_within_limits(), "{:?}", params);
+            assert!(!params.is_weaker_than(&MIN_KDF_PARAMS), "{:?}", params);
+        }
+        assert_eq!(calibrate(MIN_KDF_PARAMS, Duration::ZERO, MAX_KDF_PARAMS.m_cost).unwrap(), MIN_KDF_PARAMS);
+    }
+}
@@ -8,18 +8,47 @@ pub enum AppError {
     #[error("Encryption error: {0}")]
     Encryption(String),
     
+    #[allow(dead_code)]
     #[error("Authentication error: {0}")]
     Auth(String),
     
     #[error("TOTP error: {0}")]
     Totp(String),
     
+    // 主密码错误，或数据被篡改、损坏导致整体认证失败（两者在密码学上无法区分）
     #[error("Invalid master password")]
     InvalidMasterPassword,
     
+    #[error("Data file is truncated")]
+    VaultTruncated,
+    
+    #[error("Invalid data file header: {0}")]
+    InvalidHeader(String),
+    
+    // 主密码正确，但部分数据（例如 SQLite 后端的单个条目）认证失败
+    #[error("Data failed authentication: {0}")]
+    AuthenticationFailed(String),
+    
+    #[error("Cannot remove the last key slot")]
+    LastKeySlot,
+    
+    // 密钥文件可以被所有者以外的用户读写
+    #[error("Key file has overly permissive permissions: {0}")]
+    InsecureKeyFile(String),
+    
+    // 通过 API 添加的密钥文件不在 WEB_TOTP_KEY_FILE_DIR 目录中
+    #[error("Invalid key file path: {0}")]
+    InvalidKeyFilePath(String),
+    
     #[error("Database locked, master password required")]
     DatabaseLocked,
     
+    #[error("Data file is in use by another process: {0}")]
+    VaultInUse(String),
+    
+    #[error("Data was modified by another process since it was loaded")]
+    ModifiedExternally,
+    
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     
@@ -30,5 +59,12 @@ pub enum AppError {
     Utf8(#[from] std::string::FromUtf8Error),
 }
 
+// SQLite 后端的错误都属于存储错误（不能被当作主密码错误）
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        AppError::Storage(format!("SQLite error: {}", e))
+    }
+}
+
 pub type Result<T> = std::result::Result<T, AppError>;
 
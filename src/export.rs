@@ -0,0 +1,245 @@
+// 可移植的加密导出格式（见 docs/EXPORT_FORMAT.md）
+//
+// 导出文件是一个 JSON 对象，条目列表用独立的导出密码经 Argon2id 派生密钥后
+// 以 AES-256-GCM 加密，KDF 参数和盐值随文件保存，因此可以在任何 web-totp
+// 实例上用导出密码恢复，与各实例的主密码无关。
+
+use crate::crypto::{self, KdfParams, NONCE_SIZE};
+use crate::error::{AppError, Result};
+use crate::models::{EntrySummary, TotpEntry, VaultImportMode};
+use crate::totp_manager;
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+pub const EXPORT_FORMAT: &str = "web-totp-export";
+pub const EXPORT_VERSION: u32 = 1;
+const KDF_ARGON2ID: &str = "argon2id";
+const CIPHER_AES_256_GCM: &str = "aes-256-gcm";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportFile {
+    pub format: String,
+    pub version: u32,
+    pub kdf: ExportKdf,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportKdf {
+    pub algorithm: String,
+    pub salt: String,
+    #[serde(flatten)]
+    pub params: KdfParams,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportPayload {
+    exported_at: String,
+    entries: Vec<TotpEntry>,
+}
+
+// params 为数据文件 KDF 策略选择的参数（见 Storage::select_kdf_params），
+// 限制在导入时接受的范围内，保证导出的文件在任何实例上都能导入
+pub fn export_entries(entries: &[TotpEntry], password: &str, params: KdfParams) -> Result<String> {
+    let params = params.clamp_to_limits();
+    let payload = ExportPayload {
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        entries: entries.to_vec(),
+    };
+    // 密钥和明文 JSON（包含所有密钥）用完即清零
+    let json = Zeroizing::new(serde_json::to_vec(&payload)?);
+
+    let salt = crypto::random_salt();
+    let key = Zeroizing::new(crypto::derive_key(password, &salt, &params)?);
+    let (nonce, ciphertext) = crypto::encrypt(&key, &json)?;
+
+    let file = ExportFile {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        kdf: ExportKdf {
+            algorithm: KDF_ARGON2ID.to_string(),
+            salt: general_purpose::STANDARD.encode(salt),
+            params,
+        },
+        cipher: CIPHER_AES_256_GCM.to_string(),
+        nonce: general_purpose::STANDARD.encode(nonce),
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+    };
+
+    Ok(serde_json::to_string_pretty(&file)?)
+}
+
+fn decode_field(name: &str, value: &str) -> Result<Vec<u8>> {
+    general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| AppError::Storage(format!("Invalid {} in export file: {}", name, e)))
+}
+
+pub fn import_entries(data: &str, password: &str) -> Result<Vec<TotpEntry>> {
+    let file: ExportFile = serde_json::from_str(data)
+        .map_err(|e| AppError::Storage(format!("Not a web-totp export file: {}", e)))?;
+
+    if file.format != EXPORT_FORMAT {
+        return Err(AppError::Storage(format!("Unknown export format: {}", file.format)));
+    }
+    if file.version != EXPORT_VERSION {
+        return Err(AppError::Storage(format!("Unsupported export version: {}", file.version)));
+    }
+    if file.kdf.algorithm != KDF_ARGON2ID || file.cipher != CIPHER_AES_256_GCM {
+        return Err(AppError::Storage(format!(
+            "Unsupported export algorithms: {} / {}",
+            file.kdf.algorithm, file.cipher
+        )));
+    }
+
+    // 参数来自文件本身，先检查范围再运行 Argon2
+    if !file.kdf.params.is_within_limits() {
+        return Err(AppError::Storage(format!(
+            "Unsupported KDF parameters in export file: {:?} (allowed {:?} to {:?})",
+            file.kdf.params, crypto::MIN_KDF_PARAMS, crypto::MAX_KDF_PARAMS
+        )));
+    }
+
+    let salt = decode_field("salt", &file.kdf.salt)?;
+    let nonce: [u8; NONCE_SIZE] = decode_field("nonce", &file.nonce)?
+        .try_into()
+        .map_err(|_| AppError::Storage("Invalid nonce length in export file".to_string()))?;
+    let ciphertext = decode_field("ciphertext", &file.ciphertext)?;
+
+    let key = Zeroizing::new(crypto::derive_key(password, &salt, &file.kdf.params)?);
+    let plaintext = Zeroizing::new(crypto::decrypt(&key, &nonce, &ciphertext)
+        .map_err(|_| AppError::Encryption("Invalid export password or corrupted file".to_string()))?);
+
+    let payload: ExportPayload = serde_json::from_slice(&plaintext)?;
+    for entry in &payload.entries {
+        totp_manager::validate_entry(entry)
+            .map_err(|e| AppError::Storage(format!("Invalid entry '{}' in export: {}", entry.name, e)))?;
+    }
+
+    Ok(payload.entries)
+}
+
+pub struct ImportPlan {
+    pub to_add: Vec<TotpEntry>,
+    pub added: Vec<EntrySummary>,
+    pub duplicates: Vec<EntrySummary>,
+    pub conflicts: Vec<EntrySummary>,
+    pub removed: usize,
+}
+
+// 同一发行者和账户名但密钥不同的条目视为冲突
+fn is_conflict(a: &TotpEntry, b: &TotpEntry) -> bool {
+    a.issuer.eq_ignore_ascii_case(&b.issuer)
+        && a.name.eq_ignore_ascii_case(&b.name)
+        && !totp_manager::is_duplicate(a, b)
+}
+
+// 计算导入结果：merge 模式只添加新条目，重复项和冲突项跳过；
+// replace 模式用导入的条目替换全部现有条目，重复和冲突仅作提示
+pub fn plan_import(existing: &[TotpEntry], incoming: Vec<TotpEntry>, mode: VaultImportMode) -> ImportPlan {
+    let mut plan = ImportPlan {
+        to_add: Vec::new(),
+        added: Vec::new(),
+        duplicates: Vec::new(),
+        conflicts: Vec::new(),
+        removed: 0,
+    };
+
+    for mut entry in incoming {
+        let summary = EntrySummary::from(&entry);
+        // 导入文件内部的重复项在两种模式下都跳过
+        if plan.to_add.iter().any(|e| totp_manager::is_duplicate(e, &entry)) {
+            plan.duplicates.push(summary);
+            continue;
+        }
+
+        let duplicate = existing.iter().any(|e| totp_manager::is_duplicate(e, &entry));
+        let conflict = existing.iter().any(|e| is_conflict(e, &entry));
+
+        if duplicate {
+            plan.duplicates.push(summary);
+            if mode == VaultImportMode::Merge {
+                continue;
+            }
+        } else if conflict {
+            plan.conflicts.push(summary);
+            if mode == VaultImportMode::Merge {
+                continue;
+            }
+        } else {
+            plan.added.push(summary);
+        }
+
+        // 保留原 id 以便跨实例对应，仅在 id 冲突时重新生成
+        if mode == VaultImportMode::Merge && existing.iter().any(|e| e.id == entry.id) {
+            entry.id = uuid::Uuid::new_v4().to_string();
+        }
+        plan.to_add.push(entry);
+    }
+
+    if mode == VaultImportMode::Replace {
+        plan.removed = existing.iter()
+            .filter(|e| !plan.to_add.iter().any(|n| totp_manager::is_duplicate(e, n)))
+            .count();
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{MAX_KDF_PARAMS, MIN_KDF_PARAMS};
+    use crate::testutil::{sample_entry, TEST_PARAMS};
+
+    fn sample_entries() -> Vec<TotpEntry> {
+        vec![sample_entry("alice")]
+    }
+
+    fn with_params(file: &str, params: KdfParams) -> String {
+        let mut file: ExportFile = serde_json::from_str(file).unwrap();
+        file.kdf.params = params;
+        serde_json::to_string(&file).unwrap()
+    }
+
+    #[test]
+    fn round_trips_with_policy_params() {
+        let file = export_entries(&sample_entries(), "pw", MIN_KDF_PARAMS).unwrap();
+        let imported = import_entries(&file, "pw").unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].secret.expose_secret(), "JBSWY3DPEHPK3PXP");
+        assert!(matches!(import_entries(&file, "wrong"), Err(AppError::Encryption(_))));
+    }
+
+    #[test]
+    fn clamps_export_params_to_import_limits() {
+        let file: ExportFile = serde_json::from_str(&export_entries(&sample_entries(), "pw", TEST_PARAMS).unwrap()).unwrap();
+        assert_eq!(file.kdf.params, MIN_KDF_PARAMS);
+
+        let huge = KdfParams { m_cost: u32::MAX, t_cost: u32::MAX, p_cost: u32::MAX };
+        assert_eq!(huge.clamp_to_limits(), MAX_KDF_PARAMS);
+    }
+
+    // 超出范围的参数在运行 Argon2 之前就被拒绝
+    #[test]
+    fn rejects_params_outside_limits() {
+        let file = export_entries(&sample_entries(), "pw", MIN_KDF_PARAMS).unwrap();
+        let too_weak = [
+            KdfParams { m_cost: MIN_KDF_PARAMS.m_cost - 1, ..MIN_KDF_PARAMS },
+            KdfParams { t_cost: 1, ..MIN_KDF_PARAMS },
+            KdfParams { p_cost: 0, ..MIN_KDF_PARAMS },
+        ];
+        let too_strong = [
+            KdfParams { m_cost: 4 * 1024 * 1024, ..MIN_KDF_PARAMS },
+            KdfParams { t_cost: 1_000_000, ..MIN_KDF_PARAMS },
+            KdfParams { p_cost: 255, ..MIN_KDF_PARAMS },
+        ];
+        for params in too_weak.into_iter().chain(too_strong) {
+            let result = import_entries(&with_params(&file, params), "pw");
+            assert!(matches!(result, Err(AppError::Storage(_))), "{:?} should be rejected", params);
+        }
+    }
+}
@@ -0,0 +1,437 @@
+// data.enc 文件格式（见 docs/DATA_FORMAT.md）
+//
+// 版本 3（当前）数据用随机的数据密钥加密，数据密钥由密钥槽包装：
+//
+//   magic       8 字节  "WEBTOTP\0"
+//   version     u16     文件格式版本
+//   key_id      16 字节 数据密钥 ID
+//   cipher_id   u8      1 = AES-256-GCM
+//   slot_count  u8
+//   slots       slot_count 个密钥槽（见 encode_slot）
+//   nonce_len   u8
+//   nonce       nonce_len 字节
+//   ciphertext  其余部分
+//
+// 从 magic 到 cipher_id 的字节作为 AEAD 附加数据参与认证。密钥槽不在其中，
+// 增删密钥槽不影响数据的密文；每个槽由自己的认证标签保护（见 crypto::KeySlot）。
+//
+// 版本 2 的文件头在 version 之后是 KDF 参数，数据密钥直接由主密码派生：
+//
+//   kdf_id      u8      1 = Argon2id (v0x13)
+//   m_cost      u32     Argon2 内存（KiB）
+//   t_cost      u32     Argon2 迭代次数
+//   p_cost      u32     Argon2 并行度
+//   salt_len    u8
+//   salt        salt_len 字节
+//   cipher_id   u8
+//   nonce_len / nonce / ciphertext 同上（没有密钥槽）
+//
+// 整数均为小端序。
+//
+// 版本 1 是没有文件头的旧格式：salt(16) || nonce(12) || ciphertext，
+// 固定使用默认 Argon2id 参数和 AES-256-GCM，不带附加数据。
+//
+// 解析错误分为两类：数据不完整（VaultTruncated）和文件头字段无效（InvalidHeader），
+// 与密码错误或数据被篡改导致的解密失败区分开。
+
+use crate::crypto::{KdfParams, KeySlot, SlotKind, KEY_ID_SIZE, NONCE_SIZE, SALT_SIZE, TAG_SIZE};
+use crate::error::{AppError, Result};
+
+pub const MAGIC: &[u8; 8] = b"WEBTOTP\0";
+pub const LEGACY_VERSION: u16 = 1;
+// 版本 3 之前数据密钥由主密码派生
+const DERIVED_KEY_VERSION: u16 = 2;
+pub const CURRENT_VERSION: u16 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfAlgorithm {
+    Argon2id,
+}
+
+impl KdfAlgorithm {
+    fn id(self) -> u8 {
+        match self {
+            KdfAlgorithm::Argon2id => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self> {
+        match id {
+            1 => Ok(KdfAlgorithm::Argon2id),
+            _ => Err(AppError::InvalidHeader(format!("unsupported KDF id {}", id))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherAlgorithm {
+    Aes256Gcm,
+}
+
+impl CipherAlgorithm {
+    fn id(self) -> u8 {
+        match self {
+            CipherAlgorithm::Aes256Gcm => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self> {
+        match id {
+            1 => Ok(CipherAlgorithm::Aes256Gcm),
+            _ => Err(AppError::InvalidHeader(format!("unsupported cipher id {}", id))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileHeader {
+    pub version: u16,
+    pub key_source: KeySource,
+    pub cipher: CipherAlgorithm,
+}
+
+// 数据密钥的来源
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    // 版本 1、2：由主密码直接派生
+    Derived {
+        kdf: KdfAlgorithm,
+        kdf_params: KdfParams,
+        salt: Vec<u8>,
+    },
+    // 版本 3：随机数据密钥，由密钥槽包装
+    Slots { key_id: [u8; KEY_ID_SIZE] },
+}
+
+// 解析后的数据文件，aad 为需要参与认证的文件头字节（旧格式为空）
+pub struct EncryptedFile<'a> {
+    pub header: FileHeader,
+    pub aad: &'a [u8],
+    // 版本 3 之前为空
+    pub slots: Vec<KeySlot>,
+    pub nonce: [u8; NONCE_SIZE],
+    pub ciphertext: &'a [u8],
+}
+
+impl FileHeader {
+    pub fn new(key_id: [u8; KEY_ID_SIZE]) -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            key_source: KeySource::Slots { key_id },
+            cipher: CipherAlgorithm::Aes256Gcm,
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version < CURRENT_VERSION
+    }
+
+    // 文件头中参与认证的部分（magic 到 cipher_id）
+    pub fn to_aad(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(MAGIC.len() + 40);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&self.version.to_le_bytes());
+        match &self.key_source {
+            KeySource::Derived { kdf, kdf_params, salt } => {
+                out.push(kdf.id());
+                out.extend_from_slice(&kdf_params.m_cost.to_le_bytes());
+                out.extend_from_slice(&kdf_params.t_cost.to_le_bytes());
+                out.extend_from_slice(&kdf_params.p_cost.to_le_bytes());
+                out.push(salt.len() as u8);
+                out.extend_from_slice(salt);
+            }
+            KeySource::Slots { key_id } => out.extend_from_slice(key_id),
+        }
+        out.push(self.cipher.id());
+        out
+    }
+
+    // 按当前格式写出完整文件，aad 必须是加密时使用的 to_aad() 结果
+    pub fn encode(aad: &[u8], slots: &[KeySlot], nonce: &[u8; NONCE_SIZE], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(aad.len() + 2 + slots.len() * 128 + NONCE_SIZE + ciphertext.len());
+        out.extend_from_slice(aad);
+        let slot_count = u8::try_from(slots.len())
+            .map_err(|_| AppError::Storage("Too many key slots".to_string()))?;
+        out.push(slot_count);
+        for slot in slots {
+            out.extend_from_slice(&encode_slot(slot)?);
+        }
+        out.push(NONCE_SIZE as u8);
+        out.extend_from_slice(nonce);
+        out.extend_from_slice(ciphertext);
+        Ok(out)
+    }
+}
+
+// 密钥槽的编码，SQLite 后端把它单独保存为一行：
+//
+//   kind        u8      1 = 主密码, 2 = 恢复密钥, 3 = 密钥文件
+//   id_len      u8
+//   id          id_len 字节
+//   created_at  i64     Unix 时间戳（秒）
+//   label_len   u16
+//   label       label_len 字节（UTF-8）
+//   kdf_id      u8      1 = Argon2id (v0x13)
+//   m_cost / t_cost / p_cost  u32
+//   salt_len    u8
+//   salt        salt_len 字节
+//   nonce_len   u8
+//   nonce       nonce_len 字节
+//   key_len     u8
+//   wrapped_key key_len 字节（数据密钥的密文和认证标签）
+pub fn encode_slot(slot: &KeySlot) -> Result<Vec<u8>> {
+    let too_long = |field: &str| AppError::Storage(format!("Key slot {} is too long", field));
+    let mut out = Vec::with_capacity(64 + slot.label.len() + slot.wrapped_key.len());
+    out.push(slot.kind.id());
+    out.push(u8::try_from(slot.id.len()).map_err(|_| too_long("id"))?);
+    out.extend_from_slice(slot.id.as_bytes());
+    out.extend_from_slice(&slot.created_at.to_le_bytes());
+    out.extend_from_slice(&u16::try_from(slot.label.len()).map_err(|_| too_long("label"))?.to_le_bytes());
+    out.extend_from_slice(slot.label.as_bytes());
+    out.push(KdfAlgorithm::Argon2id.id());
+    out.extend_from_slice(&slot.kdf_params.m_cost.to_le_bytes());
+    out.extend_from_slice(&slot.kdf_params.t_cost.to_le_bytes());
+    out.extend_from_slice(&slot.kdf_params.p_cost.to_le_bytes());
+    out.push(u8::try_from(slot.salt.len()).map_err(|_| too_long("salt"))?);
+    out.extend_from_slice(&slot.salt);
+    out.push(NONCE_SIZE as u8);
+    out.extend_from_slice(&slot.nonce);
+    out.push(u8::try_from(slot.wrapped_key.len()).map_err(|_| too_long("key"))?);
+    out.extend_from_slice(&slot.wrapped_key);
+    Ok(out)
+}
+
+pub fn parse_slot(bytes: &[u8]) -> Result<KeySlot> {
+    let mut reader = Reader { buf: bytes, pos: 0 };
+    let slot = read_slot(&mut reader)?;
+    if reader.pos != bytes.len() {
+        return Err(AppError::InvalidHeader("unexpected trailing bytes in key slot".to_string()));
+    }
+    Ok(slot)
+}
+
+fn read_slot(reader: &mut Reader<'_>) -> Result<KeySlot> {
+    let kind_id = reader.u8()?;
+    let kind = SlotKind::from_id(kind_id)
+        .ok_or_else(|| AppError::InvalidHeader(format!("unsupported key slot type {}", kind_id)))?;
+    let id_len = reader.u8()? as usize;
+    let id = reader.string(id_len)?;
+    let created_at = reader.i64()?;
+    let label_len = reader.u16()? as usize;
+    let label = reader.string(label_len)?;
+    KdfAlgorithm::from_id(reader.u8()?)?;
+    let kdf_params = KdfParams {
+        m_cost: reader.u32()?,
+        t_cost: reader.u32()?,
+        p_cost: reader.u32()?,
+    };
+    let salt_len = reader.u8()? as usize;
+    let salt = reader.take(salt_len)?.to_vec();
+    let nonce_len = reader.u8()? as usize;
+    let nonce: [u8; NONCE_SIZE] = reader.take(nonce_len)?
+        .try_into()
+        .map_err(|_| AppError::InvalidHeader(format!("unsupported nonce length {}", nonce_len)))?;
+    let key_len = reader.u8()? as usize;
+    let wrapped_key = reader.take(key_len)?.to_vec();
+    Ok(KeySlot { id, kind, label, created_at, kdf_params, salt, nonce, wrapped_key })
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or(AppError::VaultTruncated)?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self, len: usize) -> Result<String> {
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| AppError::InvalidHeader("invalid UTF-8 in key slot".to_string()))
+    }
+}
+
+pub fn parse(data: &[u8]) -> Result<EncryptedFile<'_>> {
+    if !data.starts_with(MAGIC) {
+        return parse_legacy(data);
+    }
+
+    let mut reader = Reader { buf: data, pos: MAGIC.len() };
+    let header = read_header(&mut reader)?;
+    let aad = &data[..reader.pos];
+
+    let mut slots = Vec::new();
+    if let KeySource::Slots { .. } = header.key_source {
+        let slot_count = reader.u8()?;
+        for _ in 0..slot_count {
+            slots.push(read_slot(&mut reader)?);
+        }
+    }
+
+    let nonce_len = reader.u8()? as usize;
+    let nonce: [u8; NONCE_SIZE] = reader.take(nonce_len)?
+        .try_into()
+        .map_err(|_| AppError::InvalidHeader(format!("unsupported nonce length {}", nonce_len)))?;
+
+    let ciphertext = &data[reader.pos..];
+    if ciphertext.len() < TAG_SIZE {
+        return Err(AppError::VaultTruncated);
+    }
+    Ok(EncryptedFile {
+        header,
+        aad,
+        slots,
+        nonce,
+        ciphertext,
+    })
+}
+
+// 只有文件头（to_aad() 的结果）时使用，例如 SQLite 后端单独保存的文件头（密钥槽另外保存）
+pub fn parse_header(aad: &[u8]) -> Result<FileHeader> {
+    if !aad.starts_with(MAGIC) {
+        return Err(AppError::InvalidHeader("missing magic".to_string()));
+    }
+    let mut reader = Reader { buf: aad, pos: MAGIC.len() };
+    let header = read_header(&mut reader)?;
+    if reader.pos != aad.len() {
+        return Err(AppError::InvalidHeader("unexpected trailing bytes".to_string()));
+    }
+    Ok(header)
+}
+
+fn read_header(reader: &mut Reader<'_>) -> Result<FileHeader> {
+    let version = reader.u16()?;
+    if version > CURRENT_VERSION {
+        return Err(AppError::Storage(format!(
+            "Data file format version {} is newer than supported version {}",
+            version, CURRENT_VERSION
+        )));
+    }
+    // 版本 1 没有文件头，带 magic 的只可能是版本 2 或 3
+    let key_source = match version {
+        DERIVED_KEY_VERSION => {
+            let kdf = KdfAlgorithm::from_id(reader.u8()?)?;
+            let kdf_params = KdfParams {
+                m_cost: reader.u32()?,
+                t_cost: reader.u32()?,
+                p_cost: reader.u32()?,
+            };
+            let salt_len = reader.u8()? as usize;
+            let salt = reader.take(salt_len)?.to_vec();
+            KeySource::Derived { kdf, kdf_params, salt }
+        }
+        CURRENT_VERSION => {
+            let key_id = reader.take(KEY_ID_SIZE)?.try_into().unwrap();
+            KeySource::Slots { key_id }
+        }
+        _ => return Err(AppError::InvalidHeader(format!("unsupported format version {}", version))),
+    };
+    let cipher = CipherAlgorithm::from_id(reader.u8()?)?;
+    Ok(FileHeader { version, key_source, cipher })
+}
+
+fn parse_legacy(data: &[u8]) -> Result<EncryptedFile<'_>> {
+    if data.len() < SALT_SIZE + NONCE_SIZE + TAG_SIZE {
+        return Err(AppError::VaultTruncated);
+    }
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(&data[SALT_SIZE..SALT_SIZE + NONCE_SIZE]);
+
+    Ok(EncryptedFile {
+        header: FileHeader {
+            version: LEGACY_VERSION,
+            key_source: KeySource::Derived {
+                kdf: KdfAlgorithm::Argon2id,
+                kdf_params: KdfParams::default(),
+                salt: data[..SALT_SIZE].to_vec(),
+            },
+            cipher: CipherAlgorithm::Aes256Gcm,
+        },
+        aad: &[],
+        slots: Vec::new(),
+        nonce,
+        ciphertext: &data[SALT_SIZE + NONCE_SIZE..],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1: &[u8] = include_bytes!("../tests/fixtures/data-v1-headerless.enc");
+    const V2: &[u8] = include_bytes!("../tests/fixtures/data-v2-kdf-header.enc");
+
+    fn with_version(file: &[u8], version: u16) -> Vec<u8> {
+        let mut file = file.to_vec();
+        file[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&version.to_le_bytes());
+        file
+    }
+
+    #[test]
+    fn parses_headerless_v1() {
+        let file = parse(V1).unwrap();
+        assert_eq!(file.header.version, LEGACY_VERSION);
+        assert!(file.header.is_legacy());
+        assert!(file.aad.is_empty());
+        let KeySource::Derived { kdf_params, salt, .. } = &file.header.key_source else {
+            panic!("v1 key is derived from the master password");
+        };
+        assert_eq!(*kdf_params, KdfParams::default());
+        assert_eq!(salt[..], V1[..SALT_SIZE]);
+        assert_eq!(file.nonce[..], V1[SALT_SIZE..SALT_SIZE + NONCE_SIZE]);
+    }
+
+    #[test]
+    fn parses_v2_kdf_header() {
+        let file = parse(V2).unwrap();
+        assert_eq!(file.header.version, DERIVED_KEY_VERSION);
+        assert!(file.slots.is_empty());
+        let KeySource::Derived { kdf, kdf_params, salt } = &file.header.key_source else {
+            panic!("v2 key is derived from the master password");
+        };
+        assert_eq!(*kdf, KdfAlgorithm::Argon2id);
+        assert_eq!(*kdf_params, KdfParams::default());
+        assert_eq!(salt.len(), SALT_SIZE);
+        assert_eq!(file.header.to_aad(), file.aad);
+    }
+
+    #[test]
+    fn rejects_versions_without_a_header_format() {
+        for version in [0, LEGACY_VERSION] {
+            assert!(matches!(parse(&with_version(V2, version)), Err(AppError::InvalidHeader(_))));
+        }
+        assert!(matches!(parse(&with_version(V2, CURRENT_VERSION + 1)), Err(AppError::Storage(_))));
+    }
+
+    #[test]
+    fn reports_truncated_files() {
+        let aad_len = parse(V2).unwrap().aad.len();
+        assert!(matches!(parse(&V2[..MAGIC.len() + 5]), Err(AppError::VaultTruncated)));
+        assert!(matches!(parse(&V2[..aad_len + 5]), Err(AppError::VaultTruncated)));
+        assert!(matches!(parse(&V2[..aad_len + 1 + NONCE_SIZE + 4]), Err(AppError::VaultTruncated)));
+        assert!(matches!(parse(&V1[..SALT_SIZE + NONCE_SIZE]), Err(AppError::VaultTruncated)));
+    }
+}
@@ -0,0 +1,297 @@
+// Aegis Authenticator 备份（vault JSON）
+//
+// 明文备份的 header.slots / header.params 为 null，db 为 JSON 对象；
+// 加密备份的 db 是 Base64 密文，用随机主密钥以 AES-256-GCM 加密，主密钥再由
+// 每个 slot 单独包装。这里支持 type 1（密码 slot，scrypt 派生）。
+
+use super::{build_entry, invalid_backup, otp_type_name, parse_otp_type, wrong_password};
+use crate::crypto::{self, KEY_SIZE, MAX_KDF_PARAMS, NONCE_SIZE};
+use crate::error::{AppError, Result};
+use crate::models::{OtpType, TotpEntry};
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+
+const FORMAT: &str = "Aegis";
+const SLOT_TYPE_PASSWORD: u8 = 1;
+const TAG_SIZE: usize = 16;
+
+// Aegis 创建密码 slot 时使用的 scrypt 参数
+const SCRYPT_N: u64 = 1 << 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Vault {
+    version: u32,
+    header: Header,
+    db: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    slots: Option<Vec<Slot>>,
+    params: Option<KeyParams>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Slot {
+    #[serde(rename = "type")]
+    slot_type: u8,
+    uuid: String,
+    key: String,
+    key_params: KeyParams,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    n: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    r: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    p: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repaired: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyParams {
+    nonce: String,
+    tag: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Db {
+    version: u32,
+    entries: Vec<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    #[serde(rename = "type")]
+    entry_type: String,
+    #[serde(default)]
+    uuid: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    issuer: String,
+    #[serde(default)]
+    note: String,
+    #[serde(default)]
+    favorite: bool,
+    #[serde(default)]
+    icon: Option<String>,
+    info: Info,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Info {
+    secret: String,
+    #[serde(default = "default_algo")]
+    algo: String,
+    #[serde(default = "default_digits")]
+    digits: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    period: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    counter: Option<u64>,
+}
+
+fn default_algo() -> String {
+    "SHA1".to_string()
+}
+
+fn default_digits() -> usize {
+    crate::models::DEFAULT_DIGITS
+}
+
+fn hex_field(name: &str, value: &str) -> Result<Vec<u8>> {
+    hex::decode(value).map_err(|e| invalid_backup(FORMAT, format!("bad {}: {}", name, e)))
+}
+
+// Aegis 把 GCM 认证标签单独存放，这里拼回密文末尾后解密
+fn open(key: &[u8; KEY_SIZE], params: &KeyParams, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let nonce: [u8; NONCE_SIZE] = hex_field("nonce", &params.nonce)?
+        .try_into()
+        .map_err(|_| invalid_backup(FORMAT, "bad nonce length"))?;
+    let mut sealed = ciphertext.to_vec();
+    sealed.extend_from_slice(&hex_field("tag", &params.tag)?);
+    crypto::decrypt(key, &nonce, &sealed)
+}
+
+fn seal(key: &[u8; KEY_SIZE], plaintext: &[u8]) -> Result<(KeyParams, Vec<u8>)> {
+    let (nonce, mut sealed) = crypto::encrypt(key, plaintext)?;
+    let tag = sealed.split_off(sealed.len() - TAG_SIZE);
+    Ok((
+        KeyParams {
+            nonce: hex::encode(nonce),
+            tag: hex::encode(tag),
+        },
+        sealed,
+    ))
+}
+
+fn scrypt_key(password: &str, salt: &[u8], n: u64, r: u32, p: u32) -> Result<[u8; KEY_SIZE]> {
+    if !n.is_power_of_two() || n < 2 {
+        return Err(invalid_backup(FORMAT, format!("bad scrypt N {}", n)));
+    }
+    // scrypt 占用内存为 128 * N * r 字节，与 Argon2 使用相同的上限
+    let memory_kib = n.saturating_mul(u64::from(r)) / 8;
+    if memory_kib > u64::from(MAX_KDF_PARAMS.m_cost) || p > MAX_KDF_PARAMS.p_cost {
+        return Err(invalid_backup(FORMAT, format!("scrypt parameters N={} r={} p={} exceed limit", n, r, p)));
+    }
+    let params = scrypt::Params::new(n.trailing_zeros() as u8, r, p, KEY_SIZE)
+        .map_err(|e| invalid_backup(FORMAT, format!("bad scrypt parameters: {}", e)))?;
+    let mut key = [0u8; KEY_SIZE];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| AppError::Encryption(format!("Key derivation failed: {}", e)))?;
+    Ok(key)
+}
+
+// 依次尝试每个密码 slot 解出主密钥
+fn unlock_master_key(slots: &[Slot], password: &str) -> Result<[u8; KEY_SIZE]> {
+    for slot in slots.iter().filter(|s| s.slot_type == SLOT_TYPE_PASSWORD) {
+        let (Some(n), Some(r), Some(p), Some(salt)) = (slot.n, slot.r, slot.p, slot.salt.as_ref()) else {
+            continue;
+        };
+        let slot_key = scrypt_key(password, &hex_field("salt", salt)?, n, r, p)?;
+        if let Ok(master_key) = open(&slot_key, &slot.key_params, &hex_field("key", &slot.key)?) {
+            return master_key.try_into()
+                .map_err(|_| invalid_backup(FORMAT, "bad master key length"));
+        }
+    }
+    Err(wrong_password(FORMAT))
+}
+
+fn parse_entry(value: serde_json::Value) -> Result<TotpEntry> {
+    let entry: Entry = serde_json::from_value(value)
+        .map_err(|e| invalid_backup(FORMAT, e))?;
+    let otp_type = parse_otp_type(&entry.entry_type)?;
+    build_entry(
+        &entry.name,
+        &entry.issuer,
+        &entry.info.secret,
+        &entry.info.algo,
+        entry.info.digits,
+        entry.info.period.unwrap_or(crate::models::DEFAULT_PERIOD),
+        otp_type,
+        entry.info.counter.unwrap_or(0),
+    )
+}
+
+pub fn import(data: &[u8], password: Option<&str>) -> Result<Vec<Result<TotpEntry>>> {
+    let vault: Vault = serde_json::from_slice(data)
+        .map_err(|e| invalid_backup(FORMAT, e))?;
+
+    let db: Db = match (&vault.header.slots, &vault.header.params, vault.db) {
+        (None, _, db @ serde_json::Value::Object(_)) => {
+            serde_json::from_value(db).map_err(|e| invalid_backup(FORMAT, e))?
+        }
+        (Some(slots), Some(params), serde_json::Value::String(db)) => {
+            let password = password
+                .ok_or_else(|| AppError::Encryption("Password required for encrypted Aegis vault".to_string()))?;
+            let master_key = unlock_master_key(slots, password)?;
+            let ciphertext = general_purpose::STANDARD.decode(db.as_bytes())
+                .map_err(|e| invalid_backup(FORMAT, e))?;
+            let plaintext = open(&master_key, params, &ciphertext)
+                .map_err(|_| invalid_backup(FORMAT, "database authentication failed"))?;
+            serde_json::from_slice(&plaintext).map_err(|e| invalid_backup(FORMAT, e))?
+        }
+        _ => return Err(invalid_backup(FORMAT, "unrecognized vault layout")),
+    };
+
+    Ok(db.entries.into_iter().map(parse_entry).collect())
+}
+
+pub fn export(entries: &[TotpEntry], password: Option<&str>) -> Result<Vec<u8>> {
+    let entries = entries.iter()
+        .map(|e| {
+            serde_json::to_value(Entry {
+                entry_type: otp_type_name(e.otp_type).to_lowercase(),
+                uuid: e.id.clone(),
+                name: e.name.clone(),
+                issuer: e.issuer.clone(),
+                note: String::new(),
+                favorite: false,
+                icon: None,
+                info: Info {
+                    secret: e.secret.expose_secret().to_string(),
+                    algo: format!("{:?}", e.algorithm),
+                    digits: e.digits,
+                    period: (e.otp_type == OtpType::Totp).then_some(e.period),
+                    counter: (e.otp_type == OtpType::Hotp).then_some(e.counter),
+                },
+            })
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let db = Db { version: 2, entries };
+
+    let vault = match password {
+        None => Vault {
+            version: 1,
+            header: Header { slots: None, params: None },
+            db: serde_json::to_value(db)?,
+        },
+        Some(password) => {
+            let master_key: [u8; KEY_SIZE] = rand::random();
+            let salt: [u8; 32] = rand::random();
+            let slot_key = scrypt_key(password, &salt, SCRYPT_N, SCRYPT_R, SCRYPT_P)?;
+            let (key_params, wrapped_key) = seal(&slot_key, &master_key)?;
+            let (db_params, ciphertext) = seal(&master_key, &serde_json::to_vec(&db)?)?;
+
+            Vault {
+                version: 1,
+                header: Header {
+                    slots: Some(vec![Slot {
+                        slot_type: SLOT_TYPE_PASSWORD,
+                        uuid: uuid::Uuid::new_v4().to_string(),
+                        key: hex::encode(wrapped_key),
+                        key_params,
+                        n: Some(SCRYPT_N),
+                        r: Some(SCRYPT_R),
+                        p: Some(SCRYPT_P),
+                        salt: Some(hex::encode(salt)),
+                        repaired: Some(true),
+                    }]),
+                    params: Some(db_params),
+                },
+                db: serde_json::Value::String(general_purpose::STANDARD.encode(ciphertext)),
+            }
+        }
+    };
+
+    Ok(serde_json::to_vec_pretty(&vault)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::samples;
+
+    const PLAIN: &[u8] = include_bytes!("../../tests/fixtures/aegis-plain.json");
+    const ENCRYPTED: &[u8] = include_bytes!("../../tests/fixtures/aegis-encrypted.json");
+
+    #[test]
+    fn imports_plain_vault() {
+        samples::assert_imported(import(PLAIN, None).unwrap());
+    }
+
+    #[test]
+    fn imports_encrypted_vault() {
+        samples::assert_imported(import(ENCRYPTED, Some("aegis-test")).unwrap());
+        assert!(matches!(import(ENCRYPTED, None), Err(AppError::Encryption(_))));
+        assert!(matches!(import(ENCRYPTED, Some("wrong")), Err(AppError::Encryption(_))));
+    }
+
+    #[test]
+    fn round_trips_plain_and_encrypted() {
+        let entries = samples::entries();
+        samples::assert_imported(import(&export(&entries, None).unwrap(), None).unwrap());
+        samples::assert_imported(import(&export(&entries, Some("pw")).unwrap(), Some("pw")).unwrap());
+    }
+
+    #[test]
+    fn rejects_excessive_scrypt_parameters() {
+        assert!(matches!(scrypt_key("pw", &[0u8; 32], 1 << 30, 8, 1), Err(AppError::Storage(_))));
+        assert!(matches!(scrypt_key("pw", &[0u8; 32], 1 << 15, 8, 1024), Err(AppError::Storage(_))));
+    }
+}
@@ -0,0 +1,205 @@
+// andOTP 备份
+//
+// 明文备份是条目 JSON 数组；加密备份（.json.aes）是二进制文件：
+//   迭代次数(4 字节大端) || 盐(12) || IV(12) || AES-256-GCM 密文+标签
+// 密钥由 PBKDF2-HMAC-SHA1 派生。早期版本的加密备份没有迭代次数和盐，
+// 直接用 SHA-256(密码) 作为密钥：IV(12) || 密文+标签。
+
+use super::{build_entry, invalid_backup, otp_type_name, parse_otp_type, wrong_password};
+use crate::crypto::{self, KEY_SIZE, NONCE_SIZE};
+use crate::error::{AppError, Result};
+use crate::models::{OtpType, TotpEntry, DEFAULT_DIGITS, DEFAULT_PERIOD};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const FORMAT: &str = "andOTP";
+const SALT_LEN: usize = 12;
+// andOTP 每次备份在该区间内随机选择迭代次数
+const MIN_ITERATIONS: u32 = 140_000;
+const MAX_ITERATIONS: u32 = 160_000;
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    secret: String,
+    #[serde(default)]
+    issuer: String,
+    #[serde(default)]
+    label: String,
+    #[serde(default = "default_digits")]
+    digits: usize,
+    #[serde(rename = "type", default = "default_type")]
+    otp_type: String,
+    #[serde(default = "default_algorithm")]
+    algorithm: String,
+    #[serde(default)]
+    thumbnail: String,
+    #[serde(default)]
+    last_used: i64,
+    #[serde(default)]
+    used_frequency: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    period: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    counter: Option<u64>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+fn default_digits() -> usize {
+    DEFAULT_DIGITS
+}
+
+fn default_type() -> String {
+    "TOTP".to_string()
+}
+
+fn default_algorithm() -> String {
+    "SHA1".to_string()
+}
+
+fn derive_key(password: &str, salt: &[u8], iterations: u32) -> [u8; KEY_SIZE] {
+    let mut key = [0u8; KEY_SIZE];
+    pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+fn split_nonce(data: &[u8]) -> Option<([u8; NONCE_SIZE], &[u8])> {
+    let (nonce, ciphertext) = data.split_at_checked(NONCE_SIZE)?;
+    Some((nonce.try_into().ok()?, ciphertext))
+}
+
+fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>> {
+    // 新格式：迭代次数 + 盐 + IV + 密文
+    if data.len() > 4 + SALT_LEN + NONCE_SIZE {
+        let iterations = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let salt = &data[4..4 + SALT_LEN];
+        if let Some((nonce, ciphertext)) = split_nonce(&data[4 + SALT_LEN..]) {
+            if iterations > 0 && iterations <= 10 * MAX_ITERATIONS {
+                let key = derive_key(password, salt, iterations);
+                if let Ok(plaintext) = crypto::decrypt(&key, &nonce, ciphertext) {
+                    return Ok(plaintext);
+                }
+            }
+        }
+    }
+
+    // 旧格式：SHA-256(密码) 作为密钥
+    let (nonce, ciphertext) = split_nonce(data)
+        .ok_or_else(|| invalid_backup(FORMAT, "file too short"))?;
+    let key: [u8; KEY_SIZE] = Sha256::digest(password.as_bytes()).into();
+    crypto::decrypt(&key, &nonce, ciphertext).map_err(|_| wrong_password(FORMAT))
+}
+
+fn parse_entry(value: serde_json::Value) -> Result<TotpEntry> {
+    let entry: Entry = serde_json::from_value(value)
+        .map_err(|e| invalid_backup(FORMAT, e))?;
+    let otp_type = parse_otp_type(&entry.otp_type)?;
+
+    // 没有单独 issuer 字段的旧备份把 "issuer:account" 写在 label 里
+    let (issuer, name) = match (entry.issuer.is_empty(), entry.label.split_once(':')) {
+        (true, Some((issuer, account))) => (issuer.to_string(), account.to_string()),
+        _ => (entry.issuer, entry.label),
+    };
+
+    build_entry(
+        &name,
+        &issuer,
+        &entry.secret,
+        &entry.algorithm,
+        entry.digits,
+        entry.period.unwrap_or(DEFAULT_PERIOD),
+        otp_type,
+        entry.counter.unwrap_or(0),
+    )
+}
+
+pub fn import(data: &[u8], password: Option<&str>) -> Result<Vec<Result<TotpEntry>>> {
+    let plaintext;
+    let json = match (data.trim_ascii_start().first(), password) {
+        (Some(b'['), _) => data,
+        (_, Some(password)) => {
+            plaintext = decrypt(data, password)?;
+            &plaintext[..]
+        }
+        (_, None) => {
+            return Err(AppError::Encryption("Password required for encrypted andOTP backup".to_string()));
+        }
+    };
+
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(json)
+        .map_err(|e| invalid_backup(FORMAT, e))?;
+    Ok(entries.into_iter().map(parse_entry).collect())
+}
+
+pub fn export(entries: &[TotpEntry], password: Option<&str>) -> Result<Vec<u8>> {
+    let entries: Vec<Entry> = entries.iter()
+        .map(|e| Entry {
+            secret: e.secret.expose_secret().to_string(),
+            issuer: e.issuer.clone(),
+            label: e.name.clone(),
+            digits: e.digits,
+            otp_type: otp_type_name(e.otp_type).to_string(),
+            algorithm: format!("{:?}", e.algorithm),
+            thumbnail: "Default".to_string(),
+            last_used: 0,
+            used_frequency: 0,
+            period: (e.otp_type == OtpType::Totp).then_some(e.period),
+            counter: (e.otp_type == OtpType::Hotp).then_some(e.counter),
+            tags: Vec::new(),
+        })
+        .collect();
+    let json = serde_json::to_vec(&entries)?;
+
+    let Some(password) = password else {
+        return Ok(json);
+    };
+
+    let iterations = rand::thread_rng().gen_range(MIN_ITERATIONS..=MAX_ITERATIONS);
+    let salt: [u8; SALT_LEN] = rand::random();
+    let key = derive_key(password, &salt, iterations);
+    let (nonce, ciphertext) = crypto::encrypt(&key, &json)?;
+
+    let mut file = Vec::with_capacity(4 + SALT_LEN + NONCE_SIZE + ciphertext.len());
+    file.extend_from_slice(&iterations.to_be_bytes());
+    file.extend_from_slice(&salt);
+    file.extend_from_slice(&nonce);
+    file.extend_from_slice(&ciphertext);
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::samples;
+
+    const PLAIN: &[u8] = include_bytes!("../../tests/fixtures/andotp-plain.json");
+    const ENCRYPTED: &[u8] = include_bytes!("../../tests/fixtures/andotp-encrypted.json.aes");
+
+    #[test]
+    fn imports_plain_backup() {
+        samples::assert_imported(import(PLAIN, None).unwrap());
+    }
+
+    #[test]
+    fn imports_encrypted_backup() {
+        samples::assert_imported(import(ENCRYPTED, Some("andotp-test")).unwrap());
+        assert!(matches!(import(ENCRYPTED, None), Err(AppError::Encryption(_))));
+        assert!(matches!(import(ENCRYPTED, Some("wrong")), Err(AppError::Encryption(_))));
+    }
+
+    #[test]
+    fn imports_old_sha256_format() {
+        let key: [u8; KEY_SIZE] = Sha256::digest(b"pw").into();
+        let (nonce, ciphertext) = crypto::encrypt(&key, PLAIN).unwrap();
+        let file = [&nonce[..], &ciphertext].concat();
+        samples::assert_imported(import(&file, Some("pw")).unwrap());
+    }
+
+    #[test]
+    fn round_trips_plain_and_encrypted() {
+        let entries = samples::entries();
+        samples::assert_imported(import(&export(&entries, None).unwrap(), None).unwrap());
+        samples::assert_imported(import(&export(&entries, Some("pw")).unwrap(), Some("pw")).unwrap());
+    }
+}
@@ -0,0 +1,249 @@
+// Bitwarden JSON 导出
+//
+// 明文导出的 items[].login.totp 可以是 otpauth URI 或单独的 Base32 密钥。
+// 受密码保护的导出（passwordProtected=true）把明文 JSON 整体加密到 data 字段：
+// 用 PBKDF2-SHA256 或 Argon2id 从导出密码派生密钥，经 HKDF-Expand 拆分为
+// 加密密钥和 MAC 密钥，密文为 "2.IV|密文|MAC"（AES-256-CBC + HMAC-SHA256）。
+// 仅能用账户密钥解密的导出无法在这里读取。
+
+use super::{build_entry, check_argon2_limits, invalid_backup, wrong_password};
+use crate::crypto::KEY_SIZE;
+use crate::error::{AppError, Result};
+use crate::models::{OtpType, TotpEntry, DEFAULT_DIGITS, DEFAULT_PERIOD};
+use crate::totp_manager;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use base64::{Engine as _, engine::general_purpose};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+const FORMAT: &str = "Bitwarden";
+const KDF_PBKDF2: u8 = 0;
+const KDF_ARGON2ID: u8 = 1;
+// Bitwarden 允许设置的 PBKDF2 迭代次数上限
+const MAX_PBKDF2_ITERATIONS: u32 = 2_000_000;
+const ITEM_TYPE_LOGIN: u8 = 1;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    password_protected: bool,
+    #[serde(default)]
+    salt: Option<String>,
+    #[serde(default)]
+    kdf_type: Option<u8>,
+    #[serde(default)]
+    kdf_iterations: Option<u32>,
+    #[serde(default)]
+    kdf_memory: Option<u32>,
+    #[serde(default)]
+    kdf_parallelism: Option<u32>,
+    #[serde(rename = "encKeyValidation_DO_NOT_EDIT", default)]
+    enc_key_validation: Option<String>,
+    #[serde(default)]
+    data: Option<String>,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    #[serde(rename = "type")]
+    item_type: u8,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    login: Option<Login>,
+}
+
+#[derive(Deserialize)]
+struct Login {
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    totp: Option<String>,
+}
+
+struct Keys {
+    enc: [u8; KEY_SIZE],
+    mac: [u8; KEY_SIZE],
+}
+
+fn derive_keys(export: &Export, password: &str) -> Result<Keys> {
+    let salt = export.salt.as_deref()
+        .ok_or_else(|| invalid_backup(FORMAT, "missing salt"))?;
+    let iterations = export.kdf_iterations
+        .ok_or_else(|| invalid_backup(FORMAT, "missing kdfIterations"))?;
+
+    let mut master = [0u8; KEY_SIZE];
+    match export.kdf_type.unwrap_or(KDF_PBKDF2) {
+        KDF_PBKDF2 => {
+            if iterations > MAX_PBKDF2_ITERATIONS {
+                return Err(invalid_backup(FORMAT, format!("kdfIterations {} exceed limit", iterations)));
+            }
+            pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt.as_bytes(), iterations, &mut master);
+        }
+        KDF_ARGON2ID => {
+            use argon2::{Algorithm, Argon2, Params, Version};
+
+            // Bitwarden 的内存参数单位为 MiB，盐值先做 SHA-256
+            let memory = u64::from(export.kdf_memory.unwrap_or(64)) * 1024;
+            let parallelism = export.kdf_parallelism.unwrap_or(4);
+            check_argon2_limits(FORMAT, memory, iterations.into(), parallelism.into())?;
+            let params = Params::new(memory as u32, iterations, parallelism, Some(KEY_SIZE))
+                .map_err(|e| invalid_backup(FORMAT, format!("bad Argon2 parameters: {}", e)))?;
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password_into(password.as_bytes(), &Sha256::digest(salt.as_bytes()), &mut master)
+                .map_err(|e| AppError::Encryption(format!("Key derivation failed: {}", e)))?;
+        }
+        other => return Err(invalid_backup(FORMAT, format!("unsupported kdfType {}", other))),
+    }
+
+    let hkdf = hkdf::Hkdf::<Sha256>::from_prk(&master)
+        .map_err(|_| AppError::Encryption("Key derivation failed".to_string()))?;
+    let mut keys = Keys { enc: [0u8; KEY_SIZE], mac: [0u8; KEY_SIZE] };
+    hkdf.expand(b"enc", &mut keys.enc)
+        .and_then(|_| hkdf.expand(b"mac", &mut keys.mac))
+        .map_err(|_| AppError::Encryption("Key derivation failed".to_string()))?;
+    Ok(keys)
+}
+
+// 解密 "2.IV|密文|MAC" 格式的 EncString，MAC 不匹配视为密码错误
+fn decrypt_string(keys: &Keys, value: &str) -> Result<Vec<u8>> {
+    let body = value.strip_prefix("2.")
+        .ok_or_else(|| invalid_backup(FORMAT, "unsupported encryption type"))?;
+    let parts: Vec<&str> = body.split('|').collect();
+    let [iv, ciphertext, mac] = parts[..] else {
+        return Err(invalid_backup(FORMAT, "malformed encrypted string"));
+    };
+    let decode = |v: &str| general_purpose::STANDARD.decode(v).map_err(|e| invalid_backup(FORMAT, e));
+    let (iv, ciphertext, mac) = (decode(iv)?, decode(ciphertext)?, decode(mac)?);
+
+    let mut hmac = <Hmac<Sha256> as Mac>::new_from_slice(&keys.mac)
+        .map_err(|_| AppError::Encryption("Invalid MAC key".to_string()))?;
+    hmac.update(&iv);
+    hmac.update(&ciphertext);
+    hmac.verify_slice(&mac).map_err(|_| wrong_password(FORMAT))?;
+
+    cbc::Decryptor::<aes::Aes256>::new_from_slices(&keys.enc, &iv)
+        .map_err(|_| invalid_backup(FORMAT, "bad IV length"))?
+        .decrypt_padded_vec_mut::<Pkcs7>(&ciphertext)
+        .map_err(|_| wrong_password(FORMAT))
+}
+
+fn parse_item(item: &Item, totp: &str) -> Result<TotpEntry> {
+    let username = item.login.as_ref()
+        .and_then(|l| l.username.clone())
+        .unwrap_or_default();
+    let totp = totp.trim();
+
+    if totp.starts_with("otpauth://") {
+        let mut entry = totp_manager::parse_otpauth_uri(totp)?;
+        if entry.issuer.is_empty() {
+            entry.issuer = item.name.clone();
+        }
+        if entry.name.is_empty() {
+            entry.name = if username.is_empty() { item.name.clone() } else { username };
+        }
+        return Ok(entry);
+    }
+    if totp.starts_with("steam://") {
+        return Err(AppError::Totp("Steam Guard codes are not supported".to_string()));
+    }
+
+    // 只填写了 Base32 密钥时使用默认参数
+    build_entry(
+        &username,
+        &item.name,
+        totp,
+        "SHA1",
+        DEFAULT_DIGITS,
+        DEFAULT_PERIOD,
+        OtpType::Totp,
+        0,
+    )
+}
+
+pub fn import(data: &[u8], password: Option<&str>) -> Result<Vec<Result<TotpEntry>>> {
+    let mut export: Export = serde_json::from_slice(data)
+        .map_err(|e| invalid_backup(FORMAT, e))?;
+
+    if export.encrypted {
+        if !export.password_protected {
+            return Err(AppError::Encryption(
+                "Account-restricted Bitwarden exports cannot be imported; use a password-protected export".to_string(),
+            ));
+        }
+        let password = password
+            .ok_or_else(|| AppError::Encryption("Password required for encrypted Bitwarden export".to_string()))?;
+        let keys = derive_keys(&export, password)?;
+        if let Some(validation) = export.enc_key_validation.as_deref() {
+            decrypt_string(&keys, validation)?;
+        }
+        let encrypted = export.data.as_deref()
+            .ok_or_else(|| invalid_backup(FORMAT, "missing data"))?;
+        let plaintext = decrypt_string(&keys, encrypted)?;
+        export = serde_json::from_slice(&plaintext).map_err(|e| invalid_backup(FORMAT, e))?;
+    }
+
+    // 只有填写了 TOTP 字段的登录项才会导入
+    Ok(export.items.iter()
+        .filter(|item| item.item_type == ITEM_TYPE_LOGIN)
+        .filter_map(|item| {
+            let totp = item.login.as_ref()?.totp.as_deref()?;
+            (!totp.trim().is_empty()).then(|| parse_item(item, totp))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::samples;
+
+    const PLAIN: &[u8] = include_bytes!("../../tests/fixtures/bitwarden-plain.json");
+    const ENCRYPTED: &[u8] = include_bytes!("../../tests/fixtures/bitwarden-encrypted.json");
+
+    // 样例包含 GitHub（只有 Base32 密钥）、Example（otpauth URI）和一个 Steam 条目，
+    // 没有 TOTP 的登录项和安全笔记不导入
+    fn assert_sample_export(imported: Vec<Result<TotpEntry>>) {
+        let [github, example, steam] = <[_; 3]>::try_from(imported)
+            .unwrap_or_else(|items| panic!("expected three items with TOTP, got {}", items.len()));
+        samples::assert_same(&[github.unwrap(), example.unwrap()], &samples::entries()[..2]);
+        assert!(matches!(steam, Err(AppError::Totp(_))));
+    }
+
+    #[test]
+    fn imports_plain_export() {
+        assert_sample_export(import(PLAIN, None).unwrap());
+    }
+
+    #[test]
+    fn imports_password_protected_export() {
+        assert_sample_export(import(ENCRYPTED, Some("bitwarden-test")).unwrap());
+        assert!(matches!(import(ENCRYPTED, None), Err(AppError::Encryption(_))));
+        assert!(matches!(import(ENCRYPTED, Some("wrong")), Err(AppError::Encryption(_))));
+    }
+
+    #[test]
+    fn rejects_account_restricted_export() {
+        let data = br#"{"encrypted": true, "items": []}"#;
+        assert!(matches!(import(data, Some("pw")), Err(AppError::Encryption(_))));
+    }
+    #[test]
+    fn rejects_excessive_kdf_parameters() {
+        let mut export: serde_json::Value = serde_json::from_slice(ENCRYPTED).unwrap();
+        export["kdfIterations"] = (MAX_PBKDF2_ITERATIONS + 1).into();
+        let data = serde_json::to_vec(&export).unwrap();
+        assert!(matches!(import(&data, Some("bitwarden-test")), Err(AppError::Storage(_))));
+
+        export["kdfType"] = KDF_ARGON2ID.into();
+        export["kdfIterations"] = 3.into();
+        export["kdfMemory"] = u32::MAX.into();
+        let data = serde_json::to_vec(&export).unwrap();
+        assert!(matches!(import(&data, Some("bitwarden-test")), Err(AppError::Storage(_))));
+    }
+}
@@ -0,0 +1,134 @@
+// FreeOTP+ 备份（JSON 导出）
+//
+// FreeOTP+ 的 JSON 导出不加密，密钥以有符号字节数组保存；
+// tokenOrder 按 "issuer:label" 记录显示顺序。
+
+use super::{build_entry, invalid_backup, otp_type_name, parse_otp_type};
+use crate::error::{AppError, Result};
+use crate::models::{OtpType, TotpEntry, DEFAULT_DIGITS, DEFAULT_PERIOD};
+use crate::totp_manager;
+use serde::{Deserialize, Serialize};
+
+const FORMAT: &str = "FreeOTP+";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Backup {
+    #[serde(default)]
+    token_order: Vec<String>,
+    tokens: Vec<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Token {
+    #[serde(default = "default_algo")]
+    algo: String,
+    #[serde(default)]
+    counter: u64,
+    #[serde(default = "default_digits")]
+    digits: usize,
+    #[serde(default)]
+    issuer_ext: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    issuer_int: Option<String>,
+    #[serde(default)]
+    label: String,
+    #[serde(default = "default_period")]
+    period: u64,
+    secret: Vec<i8>,
+    #[serde(rename = "type", default = "default_type")]
+    token_type: String,
+}
+
+fn default_algo() -> String {
+    "SHA1".to_string()
+}
+
+fn default_digits() -> usize {
+    DEFAULT_DIGITS
+}
+
+fn default_period() -> u64 {
+    DEFAULT_PERIOD
+}
+
+fn default_type() -> String {
+    "TOTP".to_string()
+}
+
+fn parse_token(value: serde_json::Value) -> Result<TotpEntry> {
+    let token: Token = serde_json::from_value(value)
+        .map_err(|e| invalid_backup(FORMAT, e))?;
+    let otp_type = parse_otp_type(&token.token_type)?;
+    let secret: Vec<u8> = token.secret.iter().map(|b| *b as u8).collect();
+    let issuer = match token.issuer_ext.is_empty() {
+        true => token.issuer_int.unwrap_or_default(),
+        false => token.issuer_ext,
+    };
+
+    build_entry(
+        &token.label,
+        &issuer,
+        &totp_manager::encode_base32(&secret),
+        &token.algo,
+        token.digits,
+        token.period,
+        otp_type,
+        token.counter,
+    )
+}
+
+pub fn import(data: &[u8], _password: Option<&str>) -> Result<Vec<Result<TotpEntry>>> {
+    let backup: Backup = serde_json::from_slice(data)
+        .map_err(|e| invalid_backup(FORMAT, e))?;
+    Ok(backup.tokens.into_iter().map(parse_token).collect())
+}
+
+pub fn export(entries: &[TotpEntry], password: Option<&str>) -> Result<Vec<u8>> {
+    if password.is_some() {
+        return Err(AppError::Encryption("FreeOTP+ backups do not support encryption".to_string()));
+    }
+
+    let mut tokens = Vec::with_capacity(entries.len());
+    for e in entries {
+        let secret = totp_manager::decode_base32(e.secret.expose_secret())?;
+        tokens.push(serde_json::to_value(Token {
+            algo: format!("{:?}", e.algorithm),
+            counter: if e.otp_type == OtpType::Hotp { e.counter } else { 0 },
+            digits: e.digits,
+            issuer_ext: e.issuer.clone(),
+            issuer_int: Some(e.issuer.clone()),
+            label: e.name.clone(),
+            period: e.period,
+            secret: secret.iter().map(|b| *b as i8).collect(),
+            token_type: otp_type_name(e.otp_type).to_string(),
+        })?);
+    }
+
+    let backup = Backup {
+        token_order: entries.iter().map(|e| format!("{}:{}", e.issuer, e.name)).collect(),
+        tokens,
+    };
+    Ok(serde_json::to_vec_pretty(&backup)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::samples;
+
+    const BACKUP: &[u8] = include_bytes!("../../tests/fixtures/freeotp-plus.json");
+
+    #[test]
+    fn imports_backup() {
+        samples::assert_imported(import(BACKUP, None).unwrap());
+    }
+
+    #[test]
+    fn round_trips() {
+        let exported = export(&samples::entries(), None).unwrap();
+        samples::assert_imported(import(&exported, None).unwrap());
+        assert!(export(&samples::entries(), Some("pw")).is_err());
+    }
+}
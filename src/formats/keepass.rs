@@ -0,0 +1,898 @@
+// KeePass KDBX 4 数据库
+//
+// 文件结构：
+//   签名(8) || 版本(4) || 外层头字段 || 头部 SHA-256 || 头部 HMAC-SHA256 || HMAC 分块负载
+// 负载解密（AES-256-CBC 或 ChaCha20）并解压（gzip）后，是内层头字段加 XML 文档，
+// XML 中 Protected="True" 的值再用内层随机流（ChaCha20）按文档顺序异或加密。
+// 这里只支持主密码（不支持密钥文件），KDF 支持 AES-KDF、Argon2d 和 Argon2id。
+//
+// 读取的 TOTP 字段：
+//   otp                       KeePassXC 的 otpauth URI，或 KeeOtp 插件的 "key=...&step=..." 格式
+//   TimeOtp-* / HmacOtp-*     KeePass 2.47+ 内置占位符（以及 KeeOtp2）使用的字段
+
+use super::{build_entry, check_argon2_limits, invalid_backup, wrong_password};
+use crate::crypto::KEY_SIZE;
+use crate::error::{AppError, Result};
+use crate::models::{OtpAlgorithm, OtpType, TotpEntry, DEFAULT_DIGITS, DEFAULT_PERIOD};
+use crate::totp_manager;
+use aes::cipher::{
+    block_padding::Pkcs7, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit, StreamCipher,
+};
+use base64::{Engine as _, engine::general_purpose};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+const FORMAT: &str = "KeePass";
+
+const SIGNATURE_1: u32 = 0x9AA2_D903;
+const SIGNATURE_2: u32 = 0xB54B_FB67;
+const VERSION_MAJOR: u16 = 4;
+
+const CIPHER_AES256: [u8; 16] = [
+    0x31, 0xC1, 0xF2, 0xE6, 0xBF, 0x71, 0x43, 0x50, 0xBE, 0x58, 0x05, 0x21, 0x6A, 0xFC, 0x5A, 0xFF,
+];
+const CIPHER_CHACHA20: [u8; 16] = [
+    0xD6, 0x03, 0x8A, 0x2B, 0x8B, 0x6F, 0x4C, 0xB5, 0xA5, 0x24, 0x33, 0x9A, 0x31, 0xDB, 0xB5, 0x9A,
+];
+const KDF_AES: [u8; 16] = [
+    0xC9, 0xD9, 0xF3, 0x9A, 0x62, 0x8A, 0x44, 0x60, 0xBF, 0x74, 0x0D, 0x08, 0xC1, 0x8A, 0x4F, 0xEA,
+];
+const KDF_ARGON2D: [u8; 16] = [
+    0xEF, 0x63, 0x6D, 0xDF, 0x8C, 0x29, 0x44, 0x4B, 0x91, 0xF7, 0xA9, 0xA4, 0x03, 0xE3, 0x0A, 0x0C,
+];
+const KDF_ARGON2ID: [u8; 16] = [
+    0x9E, 0x29, 0x8B, 0x19, 0x56, 0xDB, 0x47, 0x73, 0xB2, 0x3D, 0xFC, 0x3E, 0xC6, 0xF0, 0xA1, 0xE6,
+];
+
+// AES-KDF 轮数上限，KeePassXC 按 1 秒基准测试得到的轮数通常在千万级
+const MAX_AES_KDF_ROUNDS: u64 = 100_000_000;
+
+// 解压后内部数据的上限：上传大小限制为 10 MiB，gzip 压缩比可以很高，解压时不能无限制地读取
+const MAX_DECOMPRESSED: u64 = 64 * 1024 * 1024;
+
+// 外层头字段
+const HEADER_END: u8 = 0;
+const HEADER_CIPHER_ID: u8 = 2;
+const HEADER_COMPRESSION: u8 = 3;
+const HEADER_MASTER_SEED: u8 = 4;
+const HEADER_ENCRYPTION_IV: u8 = 7;
+const HEADER_KDF_PARAMETERS: u8 = 11;
+
+// 内层头字段
+const INNER_END: u8 = 0;
+const INNER_STREAM_ID: u8 = 1;
+const INNER_STREAM_KEY: u8 = 2;
+const STREAM_CHACHA20: u32 = 3;
+
+// VariantDictionary 值类型
+const VARIANT_END: u8 = 0x00;
+const VARIANT_U32: u8 = 0x04;
+const VARIANT_U64: u8 = 0x05;
+const VARIANT_BYTES: u8 = 0x42;
+const VARIANT_DICT_VERSION: u16 = 0x0100;
+
+const BLOCK_SIZE: usize = 1024 * 1024;
+// .NET DateTime 纪元（0001-01-01）到 Unix 纪元的秒数
+const DOTNET_EPOCH_OFFSET: i64 = 62_135_596_800;
+
+// 导出使用与 KeePassXC 默认值相近的 Argon2d 参数
+const EXPORT_ARGON2_MEMORY: u64 = 64 * 1024 * 1024;
+const EXPORT_ARGON2_ITERATIONS: u64 = 2;
+const EXPORT_ARGON2_PARALLELISM: u32 = 2;
+
+struct ByteReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| invalid_backup(FORMAT, "file is truncated"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        usize::try_from(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+            .map_err(|_| invalid_backup(FORMAT, "negative field length"))
+    }
+}
+
+// ---------- 密钥派生 ----------
+
+// 只有主密码时，复合密钥为 SHA-256(SHA-256(password))
+fn composite_key(password: &str) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(password.as_bytes())).into()
+}
+
+fn variant_bytes<'a>(dict: &'a HashMap<String, Vec<u8>>, key: &str) -> Result<&'a [u8]> {
+    dict.get(key)
+        .map(Vec::as_slice)
+        .ok_or_else(|| invalid_backup(FORMAT, format!("missing KDF parameter {}", key)))
+}
+
+fn variant_u64(dict: &HashMap<String, Vec<u8>>, key: &str) -> Result<u64> {
+    let bytes = variant_bytes(dict, key)?;
+    match bytes.len() {
+        4 => Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as u64),
+        8 => Ok(u64::from_le_bytes(bytes.try_into().unwrap())),
+        _ => Err(invalid_backup(FORMAT, format!("bad KDF parameter {}", key))),
+    }
+}
+
+fn transform_key(kdf: &HashMap<String, Vec<u8>>, composite: &[u8; 32]) -> Result<[u8; KEY_SIZE]> {
+    let uuid = variant_bytes(kdf, "$UUID")?;
+    let salt_or_seed = variant_bytes(kdf, "S")?;
+
+    if uuid == KDF_AES {
+        let rounds = variant_u64(kdf, "R")?;
+        if rounds > MAX_AES_KDF_ROUNDS {
+            return Err(invalid_backup(FORMAT, format!("AES-KDF rounds {} exceed limit", rounds)));
+        }
+        let cipher = aes::Aes256::new_from_slice(salt_or_seed)
+            .map_err(|_| invalid_backup(FORMAT, "bad AES-KDF seed"))?;
+        let mut blocks = [
+            aes::Block::from(<[u8; 16]>::try_from(&composite[..16]).unwrap()),
+            aes::Block::from(<[u8; 16]>::try_from(&composite[16..]).unwrap()),
+        ];
+        for _ in 0..rounds {
+            cipher.encrypt_blocks(&mut blocks);
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(blocks[0]);
+        hasher.update(blocks[1]);
+        return Ok(hasher.finalize().into());
+    }
+
+    let algorithm = match uuid {
+        u if u == KDF_ARGON2D => argon2::Algorithm::Argon2d,
+        u if u == KDF_ARGON2ID => argon2::Algorithm::Argon2id,
+        _ => return Err(invalid_backup(FORMAT, "unsupported KDF")),
+    };
+    let version = match variant_u64(kdf, "V")? {
+        0x10 => argon2::Version::V0x10,
+        0x13 => argon2::Version::V0x13,
+        v => return Err(invalid_backup(FORMAT, format!("unsupported Argon2 version {:#x}", v))),
+    };
+    // KDBX 中 Argon2 内存参数单位为字节
+    let memory = variant_u64(kdf, "M")? / 1024;
+    let iterations = variant_u64(kdf, "I")?;
+    let parallelism = variant_u64(kdf, "P")?;
+    check_argon2_limits(FORMAT, memory, iterations, parallelism)?;
+
+    let params = argon2::Params::new(memory as u32, iterations as u32, parallelism as u32, Some(KEY_SIZE))
+        .map_err(|e| invalid_backup(FORMAT, format!("bad Argon2 parameters: {}", e)))?;
+    let mut key = [0u8; KEY_SIZE];
+    argon2::Argon2::new(algorithm, version, params)
+        .hash_password_into(composite, salt_or_seed, &mut key)
+        .map_err(|e| AppError::Encryption(format!("Key derivation failed: {}", e)))?;
+    Ok(key)
+}
+
+struct Keys {
+    cipher: [u8; KEY_SIZE],
+    hmac: [u8; 64],
+}
+
+fn derive_keys(master_seed: &[u8], transformed: &[u8; KEY_SIZE]) -> Keys {
+    let mut cipher = Sha256::new();
+    cipher.update(master_seed);
+    cipher.update(transformed);
+
+    let mut hmac = Sha512::new();
+    hmac.update(master_seed);
+    hmac.update(transformed);
+    hmac.update([1u8]);
+
+    Keys {
+        cipher: cipher.finalize().into(),
+        hmac: hmac.finalize().into(),
+    }
+}
+
+// 每个数据块（以及头部，索引为 u64::MAX）使用独立的 HMAC 密钥
+fn block_hmac(keys: &Keys, index: u64, parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut block_key = Sha512::new();
+    block_key.update(index.to_le_bytes());
+    block_key.update(keys.hmac);
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&block_key.finalize())
+        .expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+// 内层随机流：ChaCha20，密钥和 nonce 取自 SHA-512(流密钥)
+fn inner_stream(stream_key: &[u8]) -> chacha20::ChaCha20 {
+    let hash = Sha512::digest(stream_key);
+    chacha20::ChaCha20::new_from_slices(&hash[..32], &hash[32..44])
+        .expect("ChaCha20 key and nonce sizes are fixed")
+}
+
+// ---------- XML ----------
+
+struct Node {
+    name: String,
+    attrs: Vec<(String, String)>,
+    text: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    fn is_protected(&self) -> bool {
+        self.attrs.iter().any(|(k, v)| k == "Protected" && v.eq_ignore_ascii_case("true"))
+    }
+}
+
+fn xml_error(e: impl std::fmt::Display) -> AppError {
+    invalid_backup(FORMAT, format!("bad XML: {}", e))
+}
+
+fn parse_xml(xml: &str) -> Result<Node> {
+    use quick_xml::events::{BytesStart, Event};
+
+    fn start_node(e: &BytesStart) -> Result<Node> {
+        let mut attrs = Vec::new();
+        for attr in e.attributes() {
+            let attr = attr.map_err(xml_error)?;
+            attrs.push((
+                String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+                attr.unescape_value().map_err(xml_error)?.into_owned(),
+            ));
+        }
+        Ok(Node {
+            name: String::from_utf8_lossy(e.name().as_ref()).into_owned(),
+            attrs,
+            text: String::new(),
+            children: Vec::new(),
+        })
+    }
+
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut stack: Vec<Node> = Vec::new();
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) => stack.push(start_node(&e)?),
+            Event::Empty(e) => {
+                let node = start_node(&e)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Ok(node),
+                }
+            }
+            Event::End(_) => {
+                let node = stack.pop().ok_or_else(|| xml_error("unbalanced tags"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Ok(node),
+                }
+            }
+            Event::Text(e) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&e.unescape().map_err(xml_error)?);
+                }
+            }
+            Event::CData(e) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&String::from_utf8_lossy(&e.into_inner()));
+                }
+            }
+            Event::Eof => return Err(xml_error("unexpected end of document")),
+            _ => {}
+        }
+    }
+}
+
+// 按文档顺序解密受保护的值
+fn unprotect(node: &mut Node, stream: &mut chacha20::ChaCha20) -> Result<()> {
+    if node.name == "Value" && node.is_protected() {
+        let mut bytes = general_purpose::STANDARD.decode(node.text.trim())
+            .map_err(|e| invalid_backup(FORMAT, format!("bad protected value: {}", e)))?;
+        stream.apply_keystream(&mut bytes);
+        node.text = String::from_utf8(bytes)?;
+    }
+    for child in &mut node.children {
+        unprotect(child, stream)?;
+    }
+    Ok(())
+}
+
+// ---------- 读取 ----------
+
+struct OuterHeader {
+    cipher: Vec<u8>,
+    compressed: bool,
+    master_seed: Vec<u8>,
+    iv: Vec<u8>,
+    kdf: HashMap<String, Vec<u8>>,
+}
+
+fn read_variant_dict(data: &[u8]) -> Result<HashMap<String, Vec<u8>>> {
+    let mut reader = ByteReader::new(data);
+    if reader.u16()? >> 8 != VARIANT_DICT_VERSION >> 8 {
+        return Err(invalid_backup(FORMAT, "unsupported KDF parameter format"));
+    }
+    let mut dict = HashMap::new();
+    loop {
+        let value_type = reader.u8()?;
+        if value_type == VARIANT_END {
+            return Ok(dict);
+        }
+        let key_len = reader.len()?;
+        let key = String::from_utf8_lossy(reader.take(key_len)?).into_owned();
+        let value_len = reader.len()?;
+        dict.insert(key, reader.take(value_len)?.to_vec());
+    }
+}
+
+fn read_outer_header(reader: &mut ByteReader) -> Result<OuterHeader> {
+    if reader.u32()? != SIGNATURE_1 || reader.u32()? != SIGNATURE_2 {
+        return Err(invalid_backup(FORMAT, "not a KeePass database"));
+    }
+    let _minor = reader.u16()?;
+    let major = reader.u16()?;
+    if major != VERSION_MAJOR {
+        return Err(invalid_backup(
+            FORMAT,
+            format!("KDBX {} is not supported, save the database as KDBX 4", major),
+        ));
+    }
+
+    let mut header = OuterHeader {
+        cipher: Vec::new(),
+        compressed: false,
+        master_seed: Vec::new(),
+        iv: Vec::new(),
+        kdf: HashMap::new(),
+    };
+    loop {
+        let id = reader.u8()?;
+        let len = reader.u32()? as usize;
+        let data = reader.take(len)?;
+        match id {
+            HEADER_END => return Ok(header),
+            HEADER_CIPHER_ID => header.cipher = data.to_vec(),
+            HEADER_COMPRESSION => header.compressed = data.first().copied().unwrap_or(0) != 0,
+            HEADER_MASTER_SEED => header.master_seed = data.to_vec(),
+            HEADER_ENCRYPTION_IV => header.iv = data.to_vec(),
+            HEADER_KDF_PARAMETERS => header.kdf = read_variant_dict(data)?,
+            _ => {}
+        }
+    }
+}
+
+// 解压后超过 limit 字节时返回错误
+fn gunzip(data: &[u8], limit: u64) -> Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(data)
+        .take(limit + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| invalid_backup(FORMAT, format!("decompression failed: {}", e)))?;
+    if decompressed.len() as u64 > limit {
+        return Err(invalid_backup(FORMAT, format!("decompressed database exceeds {} bytes", limit)));
+    }
+    Ok(decompressed)
+}
+
+fn decrypt_payload(header: &OuterHeader, keys: &Keys, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    if header.cipher == CIPHER_AES256 {
+        cbc::Decryptor::<aes::Aes256>::new_from_slices(&keys.cipher, &header.iv)
+            .map_err(|_| invalid_backup(FORMAT, "bad encryption IV"))?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+            .map_err(|_| invalid_backup(FORMAT, "payload decryption failed"))
+    } else if header.cipher == CIPHER_CHACHA20 {
+        let mut plaintext = ciphertext.to_vec();
+        chacha20::ChaCha20::new_from_slices(&keys.cipher, &header.iv)
+            .map_err(|_| invalid_backup(FORMAT, "bad encryption IV"))?
+            .apply_keystream(&mut plaintext);
+        Ok(plaintext)
+    } else {
+        Err(invalid_backup(FORMAT, "unsupported cipher (only AES-256 and ChaCha20 are supported)"))
+    }
+}
+
+fn read_database(data: &[u8], password: &str) -> Result<Node> {
+    let mut reader = ByteReader::new(data);
+    let header = read_outer_header(&mut reader)?;
+    let header_bytes = &data[..reader.pos];
+
+    if reader.take(32)? != &Sha256::digest(header_bytes)[..] {
+        return Err(invalid_backup(FORMAT, "header checksum mismatch"));
+    }
+    let transformed = transform_key(&header.kdf, &composite_key(password))?;
+    let keys = derive_keys(&header.master_seed, &transformed);
+    block_hmac(&keys, u64::MAX, &[header_bytes])
+        .verify_slice(reader.take(32)?)
+        .map_err(|_| wrong_password(FORMAT))?;
+
+    // HMAC 分块：HMAC(32) || 长度(4) || 数据，长度为 0 的块表示结束
+    let mut ciphertext = Vec::new();
+    for index in 0u64.. {
+        let mac = reader.take(32)?;
+        let len_bytes = reader.take(4)?;
+        let len = i32::from_le_bytes(len_bytes.try_into().unwrap());
+        let block = reader.take(usize::try_from(len).map_err(|_| invalid_backup(FORMAT, "bad block length"))?)?;
+        block_hmac(&keys, index, &[&index.to_le_bytes(), len_bytes, block])
+            .verify_slice(mac)
+            .map_err(|_| invalid_backup(FORMAT, format!("block {} is corrupted", index)))?;
+        if block.is_empty() {
+            break;
+        }
+        ciphertext.extend_from_slice(block);
+    }
+
+    let mut payload = decrypt_payload(&header, &keys, &ciphertext)?;
+    if header.compressed {
+        payload = gunzip(&payload, MAX_DECOMPRESSED)?;
+    }
+
+    let mut inner = ByteReader::new(&payload);
+    let mut stream_id = 0;
+    let mut stream_key = Vec::new();
+    loop {
+        let id = inner.u8()?;
+        let len = inner.u32()? as usize;
+        let field = inner.take(len)?;
+        match id {
+            INNER_END => break,
+            INNER_STREAM_ID if len == 4 => stream_id = u32::from_le_bytes(field.try_into().unwrap()),
+            INNER_STREAM_KEY => stream_key = field.to_vec(),
+            _ => {}
+        }
+    }
+    if stream_id != STREAM_CHACHA20 {
+        return Err(invalid_backup(FORMAT, format!("unsupported inner stream {}", stream_id)));
+    }
+
+    let xml = std::str::from_utf8(&payload[inner.pos..])
+        .map_err(|_| xml_error("invalid UTF-8"))?;
+    let mut root = parse_xml(xml)?;
+    unprotect(&mut root, &mut inner_stream(&stream_key))?;
+    Ok(root)
+}
+
+fn entry_fields(entry: &Node) -> HashMap<String, String> {
+    entry.children_named("String")
+        .filter_map(|s| {
+            let key = s.child("Key")?.text.clone();
+            let value = s.child("Value").map(|v| v.text.clone()).unwrap_or_default();
+            Some((key, value))
+        })
+        .collect()
+}
+
+fn parse_hash_algorithm(value: &str) -> &str {
+    match value.to_uppercase().replace(['-', '_'], "").as_str() {
+        "HMACSHA256" | "SHA256" => "SHA256",
+        "HMACSHA512" | "SHA512" => "SHA512",
+        _ => "SHA1",
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: Option<&String>, default: T) -> Result<T> {
+    match value.map(|v| v.trim()).filter(|v| !v.is_empty()) {
+        Some(v) => v.parse().map_err(|_| AppError::Totp(format!("Invalid number: {}", v))),
+        None => Ok(default),
+    }
+}
+
+// TimeOtp-Secret / HmacOtp-Secret 可以是 UTF-8、十六进制、Base32 或 Base64 编码
+fn otp_secret(fields: &HashMap<String, String>, prefix: &str) -> Option<Result<String>> {
+    if let Some(secret) = fields.get(&format!("{}-Secret-Base32", prefix)) {
+        return Some(Ok(secret.clone()));
+    }
+    let raw = if let Some(secret) = fields.get(&format!("{}-Secret", prefix)) {
+        Ok(secret.as_bytes().to_vec())
+    } else if let Some(secret) = fields.get(&format!("{}-Secret-Hex", prefix)) {
+        hex::decode(secret.trim().replace(' ', ""))
+            .map_err(|e| AppError::Totp(format!("Invalid hex secret: {}", e)))
+    } else if let Some(secret) = fields.get(&format!("{}-Secret-Base64", prefix)) {
+        general_purpose::STANDARD.decode(secret.trim())
+            .map_err(|e| AppError::Totp(format!("Invalid Base64 secret: {}", e)))
+    } else {
+        return None;
+    };
+    Some(raw.map(|bytes| totp_manager::encode_base32(&bytes)))
+}
+
+// KeeOtp 插件格式：key=BASE32&size=6&step=30&type=Totp&otpHashMode=Sha256&counter=0
+fn parse_keeotp(value: &str, title: &str, username: &str) -> Result<TotpEntry> {
+    let params: HashMap<String, String> = url::form_urlencoded::parse(value.trim().as_bytes())
+        .map(|(k, v)| (k.to_lowercase(), v.into_owned()))
+        .collect();
+    let secret = params.get("key")
+        .ok_or_else(|| AppError::Totp("Missing key in otp field".to_string()))?;
+    let otp_type = match params.get("type").map(|t| t.to_lowercase()).as_deref() {
+        None | Some("totp") => OtpType::Totp,
+        Some("hotp") => OtpType::Hotp,
+        Some(other) => return Err(AppError::Totp(format!("Unsupported OTP type: {}", other))),
+    };
+
+    build_entry(
+        username,
+        title,
+        secret,
+        parse_hash_algorithm(params.get("otphashmode").map(String::as_str).unwrap_or("")),
+        parse_number(params.get("size"), DEFAULT_DIGITS)?,
+        parse_number(params.get("step"), DEFAULT_PERIOD)?,
+        otp_type,
+        parse_number(params.get("counter"), 0)?,
+    )
+}
+
+fn parse_entry(entry: &Node) -> Option<Result<TotpEntry>> {
+    let fields = entry_fields(entry);
+    let title = fields.get("Title").map(String::as_str).unwrap_or("");
+    let username = fields.get("UserName").map(String::as_str).unwrap_or("");
+
+    if let Some(otp) = fields.get("otp").filter(|v| !v.trim().is_empty()) {
+        if !otp.trim().starts_with("otpauth://") {
+            return Some(parse_keeotp(otp, title, username));
+        }
+        return Some(totp_manager::parse_otpauth_uri(otp).map(|mut parsed| {
+            if parsed.issuer.is_empty() {
+                parsed.issuer = title.to_string();
+            }
+            if parsed.name.is_empty() {
+                parsed.name = if username.is_empty() { title } else { username }.to_string();
+            }
+            parsed
+        }));
+    }
+
+    if let Some(secret) = otp_secret(&fields, "TimeOtp") {
+        return Some(secret.and_then(|secret| {
+            build_entry(
+                username,
+                title,
+                &secret,
+                parse_hash_algorithm(fields.get("TimeOtp-Algorithm").map(String::as_str).unwrap_or("")),
+                parse_number(fields.get("TimeOtp-Length"), DEFAULT_DIGITS)?,
+                parse_number(fields.get("TimeOtp-Period"), DEFAULT_PERIOD)?,
+                OtpType::Totp,
+                0,
+            )
+        }));
+    }
+
+    if let Some(secret) = otp_secret(&fields, "HmacOtp") {
+        return Some(secret.and_then(|secret| {
+            build_entry(
+                username,
+                title,
+                &secret,
+                "SHA1",
+                DEFAULT_DIGITS,
+                DEFAULT_PERIOD,
+                OtpType::Hotp,
+                parse_number(fields.get("HmacOtp-Counter"), 0)?,
+            )
+        }));
+    }
+
+    None
+}
+
+// 递归收集分组中的条目，跳过回收站；条目的历史版本不导入
+fn collect_entries(group: &Node, recycle_bin: Option<&str>, out: &mut Vec<Result<TotpEntry>>) {
+    if let (Some(bin), Some(uuid)) = (recycle_bin, group.child("UUID")) {
+        if uuid.text.trim() == bin {
+            return;
+        }
+    }
+    out.extend(group.children_named("Entry").filter_map(parse_entry));
+    for child in group.children_named("Group") {
+        collect_entries(child, recycle_bin, out);
+    }
+}
+
+pub fn import(data: &[u8], password: Option<&str>) -> Result<Vec<Result<TotpEntry>>> {
+    let password = password
+        .ok_or_else(|| AppError::Encryption("Password required for KeePass database".to_string()))?;
+    let document = read_database(data, password)?;
+
+    let recycle_bin = document.child("Meta")
+        .and_then(|m| m.child("RecycleBinUUID"))
+        .map(|n| n.text.trim())
+        .filter(|uuid| !uuid.is_empty() && *uuid != "AAAAAAAAAAAAAAAAAAAAAA==");
+
+    let mut entries = Vec::new();
+    if let Some(root) = document.child("Root") {
+        for group in root.children_named("Group") {
+            collect_entries(group, recycle_bin, &mut entries);
+        }
+    }
+    Ok(entries)
+}
+
+// ---------- 写入 ----------
+
+fn write_field(out: &mut Vec<u8>, id: u8, data: &[u8]) {
+    out.push(id);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+fn write_variant(out: &mut Vec<u8>, value_type: u8, key: &str, value: &[u8]) {
+    out.push(value_type);
+    out.extend_from_slice(&(key.len() as i32).to_le_bytes());
+    out.extend_from_slice(key.as_bytes());
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+fn argon2_kdf_parameters(salt: &[u8], memory: u64, iterations: u64) -> Vec<u8> {
+    let mut dict = VARIANT_DICT_VERSION.to_le_bytes().to_vec();
+    write_variant(&mut dict, VARIANT_BYTES, "$UUID", &KDF_ARGON2D);
+    write_variant(&mut dict, VARIANT_BYTES, "S", salt);
+    write_variant(&mut dict, VARIANT_U32, "P", &EXPORT_ARGON2_PARALLELISM.to_le_bytes());
+    write_variant(&mut dict, VARIANT_U64, "M", &memory.to_le_bytes());
+    write_variant(&mut dict, VARIANT_U64, "I", &iterations.to_le_bytes());
+    write_variant(&mut dict, VARIANT_U32, "V", &0x13u32.to_le_bytes());
+    dict.push(VARIANT_END);
+    dict
+}
+
+fn uuid_base64(id: &str) -> String {
+    let uuid = uuid::Uuid::parse_str(id).unwrap_or_else(|_| uuid::Uuid::new_v4());
+    general_purpose::STANDARD.encode(uuid.as_bytes())
+}
+
+fn kdbx_time(time: chrono::DateTime<chrono::Utc>) -> String {
+    general_purpose::STANDARD.encode((time.timestamp() + DOTNET_EPOCH_OFFSET).to_le_bytes())
+}
+
+struct XmlWriter {
+    xml: String,
+    stream: chacha20::ChaCha20,
+}
+
+impl XmlWriter {
+    fn element(&mut self, name: &str, value: &str) {
+        self.xml.push_str(&format!("<{0}>{1}</{0}>", name, quick_xml::escape::escape(value)));
+    }
+
+    fn string(&mut self, key: &str, value: &str, protected: bool) {
+        self.xml.push_str("<String>");
+        self.element("Key", key);
+        if protected {
+            let mut bytes = value.as_bytes().to_vec();
+            self.stream.apply_keystream(&mut bytes);
+            self.xml.push_str(&format!(
+                "<Value Protected=\"True\">{}</Value>",
+                general_purpose::STANDARD.encode(bytes)
+            ));
+        } else {
+            self.element("Value", value);
+        }
+        self.xml.push_str("</String>");
+    }
+
+    fn entry(&mut self, entry: &TotpEntry) {
+        let created = chrono::DateTime::parse_from_rfc3339(&entry.created_at)
+            .map(|t| t.with_timezone(&chrono::Utc))
+            .unwrap_or_else(|_| chrono::Utc::now());
+        let created = kdbx_time(created);
+        self.xml.push_str("<Entry>");
+        self.element("UUID", &uuid_base64(&entry.id));
+        self.xml.push_str("<Times>");
+        for name in ["CreationTime", "LastModificationTime", "LastAccessTime", "ExpiryTime", "LocationChanged"] {
+            self.element(name, &created);
+        }
+        self.element("Expires", "False");
+        self.element("UsageCount", "0");
+        self.xml.push_str("</Times>");
+
+        let title = if entry.issuer.is_empty() { &entry.name } else { &entry.issuer };
+        self.string("Title", title, false);
+        self.string("UserName", &entry.name, false);
+        self.string("Password", "", true);
+        self.string("otp", &totp_manager::build_otpauth_uri(entry), true);
+
+        // 同时写入 KeePass 内置字段，便于在 KeePass 2 中使用 {TIMEOTP} / {HMACOTP}
+        let algorithm = match entry.algorithm {
+            OtpAlgorithm::SHA1 => "HMAC-SHA-1",
+            OtpAlgorithm::SHA256 => "HMAC-SHA-256",
+            OtpAlgorithm::SHA512 => "HMAC-SHA-512",
+        };
+        match entry.otp_type {
+            OtpType::Totp => {
+                self.string("TimeOtp-Secret-Base32", entry.secret.expose_secret(), true);
+                self.string("TimeOtp-Length", &entry.digits.to_string(), false);
+                self.string("TimeOtp-Period", &entry.period.to_string(), false);
+                self.string("TimeOtp-Algorithm", algorithm, false);
+            }
+            OtpType::Hotp => {
+                self.string("HmacOtp-Secret-Base32", entry.secret.expose_secret(), true);
+                self.string("HmacOtp-Counter", &entry.counter.to_string(), false);
+            }
+        }
+        self.xml.push_str("</Entry>");
+    }
+}
+
+fn build_xml(entries: &[TotpEntry], stream: chacha20::ChaCha20) -> String {
+    let now = kdbx_time(chrono::Utc::now());
+    let mut writer = XmlWriter {
+        xml: String::from("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<KeePassFile>"),
+        stream,
+    };
+
+    writer.xml.push_str("<Meta>");
+    writer.element("Generator", "web-totp");
+    writer.element("DatabaseName", "web-totp");
+    writer.element("DatabaseNameChanged", &now);
+    writer.xml.push_str("<MemoryProtection>");
+    writer.element("ProtectTitle", "False");
+    writer.element("ProtectUserName", "False");
+    writer.element("ProtectPassword", "True");
+    writer.element("ProtectURL", "False");
+    writer.element("ProtectNotes", "False");
+    writer.xml.push_str("</MemoryProtection>");
+    writer.element("RecycleBinEnabled", "False");
+    writer.xml.push_str("</Meta><Root><Group>");
+    writer.element("UUID", &uuid_base64(&uuid::Uuid::new_v4().to_string()));
+    writer.element("Name", "web-totp");
+    writer.element("IsExpanded", "True");
+    for entry in entries {
+        writer.entry(entry);
+    }
+    writer.xml.push_str("</Group><DeletedObjects/></Root></KeePassFile>");
+    writer.xml
+}
+
+pub fn export(entries: &[TotpEntry], password: Option<&str>) -> Result<Vec<u8>> {
+    let password = password.ok_or_else(|| {
+        AppError::Encryption("KeePass databases require a password".to_string())
+    })?;
+    let kdf_salt: [u8; 32] = rand::random();
+    let kdf_parameters = argon2_kdf_parameters(&kdf_salt, EXPORT_ARGON2_MEMORY, EXPORT_ARGON2_ITERATIONS);
+    write_database(entries, password, &kdf_parameters)
+}
+
+fn write_database(entries: &[TotpEntry], password: &str, kdf_parameters: &[u8]) -> Result<Vec<u8>> {
+    let master_seed: [u8; 32] = rand::random();
+    let iv: [u8; 16] = rand::random();
+    let mut stream_key = [0u8; 64];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut stream_key);
+
+    // 外层头
+    let mut header = Vec::new();
+    header.extend_from_slice(&SIGNATURE_1.to_le_bytes());
+    header.extend_from_slice(&SIGNATURE_2.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(&VERSION_MAJOR.to_le_bytes());
+    write_field(&mut header, HEADER_CIPHER_ID, &CIPHER_AES256);
+    write_field(&mut header, HEADER_COMPRESSION, &1u32.to_le_bytes());
+    write_field(&mut header, HEADER_MASTER_SEED, &master_seed);
+    write_field(&mut header, HEADER_ENCRYPTION_IV, &iv);
+    write_field(&mut header, HEADER_KDF_PARAMETERS, kdf_parameters);
+    write_field(&mut header, HEADER_END, b"\r\n\r\n");
+
+    let transformed = transform_key(&read_variant_dict(kdf_parameters)?, &composite_key(password))?;
+    let keys = derive_keys(&master_seed, &transformed);
+
+    // 内层头 + XML，压缩后加密
+    let mut payload = Vec::new();
+    write_field(&mut payload, INNER_STREAM_ID, &STREAM_CHACHA20.to_le_bytes());
+    write_field(&mut payload, INNER_STREAM_KEY, &stream_key);
+    write_field(&mut payload, INNER_END, &[]);
+    payload.extend_from_slice(build_xml(entries, inner_stream(&stream_key)).as_bytes());
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&payload)?;
+    let compressed = encoder.finish()?;
+    let ciphertext = cbc::Encryptor::<aes::Aes256>::new_from_slices(&keys.cipher, &iv)
+        .map_err(|_| AppError::Encryption("Invalid cipher parameters".to_string()))?
+        .encrypt_padded_vec_mut::<Pkcs7>(&compressed);
+
+    let mut file = header.clone();
+    file.extend_from_slice(&Sha256::digest(&header));
+    file.extend_from_slice(&block_hmac(&keys, u64::MAX, &[&header]).finalize().into_bytes());
+
+    let blocks = ciphertext.chunks(BLOCK_SIZE).chain(std::iter::once(&[][..]));
+    for (index, block) in (0u64..).zip(blocks) {
+        let len = (block.len() as i32).to_le_bytes();
+        let mac = block_hmac(&keys, index, &[&index.to_le_bytes(), &len, block]).finalize();
+        file.extend_from_slice(&mac.into_bytes());
+        file.extend_from_slice(&len);
+        file.extend_from_slice(block);
+    }
+
+    Ok(file)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::MAX_KDF_PARAMS;
+    use crate::formats::samples;
+
+    const AES_KDF: &[u8] = include_bytes!("../../tests/fixtures/keepass-aes-kdf.kdbx");
+    const ARGON2ID: &[u8] = include_bytes!("../../tests/fixtures/keepass-argon2id.kdbx");
+
+    // 样例中还有没有 TOTP 的条目、回收站里的条目和历史版本，都不应导入
+    #[test]
+    fn imports_aes_kdf_database() {
+        samples::assert_imported(import(AES_KDF, Some("keepass-test")).unwrap());
+        assert!(matches!(import(AES_KDF, None), Err(AppError::Encryption(_))));
+        assert!(matches!(import(AES_KDF, Some("wrong")), Err(AppError::Encryption(_))));
+    }
+
+    #[test]
+    fn imports_argon2id_chacha20_database() {
+        samples::assert_imported(import(ARGON2ID, Some("keepass-test")).unwrap());
+    }
+
+    // 导出默认使用 64 MiB 的 Argon2d，这里换成小参数以免拖慢测试
+    #[test]
+    fn round_trips() {
+        let kdf_parameters = argon2_kdf_parameters(&[7u8; 32], 1024 * 1024, 1);
+        let exported = write_database(&samples::entries(), "pw", &kdf_parameters).unwrap();
+        samples::assert_imported(import(&exported, Some("pw")).unwrap());
+    }
+
+    #[test]
+    fn limits_decompressed_size() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&[0u8; 4096]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(gunzip(&compressed, 4096).unwrap().len(), 4096);
+        assert!(matches!(gunzip(&compressed, 4095), Err(AppError::Storage(_))));
+    }
+
+    #[test]
+    fn rejects_excessive_kdf_parameters() {
+        let composite = [0u8; 32];
+        let aes = HashMap::from([
+            ("$UUID".to_string(), KDF_AES.to_vec()),
+            ("S".to_string(), vec![0u8; 32]),
+            ("R".to_string(), (MAX_AES_KDF_ROUNDS + 1).to_le_bytes().to_vec()),
+        ]);
+        assert!(matches!(transform_key(&aes, &composite), Err(AppError::Storage(_))));
+
+        let argon2 = read_variant_dict(&argon2_kdf_parameters(&[7u8; 32], 1024 * 1024, 1)).unwrap();
+        let limits = [
+            ("M", (u64::from(MAX_KDF_PARAMS.m_cost) + 1) * 1024),
+            ("I", u64::from(MAX_KDF_PARAMS.t_cost) + 1),
+            ("P", u64::from(MAX_KDF_PARAMS.p_cost) + 1),
+            ("M", u64::MAX),
+        ];
+        for (key, value) in limits {
+            let mut kdf = argon2.clone();
+            kdf.insert(key.to_string(), value.to_le_bytes().to_vec());
+            assert!(matches!(transform_key(&kdf, &composite), Err(AppError::Storage(_))), "{}", key);
+        }
+    }
+}
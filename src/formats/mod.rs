@@ -0,0 +1,170 @@
+// 其他验证器应用的备份格式与 TotpEntry 之间的转换
+//
+// 每个格式模块提供：
+//   import(data, password) -> 逐条解析结果（单个条目失败不影响其他条目）
+//   export(entries, password) -> 备份文件内容；password 为 None 时导出明文格式
+
+mod aegis;
+mod andotp;
+mod bitwarden;
+mod freeotp;
+mod keepass;
+mod twofas;
+
+use crate::crypto::MAX_KDF_PARAMS;
+use crate::error::{AppError, Result};
+use crate::models::{BackupFormat, OtpType, TotpEntry};
+use crate::totp_manager;
+
+pub fn import(format: BackupFormat, data: &[u8], password: Option<&str>) -> Result<Vec<Result<TotpEntry>>> {
+    match format {
+        BackupFormat::Aegis => aegis::import(data, password),
+        BackupFormat::TwoFas => twofas::import(data, password),
+        BackupFormat::AndOtp => andotp::import(data, password),
+        BackupFormat::FreeOtpPlus => freeotp::import(data, password),
+        BackupFormat::Bitwarden => bitwarden::import(data, password),
+        BackupFormat::KeePass => keepass::import(data, password),
+    }
+}
+
+pub fn export(format: BackupFormat, entries: &[TotpEntry], password: Option<&str>) -> Result<Vec<u8>> {
+    match format {
+        BackupFormat::Aegis => aegis::export(entries, password),
+        BackupFormat::TwoFas => twofas::export(entries, password),
+        BackupFormat::AndOtp => andotp::export(entries, password),
+        BackupFormat::FreeOtpPlus => freeotp::export(entries, password),
+        BackupFormat::Bitwarden => Err(AppError::Storage("Exporting to Bitwarden is not supported".to_string())),
+        BackupFormat::KeePass => keepass::export(entries, password),
+    }
+}
+
+// 各应用导入时识别的默认文件名
+pub fn file_name(format: BackupFormat, encrypted: bool) -> String {
+    let date = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+    match (format, encrypted) {
+        (BackupFormat::Aegis, true) => format!("aegis-export-{}.json", date),
+        (BackupFormat::Aegis, false) => format!("aegis-export-plain-{}.json", date),
+        (BackupFormat::TwoFas, _) => format!("2fas-backup-{}.2fas", date),
+        (BackupFormat::AndOtp, true) => format!("otp_accounts_{}.json.aes", date),
+        (BackupFormat::AndOtp, false) => format!("otp_accounts_{}.json", date),
+        (BackupFormat::FreeOtpPlus, _) => format!("freeotp-backup-{}.json", date),
+        (BackupFormat::Bitwarden, _) => format!("bitwarden_export_{}.json", date),
+        (BackupFormat::KeePass, _) => format!("web-totp-{}.kdbx", date),
+    }
+}
+
+// 由外部格式字段构造并校验条目
+#[allow(clippy::too_many_arguments)]
+fn build_entry(
+    name: &str,
+    issuer: &str,
+    secret: &str,
+    algorithm: &str,
+    digits: usize,
+    period: u64,
+    otp_type: OtpType,
+    counter: u64,
+) -> Result<TotpEntry> {
+    let secret = totp_manager::normalize_secret(secret);
+    if secret.is_empty() {
+        return Err(AppError::Totp("Entry has no secret".to_string()));
+    }
+
+    let name = if name.trim().is_empty() { issuer } else { name };
+    let mut entry = TotpEntry::new(name.trim().to_string(), issuer.trim().to_string(), secret);
+    entry.algorithm = totp_manager::parse_algorithm(algorithm)?;
+    entry.digits = digits;
+    entry.period = period;
+    entry.otp_type = otp_type;
+    entry.counter = counter;
+
+    totp_manager::validate_entry(&entry)?;
+    Ok(entry)
+}
+
+fn parse_otp_type(value: &str) -> Result<OtpType> {
+    match value.to_lowercase().as_str() {
+        "totp" => Ok(OtpType::Totp),
+        "hotp" => Ok(OtpType::Hotp),
+        other => Err(AppError::Totp(format!("Unsupported OTP type: {}", other))),
+    }
+}
+
+fn otp_type_name(otp_type: OtpType) -> &'static str {
+    match otp_type {
+        OtpType::Totp => "TOTP",
+        OtpType::Hotp => "HOTP",
+    }
+}
+
+fn invalid_backup(format: &str, reason: impl std::fmt::Display) -> AppError {
+    AppError::Storage(format!("Invalid {} backup: {}", format, reason))
+}
+
+fn wrong_password(format: &str) -> AppError {
+    AppError::Encryption(format!("Invalid {} backup password or corrupted file", format))
+}
+
+// 备份文件里的 KDF 参数由文件自身决定，派生密钥前先按 crypto::MAX_KDF_PARAMS 检查上限，
+// 导入的文件不能让服务器使用超过保险库本身允许的内存和计算量
+fn check_argon2_limits(format: &str, memory_kib: u64, iterations: u64, parallelism: u64) -> Result<()> {
+    if memory_kib > u64::from(MAX_KDF_PARAMS.m_cost) {
+        return Err(invalid_backup(format, format!("Argon2 memory {} KiB exceeds limit", memory_kib)));
+    }
+    if iterations > u64::from(MAX_KDF_PARAMS.t_cost) {
+        return Err(invalid_backup(format, format!("Argon2 iterations {} exceed limit", iterations)));
+    }
+    if parallelism > u64::from(MAX_KDF_PARAMS.p_cost) {
+        return Err(invalid_backup(format, format!("Argon2 parallelism {} exceeds limit", parallelism)));
+    }
+    Ok(())
+}
+
+// tests/fixtures 下各应用备份样例中的账户，由 tests/fixtures/generate.py 生成
+#[cfg(test)]
+mod samples {
+    use crate::error::Result;
+    use crate::models::{OtpAlgorithm, OtpType, TotpEntry};
+
+    pub fn entries() -> Vec<TotpEntry> {
+        let github = TotpEntry::new("alice@example.com".into(), "GitHub".into(), "JBSWY3DPEHPK3PXP".into());
+
+        let mut example = TotpEntry::new("bob".into(), "Example".into(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".into());
+        example.algorithm = OtpAlgorithm::SHA256;
+        example.digits = 8;
+        example.period = 60;
+
+        let mut bank = TotpEntry::new("carol".into(), "Bank".into(), "NBXXI4BNONSWG4TFOQWTCMRTGQ".into());
+        bank.otp_type = OtpType::Hotp;
+        bank.counter = 5;
+
+        vec![github, example, bank]
+    }
+
+    // 比较除 id 和创建时间以外的字段
+    pub fn assert_same(actual: &[TotpEntry], expected: &[TotpEntry]) {
+        let fields = |e: &TotpEntry| {
+            (
+                e.name.clone(),
+                e.issuer.clone(),
+                e.secret.expose_secret().to_string(),
+                e.algorithm,
+                e.digits,
+                e.period,
+                e.otp_type,
+                e.counter,
+            )
+        };
+        assert_eq!(
+            actual.iter().map(fields).collect::<Vec<_>>(),
+            expected.iter().map(fields).collect::<Vec<_>>(),
+        );
+    }
+
+    pub fn assert_imported(imported: Vec<Result<TotpEntry>>) {
+        let imported: Vec<TotpEntry> = imported.into_iter()
+            .map(|entry| entry.expect("sample entry should import"))
+            .collect();
+        assert_same(&imported, &entries());
+    }
+}
@@ -0,0 +1,231 @@
+// 2FAS Authenticator 备份（.2fas）
+//
+// 明文备份的 services 数组直接保存条目；加密备份的 services 为空，条目列表
+// 序列化后放在 servicesEncrypted 中，格式为 "密文+标签:盐:IV"（均为 Base64），
+// 密钥由 PBKDF2-HMAC-SHA256 派生。
+
+use super::{build_entry, invalid_backup, otp_type_name, wrong_password};
+use crate::crypto::{self, KEY_SIZE, NONCE_SIZE};
+use crate::error::{AppError, Result};
+use crate::models::{OtpType, TotpEntry, DEFAULT_DIGITS, DEFAULT_PERIOD};
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+const FORMAT: &str = "2FAS";
+const SCHEMA_VERSION: u32 = 4;
+const PBKDF2_ITERATIONS: u32 = 10_000;
+const SALT_LEN: usize = 256;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Backup {
+    #[serde(default)]
+    services: Vec<serde_json::Value>,
+    #[serde(default)]
+    groups: Vec<serde_json::Value>,
+    #[serde(default)]
+    updated_at: i64,
+    schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    services_encrypted: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Service {
+    #[serde(default)]
+    name: String,
+    secret: String,
+    #[serde(default)]
+    updated_at: i64,
+    #[serde(default)]
+    otp: Otp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    order: Option<Order>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Otp {
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    account: Option<String>,
+    #[serde(default)]
+    issuer: Option<String>,
+    #[serde(default)]
+    digits: Option<usize>,
+    #[serde(default)]
+    period: Option<u64>,
+    #[serde(default)]
+    algorithm: Option<String>,
+    #[serde(default)]
+    token_type: Option<String>,
+    #[serde(default)]
+    counter: Option<u64>,
+    #[serde(default)]
+    source: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Order {
+    position: usize,
+}
+
+fn derive_key(password: &str, salt: &[u8]) -> [u8; KEY_SIZE] {
+    let mut key = [0u8; KEY_SIZE];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, PBKDF2_ITERATIONS, &mut key);
+    key
+}
+
+fn decode_part(name: &str, value: &str) -> Result<Vec<u8>> {
+    general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| invalid_backup(FORMAT, format!("bad {}: {}", name, e)))
+}
+
+fn decrypt_services(encrypted: &str, password: &str) -> Result<Vec<serde_json::Value>> {
+    let parts: Vec<&str> = encrypted.split(':').collect();
+    let [ciphertext, salt, iv] = parts[..] else {
+        return Err(invalid_backup(FORMAT, "servicesEncrypted must have three parts"));
+    };
+    let ciphertext = decode_part("ciphertext", ciphertext)?;
+    let salt = decode_part("salt", salt)?;
+    let nonce: [u8; NONCE_SIZE] = decode_part("iv", iv)?
+        .try_into()
+        .map_err(|_| invalid_backup(FORMAT, "bad IV length"))?;
+
+    let key = derive_key(password, &salt);
+    let plaintext = crypto::decrypt(&key, &nonce, &ciphertext)
+        .map_err(|_| wrong_password(FORMAT))?;
+    serde_json::from_slice(&plaintext).map_err(|e| invalid_backup(FORMAT, e))
+}
+
+fn parse_service(value: serde_json::Value) -> Result<TotpEntry> {
+    let service: Service = serde_json::from_value(value)
+        .map_err(|e| invalid_backup(FORMAT, e))?;
+    let otp = service.otp;
+
+    let otp_type = match otp.token_type.as_deref().map(str::to_uppercase).as_deref() {
+        None | Some("TOTP") => OtpType::Totp,
+        Some("HOTP") => OtpType::Hotp,
+        Some(other) => return Err(AppError::Totp(format!("Unsupported OTP type: {}", other))),
+    };
+    let issuer = otp.issuer.filter(|s| !s.is_empty()).unwrap_or(service.name);
+    let name = otp.account.or(otp.label).unwrap_or_default();
+
+    build_entry(
+        &name,
+        &issuer,
+        &service.secret,
+        otp.algorithm.as_deref().unwrap_or("SHA1"),
+        otp.digits.unwrap_or(DEFAULT_DIGITS),
+        otp.period.unwrap_or(DEFAULT_PERIOD),
+        otp_type,
+        otp.counter.unwrap_or(0),
+    )
+}
+
+pub fn import(data: &[u8], password: Option<&str>) -> Result<Vec<Result<TotpEntry>>> {
+    let backup: Backup = serde_json::from_slice(data)
+        .map_err(|e| invalid_backup(FORMAT, e))?;
+
+    let services = match backup.services_encrypted.as_deref() {
+        Some(encrypted) if !encrypted.is_empty() => {
+            let password = password
+                .ok_or_else(|| AppError::Encryption("Password required for encrypted 2FAS backup".to_string()))?;
+            decrypt_services(encrypted, password)?
+        }
+        _ => backup.services,
+    };
+
+    Ok(services.into_iter().map(parse_service).collect())
+}
+
+pub fn export(entries: &[TotpEntry], password: Option<&str>) -> Result<Vec<u8>> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let services = entries.iter()
+        .enumerate()
+        .map(|(position, e)| {
+            serde_json::to_value(Service {
+                name: if e.issuer.is_empty() { e.name.clone() } else { e.issuer.clone() },
+                secret: e.secret.expose_secret().to_string(),
+                updated_at: now,
+                otp: Otp {
+                    label: Some(if e.issuer.is_empty() {
+                        e.name.clone()
+                    } else {
+                        format!("{}:{}", e.issuer, e.name)
+                    }),
+                    account: Some(e.name.clone()),
+                    issuer: Some(e.issuer.clone()),
+                    digits: Some(e.digits),
+                    period: Some(e.period),
+                    algorithm: Some(format!("{:?}", e.algorithm)),
+                    token_type: Some(otp_type_name(e.otp_type).to_string()),
+                    counter: (e.otp_type == OtpType::Hotp).then_some(e.counter),
+                    source: Some("Link".to_string()),
+                },
+                order: Some(Order { position }),
+            })
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let backup = match password {
+        None => Backup {
+            services,
+            groups: Vec::new(),
+            updated_at: now,
+            schema_version: SCHEMA_VERSION,
+            services_encrypted: None,
+        },
+        Some(password) => {
+            let salt: Vec<u8> = (0..SALT_LEN).map(|_| rand::random()).collect();
+            let key = derive_key(password, &salt);
+            let (nonce, ciphertext) = crypto::encrypt(&key, &serde_json::to_vec(&services)?)?;
+            Backup {
+                services: Vec::new(),
+                groups: Vec::new(),
+                updated_at: now,
+                schema_version: SCHEMA_VERSION,
+                services_encrypted: Some(format!(
+                    "{}:{}:{}",
+                    general_purpose::STANDARD.encode(ciphertext),
+                    general_purpose::STANDARD.encode(salt),
+                    general_purpose::STANDARD.encode(nonce),
+                )),
+            }
+        }
+    };
+
+    Ok(serde_json::to_vec_pretty(&backup)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::samples;
+
+    const PLAIN: &[u8] = include_bytes!("../../tests/fixtures/twofas-plain.2fas");
+    const ENCRYPTED: &[u8] = include_bytes!("../../tests/fixtures/twofas-encrypted.2fas");
+
+    #[test]
+    fn imports_plain_backup() {
+        samples::assert_imported(import(PLAIN, None).unwrap());
+    }
+
+    #[test]
+    fn imports_encrypted_backup() {
+        samples::assert_imported(import(ENCRYPTED, Some("2fas-test")).unwrap());
+        assert!(matches!(import(ENCRYPTED, None), Err(AppError::Encryption(_))));
+        assert!(matches!(import(ENCRYPTED, Some("wrong")), Err(AppError::Encryption(_))));
+    }
+
+    #[test]
+    fn round_trips_plain_and_encrypted() {
+        let entries = samples::entries();
+        samples::assert_imported(import(&export(&entries, None).unwrap(), None).unwrap());
+        samples::assert_imported(import(&export(&entries, Some("pw")).unwrap(), Some("pw")).unwrap());
+    }
+}
@@ -0,0 +1,216 @@
+// 完整性检查：数据能解密之后，再检查解密得到的内容是否可用
+//
+// 文件本身的问题（截断、文件头无效、认证失败）在解密时已经以不同的错误返回，
+// 这里检查的是条目 ID 重复、条目密钥或参数无效、两步验证密钥无效等解锁后才会暴露的问题
+//
+// web-totp check [--recover]
+//
+// 从标准输入读取主密码，检查配置的存储后端（WEB_TOTP_STORAGE / WEB_TOTP_DATA_PATH）中的数据并输出结果；
+// --recover 时在发现问题后从最新的有效备份恢复。服务运行中时数据文件被占用，需要先停止服务或改用 API。
+// 发现问题且没有恢复时以状态码 1 退出
+
+use crate::backend;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::models::{AppData, IntegrityReport};
+use crate::storage::Storage;
+use crate::totp_manager;
+use std::collections::HashSet;
+use std::io::BufRead;
+use zeroize::Zeroizing;
+
+pub async fn run_check_command(args: &[String]) -> std::io::Result<()> {
+    let recover = args.iter().any(|a| a == "--recover");
+    let config = Config::from_env();
+    let backend = backend::open(config.storage_backend, config.data_path.as_deref(), config.backup)
+        .unwrap_or_else(|e| fail(e));
+    let storage = Storage::new(backend, config.kdf, config.key_file_dir, None, config.save_debounce);
+
+    eprintln!("Master password:");
+    let mut line = Zeroizing::new(String::new());
+    std::io::stdin().lock().read_line(&mut line)?;
+    let password = line.trim_end_matches(['\r', '\n']);
+
+    let report = if recover {
+        storage.recover(password).await
+    } else {
+        storage.check_integrity(password).await
+    }.unwrap_or_else(|e| fail(e));
+
+    print_report(&report, recover);
+    if !report.ok && report.recovered_from.is_none() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn fail(e: AppError) -> ! {
+    eprintln!("{}", e);
+    std::process::exit(1);
+}
+
+fn print_report(report: &IntegrityReport, recover: bool) {
+    if !report.found {
+        println!("No data found, nothing to check");
+        return;
+    }
+    match &report.vault_error {
+        Some(e) => println!("Data: {}", e),
+        None => println!(
+            "Data: readable (schema version {}, {} entries)",
+            report.schema_version.unwrap_or_default(),
+            report.entries.unwrap_or_default()
+        ),
+    }
+    for issue in &report.issues {
+        println!("  - {}", issue);
+    }
+    if report.ok {
+        println!("Integrity check passed");
+        return;
+    }
+
+    match &report.newest_valid_backup {
+        Some(id) => println!("Newest valid backup: {}", id),
+        None => println!("No valid backup found"),
+    }
+    if let Some(id) = &report.recovered_from {
+        if let Some(path) = &report.damaged_moved_to {
+            println!("Damaged data moved to {}", path);
+        }
+        println!("Recovered from backup {}", id);
+    } else if !recover && report.newest_valid_backup.is_some() {
+        println!("Run `web-totp check --recover` to restore it");
+    }
+}
+
+// 返回发现的问题，没有问题时为空
+pub fn check(data: &AppData) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    let mut ids = HashSet::new();
+    for entry in &data.totp_entries {
+        if !ids.insert(entry.id.as_str()) {
+            issues.push(format!("Duplicate entry id {}", entry.id));
+        }
+        if let Err(e) = totp_manager::validate_entry(entry) {
+            issues.push(format!("Entry {} ({}): {}", entry.id, entry.name, e));
+        }
+    }
+
+    if data.user.two_fa_enabled {
+        match &data.user.two_fa_secret {
+            None => issues.push("2FA is enabled but no 2FA secret is stored".to_string()),
+            Some(secret) => {
+                if let Err(e) = totp_manager::decode_base32(secret.expose_secret()) {
+                    issues.push(format!("2FA secret: {}", e));
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{file_storage, sample_entry, TempDir, EVERY_SAVE};
+    use std::fs;
+
+    // 写入两次（每次保存前备份）：最新的备份只有 alice，data.enc 有 alice 和 bob。返回 data.enc 的路径
+    async fn vault_with_backups(dir: &TempDir) -> (Storage, String) {
+        let path = dir.file("data.enc");
+        let storage = file_storage(&path, EVERY_SAVE);
+        storage.unlock("pw").await.unwrap();
+        for name in ["alice", "bob"] {
+            storage.add_totp_entry(sample_entry(name)).await.unwrap();
+            storage.save().await.unwrap();
+            // 备份 ID 精确到毫秒
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        storage.lock(false).await.unwrap();
+        (storage, path)
+    }
+
+    type Damage = fn(&mut Vec<u8>);
+
+    fn corrupt(path: &str, f: impl FnOnce(&mut Vec<u8>)) {
+        let mut bytes = fs::read(path).unwrap();
+        f(&mut bytes);
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn reports_distinct_errors_for_damaged_data() {
+        let dir = TempDir::new();
+        let (storage, path) = vault_with_backups(&dir).await;
+        let original = fs::read(&path).unwrap();
+
+        let report = storage.check_integrity("pw").await.unwrap();
+        assert!(report.ok && report.found);
+        assert_eq!(report.entries, Some(2));
+
+        let cases: [(Damage, String); 3] = [
+            (|bytes| bytes.truncate(20), AppError::VaultTruncated.to_string()),
+            // 格式版本 0 不存在
+            (|bytes| bytes[8..10].copy_from_slice(&0u16.to_le_bytes()), "Invalid data file header".to_string()),
+            (|bytes| *bytes.last_mut().unwrap() ^= 1, AppError::AuthenticationFailed("vault data".into()).to_string()),
+        ];
+        for (damage, expected) in cases {
+            fs::write(&path, &original).unwrap();
+            corrupt(&path, damage);
+            let report = storage.check_integrity("pw").await.unwrap();
+            assert!(!report.ok && report.found);
+            let error = report.vault_error.unwrap();
+            assert!(error.starts_with(&expected), "{} should start with {}", error, expected);
+            assert!(report.newest_valid_backup.is_some());
+        }
+
+        // 数据已损坏时，密码错误仍然报告为密码错误，不逐个尝试备份
+        assert!(matches!(storage.check_integrity("wrong").await, Err(AppError::InvalidMasterPassword)));
+    }
+
+    #[actix_web::test]
+    async fn recovers_from_newest_valid_backup() {
+        let dir = TempDir::new();
+        let (storage, path) = vault_with_backups(&dir).await;
+        let newest_backup = storage.list_backups().await.unwrap()[0].id.clone();
+        corrupt(&path, |bytes| *bytes.last_mut().unwrap() ^= 1);
+
+        storage.unlock("pw").await.unwrap_err();
+        let report = storage.recover("pw").await.unwrap();
+        assert_eq!(report.recovered_from.as_ref(), Some(&newest_backup));
+        let damaged = report.damaged_moved_to.unwrap();
+        assert!(damaged.starts_with(&format!("{}.damaged-", path)));
+        assert!(fs::metadata(&damaged).is_ok());
+
+        storage.unlock("pw").await.unwrap();
+        let names: Vec<_> = storage.get_totp_entries().await.unwrap().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["alice"]);
+
+        // 已解锁时不能恢复；数据完好时不做任何修改
+        assert!(matches!(storage.recover("pw").await, Err(AppError::Storage(_))));
+        storage.lock(false).await.unwrap();
+        let report = storage.recover("pw").await.unwrap();
+        assert!(report.ok && report.recovered_from.is_none());
+    }
+
+    #[test]
+    fn check_reports_duplicate_ids_and_invalid_secrets() {
+        let mut data = AppData::default();
+        let entry = sample_entry("alice");
+        data.totp_entries.push(entry.clone());
+        assert!(check(&data).is_empty());
+
+        data.totp_entries.push(entry);
+        let mut invalid = sample_entry("bob");
+        invalid.digits = 3;
+        data.totp_entries.push(invalid);
+        data.user.two_fa_enabled = true;
+        let issues = check(&data);
+        assert_eq!(issues.len(), 3, "{:?}", issues);
+        assert!(issues[0].starts_with("Duplicate entry id"));
+        assert!(issues[2].starts_with("2FA is enabled"));
+    }
+}
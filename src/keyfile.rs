@@ -0,0 +1,194 @@
+// 密钥文件：文件内容（去掉末尾的换行）作为密钥槽的秘密，与主密码一样经过 Argon2id 派生。
+// 新建的密钥文件内容为 32 个随机字节的十六进制，Unix 上权限为 0600。
+// 通过 API 添加的密钥文件只能位于 WEB_TOTP_KEY_FILE_DIR 目录中，已有的文件同样要求只有所有者可以访问
+//
+// 启动时自动解锁（WEB_TOTP_UNLOCK_KEY_FILE / WEB_TOTP_UNLOCK_CREDENTIAL）读取的文件必须只有所有者可以访问，
+// 否则拒绝启动
+
+use crate::config::UnlockSource;
+use crate::error::{AppError, Result};
+use std::fs;
+use std::io::Write;
+use zeroize::Zeroizing;
+
+pub fn read(path: &str) -> Result<Zeroizing<String>> {
+    let contents = Zeroizing::new(fs::read(path)
+        .map_err(|e| AppError::Storage(format!("Failed to read key file {}: {}", path, e)))?);
+    let text = std::str::from_utf8(&contents)
+        .map_err(|_| AppError::Storage(format!("Key file {} is not valid UTF-8 text", path)))?;
+    let secret = text.trim_end_matches(['\r', '\n']);
+    if secret.is_empty() {
+        return Err(AppError::Storage(format!("Key file {} is empty", path)));
+    }
+    Ok(Zeroizing::new(secret.to_string()))
+}
+
+// 通过 API 添加的密钥文件只能位于 dir 中：相对路径相对于 dir，绝对路径所在的目录必须就是 dir，
+// 不接受符号链接。dir 不存在时创建（Unix 上权限为 0700）。返回 dir 规范化后的完整路径
+pub fn resolve_in_dir(dir: &str, path: &str) -> Result<String> {
+    let invalid = |reason: String| AppError::InvalidKeyFilePath(format!("{}: {}", path, reason));
+
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+        .map_err(|e| AppError::Storage(format!("Failed to create key file directory {}: {}", dir, e)))?;
+    let dir = fs::canonicalize(dir)
+        .map_err(|e| AppError::Storage(format!("Failed to open key file directory {}: {}", dir, e)))?;
+
+    // 绝对路径 join 后替换 dir 本身
+    let requested = dir.join(path);
+    let name = requested.file_name()
+        .ok_or_else(|| invalid("not a file name".to_string()))?;
+    let parent = requested.parent()
+        .and_then(|parent| fs::canonicalize(parent).ok())
+        .ok_or_else(|| invalid("directory does not exist".to_string()))?;
+    if parent != dir {
+        return Err(invalid(format!("key files must be in {}", dir.display())));
+    }
+    let resolved = dir.join(name);
+    if fs::symlink_metadata(&resolved).is_ok_and(|m| m.file_type().is_symlink()) {
+        return Err(invalid("symbolic links are not allowed".to_string()));
+    }
+    Ok(resolved.to_string_lossy().into_owned())
+}
+
+// 文件已存在时检查权限后读取，否则生成新的密钥文件（不会覆盖已有文件）
+pub fn read_or_create(path: &str) -> Result<Zeroizing<String>> {
+    if fs::metadata(path).is_ok() {
+        check_permissions(path)?;
+        return read(path);
+    }
+
+    let secret = Zeroizing::new(hex::encode(rand::random::<[u8; 32]>()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)
+        .map_err(|e| AppError::Storage(format!("Failed to create key file {}: {}", path, e)))?;
+    writeln!(file, "{}", secret.as_str())
+        .and_then(|_| file.sync_all())
+        .map_err(|e| AppError::Storage(format!("Failed to write key file {}: {}", path, e)))?;
+    log::info!("Created key file {}", path);
+    Ok(secret)
+}
+
+// 读取启动时解锁使用的秘密，文件权限过宽时返回 InsecureKeyFile
+pub fn read_unlock_secret(source: &UnlockSource) -> Result<Zeroizing<String>> {
+    let path = match source {
+        UnlockSource::KeyFile(path) => path.clone(),
+        UnlockSource::Credential(name) => credential_path(name)?,
+    };
+    check_permissions(&path)?;
+    read(&path)
+}
+
+// systemd 把凭据放在 $CREDENTIALS_DIRECTORY 下，名称不能包含路径分隔符
+fn credential_path(name: &str) -> Result<String> {
+    if name.contains('/') || name == "." || name == ".." {
+        return Err(AppError::Storage(format!("Invalid credential name: {}", name)));
+    }
+    let dir = std::env::var("CREDENTIALS_DIRECTORY").map_err(|_| AppError::Storage(format!(
+        "Credential {} requested but CREDENTIALS_DIRECTORY is not set (is the service started with LoadCredential=?)",
+        name
+    )))?;
+    Ok(std::path::Path::new(&dir).join(name).to_string_lossy().into_owned())
+}
+
+// 组或其他用户有任何权限时拒绝使用（符号链接按目标文件检查）
+#[cfg(unix)]
+pub fn check_permissions(path: &str) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = fs::metadata(path)
+        .map_err(|e| AppError::Storage(format!("Failed to read key file {}: {}", path, e)))?;
+    if !metadata.is_file() {
+        return Err(AppError::Storage(format!("Key file {} is not a regular file", path)));
+    }
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        return Err(AppError::InsecureKeyFile(format!(
+            "{} has mode {:04o}, it must not be accessible by group or others (chmod 600)",
+            path, mode
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn check_permissions(_path: &str) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[cfg(unix)]
+    fn write_with_mode(path: &str, contents: &str, mode: u32) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::write(path, contents).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_permissions_rejects_group_or_other_access() {
+        let dir = TempDir::new();
+        let path = dir.file("unlock.key");
+        for mode in [0o600, 0o400, 0o700] {
+            write_with_mode(&path, "secret", mode);
+            check_permissions(&path).unwrap();
+        }
+        for mode in [0o644, 0o640, 0o604, 0o620, 0o601] {
+            write_with_mode(&path, "secret", mode);
+            assert!(matches!(check_permissions(&path), Err(AppError::InsecureKeyFile(_))), "{:o}", mode);
+        }
+        assert!(check_permissions(&dir.file("missing.key")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_or_create_creates_private_file_and_checks_existing_ones() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new();
+        let path = dir.file("unlock.key");
+        let created = read_or_create(&path).unwrap();
+        assert_eq!(created.len(), 64);
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(read_or_create(&path).unwrap(), created);
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(matches!(read_or_create(&path), Err(AppError::InsecureKeyFile(_))));
+    }
+
+    #[test]
+    fn read_trims_trailing_newlines_and_rejects_empty_files() {
+        let dir = TempDir::new();
+        let path = dir.file("unlock.key");
+        fs::write(&path, "  secret \r\n\n").unwrap();
+        assert_eq!(read(&path).unwrap().as_str(), "  secret ");
+
+        fs::write(&path, "\n").unwrap();
+        assert!(read(&path).is_err());
+    }
+
+    #[test]
+    fn resolve_in_dir_keeps_key_files_inside_dir() {
+        let dir = TempDir::new();
+        let key_dir = dir.file("keys");
+        let resolved = resolve_in_dir(&key_dir, "unlock.key").unwrap();
+        let canonical = fs::canonicalize(&key_dir).unwrap();
+        assert_eq!(resolved, canonical.join("unlock.key").to_string_lossy());
+        let absolute = canonical.join("other.key");
+        assert!(resolve_in_dir(&key_dir, &absolute.to_string_lossy()).is_ok());
+
+        for path in ["../unlock.key", "/etc/passwd", "sub/unlock.key", ".."] {
+            assert!(matches!(resolve_in_dir(&key_dir, path), Err(AppError::InvalidKeyFilePath(_))), "{}", path);
+        }
+    }
+}
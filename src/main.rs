@@ -9,12 +9,27 @@ mod auth;
 mod totp_manager;
 mod api;
 mod error;
+mod crypto;
+mod migration;
+mod export;
+mod formats;
+mod file_format;
+mod config;
+mod backup;
+mod backend;
+mod integrity;
+mod keyfile;
+mod secret;
+mod bench;
+#[cfg(test)]
+mod testutil;
 
-use actix_web::{web, App, HttpServer, middleware};
+use actix_web::{dev::Service, web, App, HttpServer, middleware};
 use actix_files as fs;
 use actix_session::{SessionMiddleware, storage::CookieSessionStore};
 use actix_web::cookie::Key;
-use log::info;
+use error::AppError;
+use log::{error, info};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -26,12 +41,48 @@ async fn main() -> std::io::Result<()> {
     // 加载环境变量
     dotenv::dotenv().ok();
     
+    // 子命令：基准测试、完整性检查
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("bench-save") => return bench::run_save_benchmark(&args[1..]).await,
+        Some("check") => return integrity::run_check_command(&args[1..]).await,
+        _ => {}
+    }
+    
     info!("Starting Web TOTP Server...");
     
+    let config = config::Config::from_env();
+    
     // Initialize storage (unlocked later via API)
-    let storage = storage::Storage::new("data.enc").await
-        .expect("Failed to initialize storage");
+    // 数据文件被另一个进程占用时直接退出
+    let backend = match backend::open(config.storage_backend, config.data_path.as_deref(), config.backup) {
+        Ok(backend) => backend,
+        Err(e) => {
+            error!("Failed to initialize storage: {}", e);
+            eprintln!("Failed to initialize storage: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let storage = storage::Storage::new(backend, config.kdf, config.key_file_dir, config.idle_lock, config.save_debounce);
+    
+    // 配置了密钥文件时在启动时解锁，失败（包括文件权限过宽）时拒绝启动
+    if let Some(source) = &config.unlock_key {
+        if let Err(e) = unlock_at_startup(&storage, source).await {
+            error!("Failed to unlock database at startup: {}", e);
+            eprintln!("Failed to unlock database at startup: {}", e);
+            std::process::exit(1);
+        }
+    }
     let app_data = web::Data::new(storage);
+    
+    // 空闲自动锁定
+    let auto_lock_storage = app_data.clone();
+    actix_web::rt::spawn(async move { auto_lock_storage.run_auto_lock().await });
+    
+    // 后台保存
+    let writer_storage = app_data.clone();
+    actix_web::rt::spawn(async move { writer_storage.run_writer().await });
+    let shutdown_storage = app_data.clone();
 
     // Generate secret key for sessions
     let secret_key = Key::generate();
@@ -42,6 +93,20 @@ async fn main() -> std::io::Result<()> {
     HttpServer::new(move || {
         App::new()
             .app_data(app_data.clone())
+            // 允许上传较大的二维码截图
+            .app_data(web::PayloadConfig::new(10 * 1024 * 1024))
+            .wrap_fn({
+                let storage = app_data.clone();
+                move |req, srv| {
+                    let storage = storage.clone();
+                    let response = srv.call(req);
+                    async move {
+                        let response = response.await?;
+                        auth::track_activity(response.request(), &storage);
+                        Ok(response)
+                    }
+                }
+            })
             .wrap(middleware::Logger::default())
             .wrap(
                 SessionMiddleware::builder(
@@ -55,24 +120,63 @@ async fn main() -> std::io::Result<()> {
                 web::scope("/api")
                     .service(api::unlock_database)
                     .service(api::get_lock_status)
+                    .service(api::lock_database)
+                    .service(api::check_integrity)
+                    .service(api::recover_from_backup)
                     .service(api::check_user_2fa)
                     .service(api::login)
                     .service(api::logout)
                     .service(api::check_session)
                     .service(api::change_password)
+                    .service(api::change_master_password)
+                    .service(api::list_key_slots)
+                    .service(api::add_password_slot)
+                    .service(api::add_recovery_key_slot)
+                    .service(api::add_key_file_slot)
+                    .service(api::remove_key_slot)
                     .service(api::enable_2fa)
                     .service(api::disable_2fa)
                     .service(api::verify_2fa)
                     .service(api::get_2fa_status)
                     .service(api::add_totp_entry)
+                    .service(api::import_otpauth_uris)
+                    .service(api::import_migration)
+                    .service(api::scan_qr_image)
                     .service(api::list_totp_entries)
                     .service(api::delete_totp_entry)
+                    .service(api::export_totp_entry)
                     .service(api::generate_totp_code)
+                    .service(api::resync_hotp_counter)
+                    .service(api::export_vault)
+                    .service(api::import_vault)
+                    .service(api::import_backup)
+                    .service(api::export_backup)
+                    .service(api::list_vault_backups)
+                    .service(api::restore_vault_backup)
             )
             .service(fs::Files::new("/", "./static").index_file("index.html"))
     })
     .bind(("127.0.0.1", 18007))?
     .run()
-    .await
+    .await?;
+    
+    // 正常退出（Ctrl+C / SIGTERM）时写入尚未保存的修改
+    if let Err(e) = shutdown_storage.flush().await {
+        error!("Failed to save pending changes on shutdown: {}", e);
+    }
+    Ok(())
 }
 
+
+// 只解锁已有的数据：新建数据库需要设置主密码并保存恢复密钥，不能在无人值守时进行
+async fn unlock_at_startup(storage: &storage::Storage, source: &config::UnlockSource) -> error::Result<()> {
+    let secret = keyfile::read_unlock_secret(source)?;
+    if !storage.has_vault().await? {
+        return Err(AppError::Storage(
+            "No data to unlock yet, set the master password through the web interface first".to_string(),
+        ));
+    }
+    storage.unlock(&secret).await?;
+    info!("Database unlocked at startup using {}", source);
+    Ok(())
+}
@@ -0,0 +1,359 @@
+// Google Authenticator "otpauth-migration://offline?data=..." 导出格式解析
+//
+// data 参数是 Base64 编码的 protobuf 消息：
+//
+//   message MigrationPayload {
+//     repeated OtpParameters otp_parameters = 1;
+//     int32 version = 2;
+//     int32 batch_size = 3;
+//     int32 batch_index = 4;
+//     int32 batch_id = 5;
+//   }
+//
+//   message OtpParameters {
+//     bytes secret = 1;
+//     string name = 2;
+//     string issuer = 3;
+//     Algorithm algorithm = 4;   // 0 未指定, 1 SHA1, 2 SHA256, 3 SHA512, 4 MD5
+//     DigitCount digits = 5;     // 0 未指定, 1 六位, 2 八位
+//     OtpType type = 6;          // 0 未指定, 1 HOTP, 2 TOTP
+//     int64 counter = 7;
+//   }
+//
+// 消息结构很小，这里直接手写 protobuf 解码，不引入代码生成依赖。
+
+use crate::error::{AppError, Result};
+use crate::models::{MissingBatches, OtpAlgorithm, OtpType, TotpEntry};
+use crate::totp_manager;
+use base64::{Engine as _, engine::general_purpose};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub struct MigrationBatch {
+    pub batch_id: i32,
+    pub batch_index: i32,
+    pub batch_size: i32,
+    pub entries: Vec<Result<TotpEntry>>,
+}
+
+struct ProtoReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+enum FieldValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn read_varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.buf.get(self.pos)
+                .ok_or_else(|| malformed("truncated varint"))?;
+            self.pos += 1;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(malformed("varint too long"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| malformed("truncated field"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    // 读取下一个字段，返回 (字段号, 值)；固定长度字段被跳过
+    fn next_field(&mut self) -> Result<Option<(u64, FieldValue<'a>)>> {
+        loop {
+            if self.pos >= self.buf.len() {
+                return Ok(None);
+            }
+            let key = self.read_varint()?;
+            let field = key >> 3;
+            match key & 0x7 {
+                0 => return Ok(Some((field, FieldValue::Varint(self.read_varint()?)))),
+                1 => {
+                    self.take(8)?;
+                }
+                2 => {
+                    let len = self.read_varint()? as usize;
+                    return Ok(Some((field, FieldValue::Bytes(self.take(len)?))));
+                }
+                5 => {
+                    self.take(4)?;
+                }
+                wire_type => {
+                    return Err(malformed(&format!("unsupported wire type {}", wire_type)));
+                }
+            }
+        }
+    }
+}
+
+fn malformed(reason: &str) -> AppError {
+    AppError::Totp(format!("Malformed migration payload: {}", reason))
+}
+
+fn to_string(bytes: &[u8]) -> Result<String> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| malformed("invalid UTF-8 string"))
+}
+
+fn parse_otp_parameters(buf: &[u8]) -> Result<TotpEntry> {
+    let mut reader = ProtoReader::new(buf);
+    let mut secret = Vec::new();
+    let mut name = String::new();
+    let mut issuer = String::new();
+    let mut algorithm = OtpAlgorithm::default();
+    let mut digits = 6;
+    let mut otp_type = OtpType::Totp;
+    let mut counter = 0;
+
+    while let Some((field, value)) = reader.next_field()? {
+        match (field, value) {
+            (1, FieldValue::Bytes(b)) => secret = b.to_vec(),
+            (2, FieldValue::Bytes(b)) => name = to_string(b)?,
+            (3, FieldValue::Bytes(b)) => issuer = to_string(b)?,
+            (4, FieldValue::Varint(v)) => {
+                algorithm = match v {
+                    0 | 1 => OtpAlgorithm::SHA1,
+                    2 => OtpAlgorithm::SHA256,
+                    3 => OtpAlgorithm::SHA512,
+                    4 => return Err(AppError::Totp("MD5 algorithm is not supported".to_string())),
+                    _ => return Err(malformed(&format!("unknown algorithm {}", v))),
+                }
+            }
+            (5, FieldValue::Varint(v)) => {
+                digits = match v {
+                    0 | 1 => 6,
+                    2 => 8,
+                    _ => return Err(malformed(&format!("unknown digit count {}", v))),
+                }
+            }
+            (6, FieldValue::Varint(v)) => {
+                otp_type = match v {
+                    1 => OtpType::Hotp,
+                    0 | 2 => OtpType::Totp,
+                    _ => return Err(malformed(&format!("unknown OTP type {}", v))),
+                }
+            }
+            (7, FieldValue::Varint(v)) => counter = v,
+            _ => {}
+        }
+    }
+
+    if secret.is_empty() {
+        return Err(AppError::Totp("Entry has no secret".to_string()));
+    }
+
+    // name 可能带有 "issuer:" 前缀，与 otpauth URI 标签一致
+    let (label_issuer, account) = match name.split_once(':') {
+        Some((prefix, account)) => (prefix.trim().to_string(), account.trim().to_string()),
+        None => (String::new(), name.trim().to_string()),
+    };
+    let issuer = if issuer.is_empty() { label_issuer } else { issuer };
+    let name = if account.is_empty() { issuer.clone() } else { account };
+
+    let mut entry = TotpEntry::new(name, issuer, totp_manager::encode_base32(&secret));
+    entry.algorithm = algorithm;
+    entry.digits = digits;
+    entry.otp_type = otp_type;
+    entry.counter = counter;
+
+    totp_manager::validate_entry(&entry)?;
+    Ok(entry)
+}
+
+fn parse_payload(buf: &[u8]) -> Result<MigrationBatch> {
+    let mut reader = ProtoReader::new(buf);
+    let mut batch = MigrationBatch {
+        batch_id: 0,
+        batch_index: 0,
+        batch_size: 1,
+        entries: Vec::new(),
+    };
+
+    while let Some((field, value)) = reader.next_field()? {
+        match (field, value) {
+            (1, FieldValue::Bytes(b)) => batch.entries.push(parse_otp_parameters(b)),
+            (3, FieldValue::Varint(v)) => batch.batch_size = v as i32,
+            (4, FieldValue::Varint(v)) => batch.batch_index = v as i32,
+            (5, FieldValue::Varint(v)) => batch.batch_id = v as i32,
+            _ => {}
+        }
+    }
+
+    Ok(batch)
+}
+
+// 按 batch_id 分组，找出每次导出中没有提交的批次（按 batch_id 排列）
+pub fn missing_batches(batches: &[MigrationBatch]) -> Vec<MissingBatches> {
+    let mut exports: BTreeMap<i32, (i32, BTreeSet<i32>)> = BTreeMap::new();
+    for batch in batches {
+        let export = exports.entry(batch.batch_id).or_insert_with(|| (batch.batch_size, BTreeSet::new()));
+        export.0 = export.0.max(batch.batch_size);
+        export.1.insert(batch.batch_index);
+    }
+
+    exports.into_iter()
+        .filter_map(|(batch_id, (batch_size, received))| {
+            let missing_indexes: Vec<i32> = (0..batch_size).filter(|i| !received.contains(i)).collect();
+            (!missing_indexes.is_empty()).then_some(MissingBatches {
+                batch_id,
+                batch_size,
+                received: received.len(),
+                missing_indexes,
+            })
+        })
+        .collect()
+}
+
+// 解析一个 otpauth-migration://offline?data=... URI（多批次导出中的一批）
+pub fn parse_migration_uri(uri: &str) -> Result<MigrationBatch> {
+    let url = url::Url::parse(uri.trim())
+        .map_err(|e| AppError::Totp(format!("Invalid URI: {}", e)))?;
+
+    if url.scheme() != "otpauth-migration" {
+        return Err(AppError::Totp(format!("Unsupported URI scheme: {}", url.scheme())));
+    }
+
+    let data = url.query_pairs()
+        .find(|(key, _)| key == "data")
+        .map(|(_, value)| value.into_owned())
+        .ok_or_else(|| AppError::Totp("Missing data parameter".to_string()))?;
+
+    // query_pairs 会把 '+' 解码为空格，这里还原；同时兼容 URL-safe Base64 和缺省填充
+    let data: String = data
+        .chars()
+        .map(|c| match c {
+            ' ' => '+',
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .filter(|c| *c != '=' && !c.is_whitespace())
+        .collect();
+    let payload = general_purpose::STANDARD_NO_PAD
+        .decode(data.as_bytes())
+        .map_err(|e| AppError::Totp(format!("Invalid Base64 payload: {}", e)))?;
+
+    parse_payload(&payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DEFAULT_PERIOD;
+
+    // tests/fixtures/generate.py 按应用的导出格式生成，账户与 src/formats 的样例相同
+    const SINGLE: &str = include_str!("../tests/fixtures/google-authenticator-single.txt");
+    const BATCHES: &str = include_str!("../tests/fixtures/google-authenticator-batches.txt");
+
+    fn parse_entries(uri: &str) -> (MigrationBatch, Vec<TotpEntry>) {
+        let mut batch = parse_migration_uri(uri).unwrap();
+        let entries = std::mem::take(&mut batch.entries).into_iter().map(Result::unwrap).collect();
+        (batch, entries)
+    }
+
+    fn assert_sample_accounts(entries: &[TotpEntry]) {
+        let [github, example, bank] = entries else {
+            panic!("expected three entries, got {}", entries.len());
+        };
+        assert_eq!((github.issuer.as_str(), github.name.as_str()), ("GitHub", "alice@example.com"));
+        assert_eq!(github.secret.expose_secret(), "JBSWY3DPEHPK3PXP");
+        assert_eq!((github.algorithm, github.digits, github.otp_type), (OtpAlgorithm::SHA1, 6, OtpType::Totp));
+
+        assert_eq!((example.issuer.as_str(), example.name.as_str()), ("Example", "bob"));
+        assert_eq!(example.secret.expose_secret(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!((example.algorithm, example.digits), (OtpAlgorithm::SHA256, 8));
+        // 导出格式没有周期字段
+        assert_eq!(example.period, DEFAULT_PERIOD);
+
+        assert_eq!((bank.issuer.as_str(), bank.name.as_str()), ("Bank", "carol"));
+        assert_eq!(bank.secret.expose_secret(), "NBXXI4BNONSWG4TFOQWTCMRTGQ");
+        assert_eq!((bank.otp_type, bank.counter), (OtpType::Hotp, 5));
+    }
+
+    // 格式说明中广泛引用的示例导出（只有一个账户）
+    #[test]
+    fn decodes_published_example() {
+        let uri = "otpauth-migration://offline?data=CjEKCkhlbGxvId6tvu8SGEV4YW1wbGU6YWxpY2VAZ29vZ2xlLmNvbRoHRXhhbXBsZSABKAEwAhABGAEgACjr4JP%2BBw%3D%3D";
+        let (batch, entries) = parse_entries(uri);
+        assert_eq!((batch.batch_size, batch.batch_index, batch.batch_id), (1, 0, 2143613035));
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].issuer.as_str(), entries[0].name.as_str()), ("Example", "alice@google.com"));
+        assert_eq!(entries[0].secret.expose_secret(), "JBSWY3DPEHPK3PXP");
+    }
+
+    #[test]
+    fn decodes_single_batch_export() {
+        let (batch, entries) = parse_entries(SINGLE.trim());
+        assert_eq!((batch.batch_size, batch.batch_index, batch.batch_id), (1, 0, 1234567890));
+        assert_sample_accounts(&entries);
+    }
+
+    #[test]
+    fn decodes_multi_batch_export() {
+        let mut entries = Vec::new();
+        for (index, uri) in BATCHES.lines().enumerate() {
+            let (batch, batch_entries) = parse_entries(uri);
+            assert_eq!((batch.batch_size, batch.batch_index, batch.batch_id), (2, index as i32, 987654321));
+            entries.extend(batch_entries);
+        }
+        assert_sample_accounts(&entries);
+    }
+
+    #[test]
+    fn reports_missing_batches() {
+        let uris: Vec<&str> = BATCHES.lines().collect();
+        let parse_all = |uris: &[&str]| -> Vec<MigrationBatch> {
+            uris.iter().map(|uri| parse_migration_uri(uri).unwrap()).collect()
+        };
+
+        assert!(missing_batches(&parse_all(&uris)).is_empty());
+        assert!(missing_batches(&parse_all(&[SINGLE.trim()])).is_empty());
+
+        // 同一批次重复提交不算作其他批次
+        let partial = parse_all(&[uris[1], uris[1], SINGLE.trim()]);
+        assert_eq!(missing_batches(&partial), [MissingBatches {
+            batch_id: 987654321,
+            batch_size: 2,
+            received: 1,
+            missing_indexes: vec![0],
+        }]);
+    }
+
+    #[test]
+    fn rejects_truncated_varints() {
+        // 字段值、字段键截断，以及超过 10 字节的 varint
+        let mut too_long = vec![0x10];
+        too_long.extend([0xFF; 10]);
+        for payload in [&[0x10, 0x80][..], &[0x80], &[0x10, 0xFF, 0xFF], &too_long] {
+            assert!(matches!(parse_payload(payload), Err(AppError::Totp(_))), "{:?}", payload);
+        }
+    }
+
+    #[test]
+    fn rejects_fields_past_the_buffer() {
+        // 长度超过剩余数据，以及加上当前位置会溢出的长度
+        let huge = [0x0A, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00];
+        for payload in [&[0x0A, 0x05, 0x01, 0x02][..], &huge, &[0x09, 0x01, 0x02]] {
+            assert!(matches!(parse_payload(payload), Err(AppError::Totp(_))), "{:?}", payload);
+        }
+
+        // 条目内部的字段越界只影响这个条目
+        let batch = parse_payload(&[0x0A, 0x04, 0x0A, 0x10, 0x01, 0x02, 0x18, 0x02]).unwrap();
+        assert_eq!(batch.batch_size, 2);
+        assert!(matches!(batch.entries.as_slice(), [Err(AppError::Totp(_))]));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::crypto::{KeySlot, SlotKind};
+use crate::secret::SecretString;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -6,7 +8,34 @@ pub struct User {
     pub username: String,
     pub password_hash: String,
     pub two_fa_enabled: bool,
-    pub two_fa_secret: Option<String>,
+    pub two_fa_secret: Option<SecretString>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OtpAlgorithm {
+    #[default]
+    SHA1,
+    SHA256,
+    SHA512,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OtpType {
+    #[default]
+    Totp,
+    Hotp,
+}
+
+pub const DEFAULT_DIGITS: usize = 6;
+pub const DEFAULT_PERIOD: u64 = 30;
+
+fn default_digits() -> usize {
+    DEFAULT_DIGITS
+}
+
+fn default_period() -> u64 {
+    DEFAULT_PERIOD
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,8 +43,20 @@ pub struct TotpEntry {
     pub id: String,
     pub name: String,
     pub issuer: String,
-    pub secret: String,
+    pub secret: SecretString,
     pub created_at: String,
+    // 旧版本数据文件没有以下字段，缺省时按 SHA1 / 6 位 / 30 秒处理
+    #[serde(default)]
+    pub algorithm: OtpAlgorithm,
+    #[serde(default = "default_digits")]
+    pub digits: usize,
+    #[serde(default = "default_period")]
+    pub period: u64,
+    #[serde(default)]
+    pub otp_type: OtpType,
+    // HOTP 计数器：下一次生成验证码时使用的值
+    #[serde(default)]
+    pub counter: u64,
 }
 
 impl TotpEntry {
@@ -24,14 +65,23 @@ impl TotpEntry {
             id: Uuid::new_v4().to_string(),
             name,
             issuer,
-            secret,
+            secret: secret.into(),
             created_at: chrono::Utc::now().to_rfc3339(),
+            algorithm: OtpAlgorithm::default(),
+            digits: DEFAULT_DIGITS,
+            period: DEFAULT_PERIOD,
+            otp_type: OtpType::default(),
+            counter: 0,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// AppData JSON 结构版本，每次修改结构都要在 backend/mod.rs 中添加对应的迁移步骤
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppData {
+    pub schema_version: u32,
     pub user: User,
     pub totp_entries: Vec<TotpEntry>,
 }
@@ -39,6 +89,7 @@ pub struct AppData {
 impl Default for AppData {
     fn default() -> Self {
         Self {
+            schema_version: SCHEMA_VERSION,
             user: User {
                 username: "admin".to_string(),
                 password_hash: hash_password("admin"),
@@ -74,7 +125,7 @@ pub fn verify_password(password: &str, hash_str: &str) -> bool {
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
     pub username: String,
-    pub password: String,
+    pub password: SecretString,
     pub totp_code: Option<String>,
 }
 
@@ -85,7 +136,22 @@ pub struct CheckUser2FARequest {
 
 #[derive(Debug, Deserialize)]
 pub struct UnlockRequest {
-    pub master_password: String,
+    pub master_password: SecretString,
+}
+
+// 新建数据库时 recovery_key 为生成的恢复密钥，只返回这一次
+#[derive(Debug, Serialize)]
+pub struct UnlockResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_key: Option<SecretString>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeMasterPasswordRequest {
+    pub old_master_password: SecretString,
+    pub new_master_password: SecretString,
 }
 
 #[derive(Debug, Serialize)]
@@ -97,15 +163,15 @@ pub struct LoginResponse {
 
 #[derive(Debug, Deserialize)]
 pub struct ChangePasswordRequest {
-    pub old_password: String,
-    pub new_password: String,
+    pub old_password: SecretString,
+    pub new_password: SecretString,
 }
 
 #[derive(Debug, Serialize)]
 pub struct TwoFaSetupResponse {
-    pub secret: String,
+    pub secret: SecretString,
     pub qr_code: String,
-    pub otpauth_url: String,
+    pub otpauth_url: SecretString,
 }
 
 #[derive(Debug, Deserialize)]
@@ -115,7 +181,7 @@ pub struct VerifyTwoFaRequest {
 
 #[derive(Debug, Deserialize)]
 pub struct DisableTwoFaRequest {
-    pub password: String,
+    pub password: SecretString,
     pub code: String,
 }
 
@@ -123,7 +189,292 @@ pub struct DisableTwoFaRequest {
 pub struct AddTotpRequest {
     pub name: String,
     pub issuer: String,
-    pub secret: String,
+    pub secret: SecretString,
+    #[serde(default)]
+    pub algorithm: OtpAlgorithm,
+    #[serde(default = "default_digits")]
+    pub digits: usize,
+    #[serde(default = "default_period")]
+    pub period: u64,
+    #[serde(default)]
+    pub otp_type: OtpType,
+    #[serde(default)]
+    pub counter: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResyncHotpRequest {
+    pub id: String,
+    pub code1: String,
+    pub code2: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportUriRequest {
+    pub uris: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResult {
+    pub index: usize,
+    pub success: bool,
+    pub duplicate: bool,
+    pub message: String,
+    // 只返回 ID、名称和发行方，不包含密钥
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<EntrySummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    pub imported: usize,
+    pub duplicates: usize,
+    pub failed: usize,
+    pub results: Vec<ImportResult>,
+    // Google Authenticator 多批次导出中还没有提交的批次，界面据此提示扫描剩余的二维码
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing_batches: Vec<MissingBatches>,
+}
+
+// 一次多批次导出（同一个 batch_id）中缺少的批次，序号从 0 开始
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct MissingBatches {
+    pub batch_id: i32,
+    pub batch_size: i32,
+    pub received: usize,
+    pub missing_indexes: Vec<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LockQuery {
+    #[serde(default)]
+    pub discard_unsaved: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScanQrQuery {
+    #[serde(default)]
+    pub commit: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanQrResponse {
+    pub committed: bool,
+    pub uris: Vec<String>,
+    pub result: ImportResponse,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QrImageFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportEntryRequest {
+    pub password: SecretString,
+    #[serde(default)]
+    pub format: QrImageFormat,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportEntryResponse {
+    pub otpauth_uri: SecretString,
+    pub qr_code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VaultExportRequest {
+    pub password: SecretString,
+    pub export_password: SecretString,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultImportMode {
+    #[default]
+    Merge,
+    Replace,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VaultImportRequest {
+    pub password: SecretString,
+    pub export_password: SecretString,
+    pub data: String,
+    #[serde(default)]
+    pub mode: VaultImportMode,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+// 其他验证器应用的备份格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BackupFormat {
+    #[serde(rename = "aegis")]
+    Aegis,
+    #[serde(rename = "2fas")]
+    TwoFas,
+    #[serde(rename = "andotp")]
+    AndOtp,
+    #[serde(rename = "freeotp")]
+    FreeOtpPlus,
+    #[serde(rename = "bitwarden")]
+    Bitwarden,
+    #[serde(rename = "keepass")]
+    KeePass,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackupImportRequest {
+    pub format: BackupFormat,
+    // 备份文件内容；二进制文件（加密的 andOTP 备份、KDBX 数据库）需 Base64 编码并设置 base64=true。
+    // 明文备份中包含全部密钥
+    pub data: SecretString,
+    #[serde(default)]
+    pub base64: bool,
+    #[serde(default)]
+    pub backup_password: Option<SecretString>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackupExportRequest {
+    pub password: SecretString,
+    pub format: BackupFormat,
+    // 为空时导出明文备份
+    #[serde(default)]
+    pub backup_password: Option<SecretString>,
+}
+
+// 后台保存状态，通过 /lock-status 返回
+#[derive(Debug, Serialize)]
+pub struct SaveStatus {
+    // 是否有尚未写入文件的修改
+    pub pending: bool,
+    pub last_saved_at: Option<String>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupInfo {
+    pub id: String,
+    pub created_at: String,
+    pub size: u64,
+}
+
+// 密钥槽列表（不含密钥材料），通过 GET /key-slots 返回
+#[derive(Debug, Serialize)]
+pub struct KeySlotInfo {
+    pub id: String,
+    pub kind: SlotKind,
+    // 密钥文件槽为文件路径
+    pub label: String,
+    pub created_at: String,
+}
+
+impl From<&KeySlot> for KeySlotInfo {
+    fn from(slot: &KeySlot) -> Self {
+        Self {
+            id: slot.id.clone(),
+            kind: slot.kind,
+            label: slot.label.clone(),
+            created_at: chrono::DateTime::from_timestamp(slot.created_at, 0)
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddPasswordSlotRequest {
+    pub master_password: SecretString,
+    pub new_password: SecretString,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddRecoveryKeySlotRequest {
+    pub master_password: SecretString,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddKeyFileSlotRequest {
+    pub master_password: SecretString,
+    // WEB_TOTP_KEY_FILE_DIR 中的文件名（或该目录中的完整路径）；文件不存在时生成新的密钥文件
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemoveKeySlotRequest {
+    pub master_password: SecretString,
+    pub id: String,
+}
+
+// recovery_key 只在添加恢复密钥槽时返回
+#[derive(Debug, Serialize)]
+pub struct KeySlotCreatedResponse {
+    pub success: bool,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_key: Option<SecretString>,
+}
+
+// 完整性检查的结果，通过 /integrity-check 和 check 子命令返回
+#[derive(Debug, Default, Serialize)]
+pub struct IntegrityReport {
+    pub ok: bool,
+    // 还没有保险库时为 false，此时不做其他检查
+    pub found: bool,
+    // 数据无法读取或解密的原因
+    pub vault_error: Option<String>,
+    // 迁移前的结构版本
+    pub schema_version: Option<u32>,
+    pub entries: Option<usize>,
+    pub issues: Vec<String>,
+    // 最新的、能用主密码解密且通过检查的备份
+    pub newest_valid_backup: Option<String>,
+    // 以下两项只在恢复时设置
+    pub recovered_from: Option<String>,
+    pub damaged_moved_to: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreBackupRequest {
+    pub id: String,
+    pub master_password: SecretString,
+    // 备份使用的主密码与当前不同（期间更换过主密码）时需要提供
+    #[serde(default)]
+    pub backup_password: Option<SecretString>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntrySummary {
+    pub id: String,
+    pub name: String,
+    pub issuer: String,
+}
+
+impl From<&TotpEntry> for EntrySummary {
+    fn from(entry: &TotpEntry) -> Self {
+        Self {
+            id: entry.id.clone(),
+            name: entry.name.clone(),
+            issuer: entry.issuer.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct VaultImportReport {
+    pub dry_run: bool,
+    pub mode: VaultImportMode,
+    pub added: Vec<EntrySummary>,
+    pub duplicates: Vec<EntrySummary>,
+    pub conflicts: Vec<EntrySummary>,
+    pub removed: usize,
 }
 
 #[derive(Debug, Deserialize)]
@@ -134,7 +485,14 @@ pub struct DeleteTotpRequest {
 #[derive(Debug, Serialize)]
 pub struct TotpCodeResponse {
     pub code: String,
-    pub remaining_seconds: u64,
+    pub otp_type: OtpType,
+    // TOTP 条目返回剩余秒数和周期，HOTP 条目返回本次使用的计数器
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counter: Option<u64>,
 }
 
 #[derive(Debug, Serialize)]
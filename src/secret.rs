@@ -0,0 +1,97 @@
+// 保存敏感字符串（TOTP 密钥、主密码等）的容器：
+// - 释放时清零内存
+// - Debug 输出不包含内容，避免通过日志或错误信息泄露
+// - 序列化为普通字符串，JSON 结构不变
+// 需要使用明文时调用 expose_secret()
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroize;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString([REDACTED])")
+    }
+}
+
+// 清零整个缓冲区（包括未使用的容量）
+impl Zeroize for SecretString {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_redacts_content() {
+        let secret = SecretString::from("JBSWY3DPEHPK3PXP");
+        assert_eq!(format!("{:?}", secret), "SecretString([REDACTED])");
+        assert_eq!(format!("{:?}", Some(secret)), "Some(SecretString([REDACTED]))");
+    }
+
+    #[test]
+    fn serializes_as_plain_string() {
+        let secret: SecretString = serde_json::from_str("\"abc\"").unwrap();
+        assert_eq!(secret.expose_secret(), "abc");
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"abc\"");
+    }
+
+    #[test]
+    fn request_models_do_not_leak_secrets_in_debug() {
+        let request: crate::models::VaultExportRequest = serde_json::from_str(
+            r#"{"password": "login-password", "export_password": "export-password"}"#,
+        ).unwrap();
+        let debug = format!("{:?}", request);
+        assert!(!debug.contains("login-password"));
+        assert!(!debug.contains("export-password"));
+    }
+
+    #[test]
+    fn zeroize_clears_whole_buffer() {
+        let mut secret = SecretString::from(String::with_capacity(64) + "JBSWY3DPEHPK3PXP");
+        let (ptr, capacity) = (secret.0.as_ptr(), secret.0.capacity());
+        secret.zeroize();
+        assert!(secret.is_empty());
+        // zeroize 不释放缓冲区，也不缩小容量；逐字节读取，不在未使用的容量上建立切片
+        assert_eq!(secret.0.capacity(), capacity);
+        assert_eq!(secret.0.as_ptr(), ptr);
+        for i in 0..capacity {
+            assert_eq!(unsafe { std::ptr::read_volatile(ptr.add(i)) }, 0, "byte {}", i);
+        }
+    }
+}
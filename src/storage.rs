@@ -1,234 +1,908 @@
-use crate::models::AppData;
+use crate::models::{AppData, BackupInfo, IntegrityReport, KeySlotInfo, SaveStatus};
+use crate::backend::{Decrypted, StorageBackend};
+use crate::integrity;
+use crate::keyfile;
+use crate::crypto::{self, KdfParams, KdfPolicy, MasterKey, SlotKind};
 use crate::error::{AppError, Result};
-use std::sync::Mutex;
-use tokio::fs;
-use aes_gcm::{
-    aead::{Aead, NewAead},
-    Aes256Gcm, Nonce, Key,
-};
-use rand::rngs::OsRng;
-use log::{info, warn, debug};
-
-const NONCE_SIZE: usize = 12;
-const SALT_SIZE: usize = 16;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, RwLock};
+use zeroize::Zeroizing;
+use log::{info, warn, error};
 
-pub struct Storage {
-    file_path: String,
-    data: Mutex<Option<AppData>>,
-    master_password_hash: Mutex<Option<String>>,
+// 解锁后的状态：明文数据和数据密钥（含密钥槽）总是一起存在、一起清除。
+// 保存时直接使用该密钥，不保留主密码
+struct Unlocked {
+    data: AppData,
+    key: MasterKey,
+}
+
+// 后台保存失败后的重试间隔
+const SAVE_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// Argon2 派生、KDF 基准测试和后端的 IO 放到阻塞线程池中运行，避免占住 actix 工作线程；
+// api.rs 中导入导出文件的加解密也使用它
+pub async fn blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await
+        .map_err(|e| AppError::Storage(format!("Background task failed: {}", e)))?
 }
 
-// 从主密码派生加密密钥
-fn derive_key(password: &str, salt: &[u8]) -> Result<[u8; 32]> {
-    use argon2::Argon2;
-    
-    let mut key = [0u8; 32];
-    Argon2::default()
-        .hash_password_into(password.as_bytes(), salt, &mut key)
-        .map_err(|e| AppError::Encryption(format!("Key derivation failed: {}", e)))?;
-    
-    Ok(key)
+pub struct Storage {
+    backend: Arc<dyn StorageBackend>,
+    kdf_policy: KdfPolicy,
+    // 密钥文件槽的文件只能位于这个目录中
+    key_file_dir: String,
+    // 读操作可以并发；修改内存数据时持有写锁，但不会跨越文件 IO 或密钥派生
+    state: RwLock<Option<Unlocked>>,
+    // 串行化所有写入后端的操作（保存、解锁时升级、更换主密码、恢复备份、锁定）
+    write_lock: tokio::sync::Mutex<()>,
+    // 空闲超过该时长自动锁定，None 表示不自动锁定
+    idle_timeout: Option<Duration>,
+    // 只在同步代码中短暂持有
+    last_activity: Mutex<Instant>,
+    // 后台保存：修改后调用 schedule_save() 标记并唤醒后台任务，
+    // 后台任务等待 save_debounce 合并这段时间内的修改后再写文件
+    dirty: AtomicBool,
+    save_requested: Notify,
+    save_debounce: Duration,
+    last_saved_at: Mutex<Option<chrono::DateTime<chrono::Utc>>>,
+    last_save_error: Mutex<Option<String>>,
 }
 
 impl Storage {
-    pub async fn new(file_path: &str) -> Result<Self> {
-        info!("Initializing storage at: {}", file_path);
-        
-        // 检查文件是否存在
-        let file_exists = fs::metadata(file_path).await.is_ok();
+    pub fn new(
+        backend: Box<dyn StorageBackend>,
+        kdf_policy: KdfPolicy,
+        key_file_dir: String,
+        idle_timeout: Option<Duration>,
+        save_debounce: Duration,
+    ) -> Self {
+        info!("Initializing storage: {}", backend.describe());
         
-        if !file_exists {
-            info!("Data file not found, will create new one");
+        Self {
+            backend: Arc::from(backend),
+            kdf_policy,
+            key_file_dir,
+            state: RwLock::new(None),
+            write_lock: tokio::sync::Mutex::new(()),
+            idle_timeout,
+            last_activity: Mutex::new(Instant::now()),
+            dirty: AtomicBool::new(false),
+            save_requested: Notify::new(),
+            save_debounce,
+            last_saved_at: Mutex::new(None),
+            last_save_error: Mutex::new(None),
         }
-        
-        Ok(Self {
-            file_path: file_path.to_string(),
-            data: Mutex::new(None),
-            master_password_hash: Mutex::new(None),
-        })
     }
 
-    // 解锁数据库（使用主密码）
-    pub async fn unlock(&self, master_password: &str) -> Result<bool> {
+    // 解锁数据库：master_password 可以是任一密钥槽的秘密（主密码、恢复密钥或密钥文件内容）。
+    // 还没有数据时用它作为主密码创建新数据库，同时生成恢复密钥并返回（只返回这一次）
+    pub async fn unlock(&self, master_password: &str) -> Result<Option<Zeroizing<String>>> {
         info!("Attempting to unlock database");
+        let _write_guard = self.write_lock.lock().await;
+        let password = Zeroizing::new(master_password.to_string());
         
-        match fs::read(&self.file_path).await {
-            Ok(encrypted_data) => {
-                // 文件存在，尝试解密
-                match Self::decrypt_data(&encrypted_data, master_password) {
-                    Ok(data) => {
-                        info!("Database unlocked successfully");
-                        *self.data.lock().unwrap() = Some(data);
-                        *self.master_password_hash.lock().unwrap() = Some(master_password.to_string());
-                        Ok(true)
-                    }
-                    Err(e) => {
-                        warn!("Failed to unlock database: {}", e);
-                        Err(AppError::InvalidMasterPassword)
-                    }
+        let backend = self.backend.clone();
+        let load_password = password.clone();
+        match blocking(move || backend.load(&load_password)).await {
+            Ok(Some(decrypted)) => {
+                info!("Database unlocked successfully");
+                let mut key = decrypted.key;
+                let mut rewrite = decrypted.needs_upgrade;
+                
+                // 解开的密钥槽 KDF 参数低于当前策略时自动升级（同时更换盐值，数据密钥不变）
+                let slot_params = key.slot(&decrypted.slot_id).map(|slot| slot.kdf_params);
+                if let Some(params) = slot_params.filter(|p| !self.kdf_policy.is_satisfied_by(p)) {
+                    let stronger = self.select_kdf_params().await?;
+                    info!("KDF parameters {:?} of key slot {} are below policy, re-wrapping with {:?}",
+                        params, decrypted.slot_id, stronger);
+                    let slot_id = decrypted.slot_id.clone();
+                    key = blocking(move || {
+                        key.rewrap_slot(&slot_id, &password, stronger)?;
+                        Ok(key)
+                    }).await?;
+                    rewrite = true;
                 }
+                if rewrite {
+                    self.upgrade(&decrypted.data, &key).await?;
+                }
+                
+                // 先重置空闲计时，避免自动锁定任务看到旧的活动时间
+                self.touch();
+                *self.state.write().await = Some(Unlocked { data: decrypted.data, key });
+                Ok(None)
             }
-            Err(_) => {
-                // 文件不存在，创建新数据库
+            Ok(None) => {
+                // 还没有数据，创建新数据库：随机数据密钥由主密码槽和恢复密钥槽包装，
+                // KDF 参数按策略选择（首次运行时做基准测试）
                 info!("Creating new database with master password");
+                let kdf_params = self.select_kdf_params().await?;
+                info!("Using KDF parameters {:?}", kdf_params);
+                let recovery_key = crypto::generate_recovery_key();
+                let slot_secret = recovery_key.clone();
+                let key = blocking(move || {
+                    let mut key = MasterKey::generate();
+                    key.add_slot(SlotKind::Password, "", &password, kdf_params)?;
+                    key.add_slot(SlotKind::RecoveryKey, "", &slot_secret, kdf_params)?;
+                    Ok(key)
+                }).await?;
                 let default_data = AppData::default();
-                self.save_with_password(&default_data, master_password).await?;
-                *self.data.lock().unwrap() = Some(default_data);
-                *self.master_password_hash.lock().unwrap() = Some(master_password.to_string());
-                Ok(true)
+                self.store(&default_data, &key).await?;
+                self.touch();
+                *self.state.write().await = Some(Unlocked { data: default_data, key });
+                Ok(Some(recovery_key))
+            }
+            Err(AppError::InvalidMasterPassword) => {
+                warn!("Failed to unlock database: invalid master password");
+                Err(AppError::InvalidMasterPassword)
+            }
+            Err(e) => {
+                // 数据被截断、文件头无效、部分数据认证失败，或格式、结构版本不受支持，
+                // 密码本身可能是正确的（可以用完整性检查确认并从备份恢复）
+                error!("Failed to load database: {}", e);
+                Err(e)
             }
         }
     }
 
+    // 新建数据文件、重新包装密钥槽和导出文件时使用的参数
+    pub async fn select_kdf_params(&self) -> Result<crypto::KdfParams> {
+        let policy = self.kdf_policy.clone();
+        blocking(move || policy.select_params()).await
+    }
+
+    // 是否已经有数据（不需要解锁）
+    pub async fn has_vault(&self) -> Result<bool> {
+        let backend = self.backend.clone();
+        blocking(move || backend.has_vault()).await
+    }
+
     // 检查是否已解锁
-    pub fn is_unlocked(&self) -> bool {
-        self.data.lock().unwrap().is_some()
+    pub async fn is_unlocked(&self) -> bool {
+        self.state.read().await.is_some()
     }
 
-    async fn save_with_password(
-        &self,
-        data: &AppData,
-        password: &str,
-    ) -> Result<()> {
-        debug!("Encrypting and saving data");
-        let encrypted_data = Self::encrypt_data(data, password)?;
-        fs::write(&self.file_path, encrypted_data).await
-            .map_err(|e| AppError::Storage(format!("Failed to write file: {}", e)))?;
-        info!("Data saved successfully");
+    // 锁定数据库：先写入尚未保存的修改，再清除内存中的数据和密钥，之后需要重新解锁。
+    // 写入失败时不锁定并返回错误，避免丢失修改；discard_unsaved 时放弃尚未保存的修改
+    // （例如数据已被其他程序修改，需要重新解锁以读取新数据）
+    pub async fn lock(&self, discard_unsaved: bool) -> Result<()> {
+        let _write_guard = self.write_lock.lock().await;
+        if discard_unsaved && self.dirty.swap(false, Ordering::SeqCst) {
+            warn!("Discarding unsaved changes");
+            *self.last_save_error.lock().unwrap() = None;
+        }
+        self.write_pending().await?;
+        *self.state.write().await = None;
+        info!("Database locked");
         Ok(())
     }
 
-    fn encrypt_data(data: &AppData, password: &str) -> Result<Vec<u8>> {
-        // 生成随机盐值
-        let mut salt_bytes = [0u8; SALT_SIZE];
-        rand::RngCore::fill_bytes(&mut OsRng, &mut salt_bytes);
-        
-        // 从密码派生密钥
-        let key = derive_key(password, &salt_bytes)?;
-        
-        // 生成随机 nonce
-        let nonce_bytes: [u8; NONCE_SIZE] = rand::random();
-        let nonce = Nonce::from_slice(&nonce_bytes);
-        
-        // 序列化数据
-        let json = serde_json::to_string(data)
-            .map_err(|e| AppError::Storage(format!("Serialization failed: {}", e)))?;
-        
-        // 使用 AES-256-GCM 加密
-        let cipher = Aes256Gcm::new(Key::from_slice(&key));
-        let ciphertext = cipher
-            .encrypt(nonce, json.as_bytes())
-            .map_err(|e| AppError::Encryption(format!("Encryption failed: {}", e)))?;
-        
-        // 格式: salt (16 bytes) + nonce (12 bytes) + ciphertext
-        let mut result = Vec::new();
-        result.extend_from_slice(&salt_bytes);
-        result.extend_from_slice(&nonce_bytes);
-        result.extend_from_slice(&ciphertext);
-        
-        debug!("Data encrypted successfully");
-        Ok(result)
+    // 记录一次用户活动，重新开始空闲计时
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
     }
 
-    fn decrypt_data(encrypted_data: &[u8], password: &str) -> Result<AppData> {
-        if encrypted_data.len() < SALT_SIZE + NONCE_SIZE {
-            return Err(AppError::Encryption("Invalid encrypted data format".to_string()));
+    // 距离自动锁定的剩余时间；未解锁或未启用自动锁定时为 None
+    pub async fn auto_lock_remaining(&self) -> Option<Duration> {
+        let idle_timeout = self.idle_timeout?;
+        if !self.is_unlocked().await {
+            return None;
+        }
+        let idle = self.last_activity.lock().unwrap().elapsed();
+        Some(idle_timeout.saturating_sub(idle))
+    }
+
+    // 后台任务：每秒检查一次，空闲超时后锁定数据库
+    pub async fn run_auto_lock(&self) {
+        if self.idle_timeout.is_none() {
+            return;
+        }
+        let mut ticker = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            ticker.tick().await;
+            if self.auto_lock_remaining().await == Some(Duration::ZERO) {
+                info!("Database idle for too long, locking");
+                if let Err(e) = self.lock(false).await {
+                    error!("Auto-lock postponed, pending changes could not be saved: {}", e);
+                }
+            }
+        }
+    }
+
+    // 把数据和密钥交给后端写入（在阻塞线程池中加密和写入）
+    async fn store(&self, data: &AppData, key: &MasterKey) -> Result<()> {
+        let backend = self.backend.clone();
+        let data = data.clone();
+        let key = key.clone();
+        blocking(move || backend.store(&data, &key)).await
+    }
+
+    // 旧格式、旧结构或 KDF 参数过弱的数据在解锁后立即按当前格式重写，后端先保留一份原始内容
+    async fn upgrade(&self, data: &AppData, key: &MasterKey) -> Result<()> {
+        let backend = self.backend.clone();
+        blocking(move || backend.preserve_before_upgrade()).await?;
+        self.store(data, key).await
+    }
+
+    // 立即保存（包括之前排队的修改），用于返回前必须落盘的操作，例如 HOTP 计数器
+    pub async fn save(&self) -> Result<()> {
+        self.dirty.store(true, Ordering::SeqCst);
+        self.flush().await
+    }
+
+    // 标记有未保存的修改，由后台任务合并后保存
+    pub fn schedule_save(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+        self.save_requested.notify_one();
+    }
+
+    // 写入所有尚未保存的修改；没有待保存的修改时什么都不做
+    pub async fn flush(&self) -> Result<()> {
+        let _write_guard = self.write_lock.lock().await;
+        self.write_pending().await
+    }
+
+    // 调用方需持有 write_lock
+    async fn write_pending(&self) -> Result<()> {
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
         }
         
-        // 提取 salt, nonce 和 ciphertext
-        let salt = &encrypted_data[..SALT_SIZE];
-        let nonce_bytes = &encrypted_data[SALT_SIZE..SALT_SIZE + NONCE_SIZE];
-        let ciphertext = &encrypted_data[SALT_SIZE + NONCE_SIZE..];
-        
-        // 从密码派生密钥
-        let key = derive_key(password, salt)?;
+        let result = self.write_current().await;
+        if let Err(ref e) = result {
+            // 数据库已锁定时没有可写的数据，其他错误保留标记以便重试
+            if !matches!(e, AppError::DatabaseLocked) {
+                self.dirty.store(true, Ordering::SeqCst);
+            }
+        }
+        self.record_save_result(&result);
+        result
+    }
+
+    async fn write_current(&self) -> Result<()> {
+        // 复制数据时只持有读锁，写入时不再持有
+        let (data, key) = {
+            let state = self.state.read().await;
+            let unlocked = state.as_ref().ok_or(AppError::DatabaseLocked)?;
+            (unlocked.data.clone(), unlocked.key.clone())
+        };
         
-        // 解密
-        let cipher = Aes256Gcm::new(Key::from_slice(&key));
-        let nonce = Nonce::from_slice(nonce_bytes);
-        let plaintext = cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|_| AppError::InvalidMasterPassword)?;
+        self.rotate_backup(false).await;
+        self.store(&data, &key).await
+    }
+
+    fn record_save_result(&self, result: &Result<()>) {
+        match result {
+            Ok(()) => {
+                *self.last_saved_at.lock().unwrap() = Some(chrono::Utc::now());
+                *self.last_save_error.lock().unwrap() = None;
+            }
+            Err(e) => *self.last_save_error.lock().unwrap() = Some(e.to_string()),
+        }
+    }
+
+    pub fn save_status(&self) -> SaveStatus {
+        SaveStatus {
+            pending: self.dirty.load(Ordering::SeqCst),
+            last_saved_at: self.last_saved_at.lock().unwrap().map(|t| t.to_rfc3339()),
+            last_error: self.last_save_error.lock().unwrap().clone(),
+        }
+    }
+
+    // 后台任务：收到保存请求后等待 save_debounce，把这段时间内的修改合并为一次写入。
+    // 失败时稍后重试，错误通过 save_status() 报告
+    pub async fn run_writer(&self) {
+        loop {
+            self.save_requested.notified().await;
+            tokio::time::sleep(self.save_debounce).await;
+            if let Err(e) = self.flush().await {
+                error!("Background save failed: {}", e);
+                if self.dirty.load(Ordering::SeqCst) {
+                    tokio::time::sleep(SAVE_RETRY_DELAY).await;
+                    self.save_requested.notify_one();
+                }
+            }
+        }
+    }
+
+    // 当前密钥的副本；数据库已锁定时返回 DatabaseLocked
+    async fn current_key(&self) -> Result<MasterKey> {
+        self.state.read().await
+            .as_ref()
+            .map(|unlocked| unlocked.key.clone())
+            .ok_or(AppError::DatabaseLocked)
+    }
+
+    // 确认用户输入的主密码（或其他密钥槽的秘密）能解开当前的数据密钥，返回对应的槽 ID
+    async fn check_master_password(key: &MasterKey, password: &str) -> Result<String> {
+        let key = key.clone();
+        let password = Zeroizing::new(password.to_string());
+        if let Some(slot_id) = blocking(move || key.find_slot(&password)).await? {
+            Ok(slot_id)
+        } else {
+            Err(AppError::InvalidMasterPassword)
+        }
+    }
+
+    // 覆盖当前数据之前保留一份备份；备份失败只记录日志，不阻止保存。
+    // 普通保存按备份间隔，force 用于恢复备份这类丢弃当前数据的操作
+    async fn rotate_backup(&self, force: bool) {
+        let backend = self.backend.clone();
+        if let Err(e) = blocking(move || backend.create_backup(force)).await {
+            warn!("Failed to back up data before saving: {}", e);
+        }
+    }
+
+    pub async fn list_backups(&self) -> Result<Vec<BackupInfo>> {
+        let backend = self.backend.clone();
+        blocking(move || backend.list_backups()).await
+    }
+
+    // 用备份替换当前数据：备份按 backup_password 解密，再用当前主密码重新加密保存，
+    // 替换前的数据同样会留下一份备份。备份不存在时返回 false
+    pub async fn restore_backup(
+        &self,
+        id: &str,
+        master_password: &str,
+        backup_password: Option<&str>,
+    ) -> Result<bool> {
+        let _write_guard = self.write_lock.lock().await;
+        let key = self.current_key().await?;
+        Self::check_master_password(&key, master_password).await?;
         
-        // 反序列化
-        let json = String::from_utf8(plaintext)
-            .map_err(|e| AppError::Encryption(format!("UTF-8 decode failed: {}", e)))?;
-        let data: AppData = serde_json::from_str(&json)
-            .map_err(|e| AppError::Storage(format!("Deserialization failed: {}", e)))?;
+        let backend = self.backend.clone();
+        let backup_id = id.to_string();
+        let password = Zeroizing::new(backup_password.unwrap_or(master_password).to_string());
+        let Some(restored) = blocking(move || backend.load_backup(&backup_id, &password)).await? else {
+            return Ok(false);
+        };
+        let restored = restored.data;
         
-        debug!("Data decrypted successfully");
-        Ok(data)
+        // 恢复会丢弃当前数据，包括尚未保存的修改（写入前已留下一份备份）
+        self.write_pending().await?;
+        self.rotate_backup(true).await;
+        self.store(&restored, &key).await?;
+        self.record_save_result(&Ok(()));
+        if let Some(unlocked) = self.state.write().await.as_mut() {
+            unlocked.data = restored;
+        }
+        info!("Restored data from backup {}", id);
+        Ok(true)
     }
 
-    pub async fn save(&self) -> Result<()> {
-        let data_lock = self.data.lock().unwrap();
-        let password_lock = self.master_password_hash.lock().unwrap();
-        
-        match (data_lock.as_ref(), password_lock.as_ref()) {
-            (Some(data), Some(password)) => {
-                self.save_with_password(data, password).await
+    // 完整性检查：只读取数据，不解锁也不修改。
+    // 解密失败时无法区分主密码错误和数据被篡改，此时能用该密码解密任一备份才认为是数据损坏，
+    // 否则返回 InvalidMasterPassword
+    pub async fn check_integrity(&self, master_password: &str) -> Result<IntegrityReport> {
+        let _write_guard = self.write_lock.lock().await;
+        Ok(self.inspect(master_password).await?.0)
+    }
+
+    // 从最新的有效备份恢复损坏的数据：损坏的数据移到一边（不删除），备份按原来的密钥写回。
+    // 只能在锁定状态下进行；数据通过检查或没有有效备份时不做任何修改，recovered_from 为 None
+    pub async fn recover(&self, master_password: &str) -> Result<IntegrityReport> {
+        let _write_guard = self.write_lock.lock().await;
+        if self.is_unlocked().await {
+            return Err(AppError::Storage("Lock the database before recovering from a backup".to_string()));
+        }
+
+        let (mut report, backup) = self.inspect(master_password).await?;
+        let Some((id, decrypted)) = backup.filter(|_| !report.ok) else {
+            return Ok(report);
+        };
+
+        let backend = self.backend.clone();
+        report.damaged_moved_to = blocking(move || backend.quarantine()).await?;
+        self.store(&decrypted.data, &decrypted.key).await?;
+        self.record_save_result(&Ok(()));
+        warn!("Recovered data from backup {}", id);
+        report.recovered_from = Some(id);
+        Ok(report)
+    }
+
+    // 检查当前数据；有问题时同时返回最新的有效备份（已解密）。
+    // 没有密钥槽能用该密码解开时直接返回 InvalidMasterPassword，不逐个尝试备份
+    // （每个备份都要运行一次 Argon2，错误的密码不能触发这么多计算）
+    async fn inspect(&self, master_password: &str) -> Result<(IntegrityReport, Option<(String, Decrypted)>)> {
+        let backend = self.backend.clone();
+        let password = Zeroizing::new(master_password.to_string());
+        let mut report = IntegrityReport::default();
+        match blocking(move || backend.inspect(&password)).await {
+            Ok(Some(decrypted)) => {
+                report.found = true;
+                report.schema_version = Some(decrypted.schema_version);
+                report.entries = Some(decrypted.data.totp_entries.len());
+                report.issues = integrity::check(&decrypted.data);
+            }
+            Ok(None) => {}
+            Err(AppError::InvalidMasterPassword) => return Err(AppError::InvalidMasterPassword),
+            Err(e) => {
+                report.found = true;
+                report.vault_error = Some(e.to_string());
+            }
+        }
+        report.ok = report.vault_error.is_none() && report.issues.is_empty();
+        if report.ok {
+            return Ok((report, None));
+        }
+
+        let backup = self.newest_valid_backup(master_password).await?;
+        report.newest_valid_backup = backup.as_ref().map(|(id, _)| id.clone());
+        Ok((report, backup))
+    }
+
+    // 从新到旧尝试备份，返回第一个能用该密码解密且通过检查的备份
+    async fn newest_valid_backup(&self, password: &str) -> Result<Option<(String, Decrypted)>> {
+        let backend = self.backend.clone();
+        let password = Zeroizing::new(password.to_string());
+        blocking(move || {
+            for backup in backend.list_backups()? {
+                match backend.load_backup(&backup.id, &password) {
+                    Ok(Some(decrypted)) if integrity::check(&decrypted.data).is_empty() => {
+                        return Ok(Some((backup.id, decrypted)));
+                    }
+                    Ok(Some(_)) => warn!("Backup {} failed the integrity check", backup.id),
+                    Ok(None) | Err(AppError::InvalidMasterPassword) => {}
+                    Err(e) => warn!("Backup {} is not usable: {}", backup.id, e),
+                }
+            }
+            Ok(None)
+        }).await
+    }
+
+    // 更换主密码：只重新包装数据密钥，数据本身不需要重新加密。
+    // 用主密码确认时替换该密码槽；用恢复密钥或密钥文件确认时替换所有密码槽（即重设主密码）
+    pub async fn change_master_password(&self, old_password: &str, new_password: &str) -> Result<()> {
+        let password = Zeroizing::new(new_password.to_string());
+        self.update_key_slots(old_password, move |key, slot_id, params| {
+            if key.slot(slot_id).is_some_and(|slot| slot.kind == SlotKind::Password) {
+                key.rewrap_slot(slot_id, &password, params)
+            } else {
+                key.slots.retain(|slot| slot.kind != SlotKind::Password);
+                key.add_slot(SlotKind::Password, "", &password, params).map(|_| ())
             }
-            _ => Err(AppError::DatabaseLocked),
+        }).await?;
+        info!("Master password changed");
+        Ok(())
+    }
+
+    pub async fn list_key_slots(&self) -> Result<Vec<KeySlotInfo>> {
+        let key = self.current_key().await?;
+        Ok(key.slots.iter().map(KeySlotInfo::from).collect())
+    }
+
+    // 添加一个额外的主密码，返回槽 ID
+    pub async fn add_password_slot(&self, master_password: &str, new_password: &str) -> Result<String> {
+        let password = Zeroizing::new(new_password.to_string());
+        let id = self.update_key_slots(master_password, move |key, _, params| {
+            key.add_slot(SlotKind::Password, "", &password, params)
+        }).await?;
+        info!("Added password key slot {}", id);
+        Ok(id)
+    }
+
+    // 生成新的恢复密钥，返回槽 ID 和恢复密钥（只返回这一次）
+    pub async fn add_recovery_key_slot(&self, master_password: &str) -> Result<(String, Zeroizing<String>)> {
+        let recovery_key = crypto::generate_recovery_key();
+        let slot_secret = recovery_key.clone();
+        let id = self.update_key_slots(master_password, move |key, _, params| {
+            key.add_slot(SlotKind::RecoveryKey, "", &slot_secret, params)
+        }).await?;
+        info!("Added recovery key slot {}", id);
+        Ok((id, recovery_key))
+    }
+
+    // 用密钥文件的内容添加密钥槽；文件不存在时生成新的密钥文件。
+    // path 必须位于 key_file_dir 中（相对路径相对于该目录），槽的说明为完整路径。返回槽 ID
+    pub async fn add_key_file_slot(&self, master_password: &str, path: &str) -> Result<String> {
+        let path = keyfile::resolve_in_dir(&self.key_file_dir, path)?;
+        let id = self.update_key_slots(master_password, move |key, _, params| {
+            let secret = keyfile::read_or_create(&path)?;
+            key.add_slot(SlotKind::KeyFile, &path, &secret, params)
+        }).await?;
+        info!("Added key file slot {}", id);
+        Ok(id)
+    }
+
+    // 槽不存在时返回 false；不能删除最后一个槽
+    pub async fn remove_key_slot(&self, master_password: &str, id: &str) -> Result<bool> {
+        if self.current_key().await?.slot(id).is_none() {
+            return Ok(false);
+        }
+        let slot_id = id.to_string();
+        let removed = self.update_key_slots(master_password, move |key, _, _| {
+            if key.slot(&slot_id).is_some() && key.slots.len() == 1 {
+                return Err(AppError::LastKeySlot);
+            }
+            Ok(key.remove_slot(&slot_id))
+        }).await?;
+        if removed {
+            info!("Removed key slot {}", id);
+        }
+        Ok(removed)
+    }
+
+    // 修改密钥槽：确认 master_password 后在阻塞线程池中调用 f（可能运行 Argon2），
+    // 再把当前数据连同新的密钥槽写入，成功后才替换内存中的密钥。
+    // f 的参数为确认时解开的槽 ID 和新槽使用的 KDF 参数（该槽的参数，低于策略时按策略重新选择）
+    async fn update_key_slots<F, R>(&self, master_password: &str, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut MasterKey, &str, KdfParams) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let _write_guard = self.write_lock.lock().await;
+        
+        let mut key = self.current_key().await?;
+        let slot_id = Self::check_master_password(&key, master_password).await?;
+        let params = match key.slot(&slot_id).map(|slot| slot.kdf_params) {
+            Some(params) if self.kdf_policy.is_satisfied_by(&params) => params,
+            _ => self.select_kdf_params().await?,
+        };
+        let (key, output) = blocking(move || {
+            let output = f(&mut key, &slot_id, params)?;
+            Ok((key, output))
+        }).await?;
+        
+        // 写入的是当前的全部数据，排队中的修改一并写入
+        let data = {
+            let state = self.state.read().await;
+            let unlocked = state.as_ref().ok_or(AppError::DatabaseLocked)?;
+            self.dirty.store(false, Ordering::SeqCst);
+            unlocked.data.clone()
+        };
+        
+        self.rotate_backup(false).await;
+        let result = self.store(&data, &key).await;
+        if result.is_err() {
+            self.dirty.store(true, Ordering::SeqCst);
+        }
+        self.record_save_result(&result);
+        result?;
+        if let Some(unlocked) = self.state.write().await.as_mut() {
+            unlocked.key = key;
         }
+        Ok(output)
     }
 
-    pub fn get_user(&self) -> Result<crate::models::User> {
-        let data = self.data.lock().unwrap();
-        data.as_ref()
-            .map(|d| d.user.clone())
+    pub async fn get_user(&self) -> Result<crate::models::User> {
+        let state = self.state.read().await;
+        state.as_ref()
+            .map(|u| u.data.user.clone())
             .ok_or(AppError::DatabaseLocked)
     }
 
-    pub fn update_user<F>(&self, f: F) -> Result<()>
+    pub async fn update_user<F>(&self, f: F) -> Result<()>
     where
         F: FnOnce(&mut crate::models::User),
     {
-        let mut data = self.data.lock().unwrap();
-        match data.as_mut() {
-            Some(d) => {
-                f(&mut d.user);
+        let mut state = self.state.write().await;
+        match state.as_mut() {
+            Some(u) => {
+                f(&mut u.data.user);
                 Ok(())
             }
             None => Err(AppError::DatabaseLocked),
         }
     }
 
-    pub fn get_totp_entries(&self) -> Result<Vec<crate::models::TotpEntry>> {
-        let data = self.data.lock().unwrap();
-        data.as_ref()
-            .map(|d| d.totp_entries.clone())
+    pub async fn get_totp_entries(&self) -> Result<Vec<crate::models::TotpEntry>> {
+        let state = self.state.read().await;
+        state.as_ref()
+            .map(|u| u.data.totp_entries.clone())
             .ok_or(AppError::DatabaseLocked)
     }
 
-    pub fn add_totp_entry(&self, entry: crate::models::TotpEntry) -> Result<()> {
-        let mut data = self.data.lock().unwrap();
-        match data.as_mut() {
-            Some(d) => {
-                d.totp_entries.push(entry);
+    pub async fn add_totp_entry(&self, entry: crate::models::TotpEntry) -> Result<()> {
+        let mut state = self.state.write().await;
+        match state.as_mut() {
+            Some(u) => {
+                u.data.totp_entries.push(entry);
                 Ok(())
             }
             None => Err(AppError::DatabaseLocked),
         }
     }
 
-    pub fn delete_totp_entry(&self, id: &str) -> Result<bool> {
-        let mut data = self.data.lock().unwrap();
-        match data.as_mut() {
-            Some(d) => {
-                let len_before = d.totp_entries.len();
-                d.totp_entries.retain(|e| e.id != id);
-                Ok(d.totp_entries.len() < len_before)
+    pub async fn replace_totp_entries(&self, entries: Vec<crate::models::TotpEntry>) -> Result<()> {
+        let mut state = self.state.write().await;
+        match state.as_mut() {
+            Some(u) => {
+                u.data.totp_entries = entries;
+                Ok(())
             }
             None => Err(AppError::DatabaseLocked),
         }
     }
 
-    pub fn get_totp_entry(&self, id: &str) -> Result<Option<crate::models::TotpEntry>> {
-        let data = self.data.lock().unwrap();
-        match data.as_ref() {
-            Some(d) => Ok(d.totp_entries.iter().find(|e| e.id == id).cloned()),
+    pub async fn delete_totp_entry(&self, id: &str) -> Result<bool> {
+        let mut state = self.state.write().await;
+        match state.as_mut() {
+            Some(u) => {
+                let len_before = u.data.totp_entries.len();
+                u.data.totp_entries.retain(|e| e.id != id);
+                Ok(u.data.totp_entries.len() < len_before)
+            }
+            None => Err(AppError::DatabaseLocked),
+        }
+    }
+
+    pub async fn update_totp_entry<F, R>(&self, id: &str, f: F) -> Result<Option<R>>
+    where
+        F: FnOnce(&mut crate::models::TotpEntry) -> R,
+    {
+        let mut state = self.state.write().await;
+        match state.as_mut() {
+            Some(u) => Ok(u.data.totp_entries.iter_mut().find(|e| e.id == id).map(f)),
+            None => Err(AppError::DatabaseLocked),
+        }
+    }
+
+    pub async fn get_totp_entry(&self, id: &str) -> Result<Option<crate::models::TotpEntry>> {
+        let state = self.state.read().await;
+        match state.as_ref() {
+            Some(u) => Ok(u.data.totp_entries.iter().find(|e| e.id == id).cloned()),
             None => Err(AppError::DatabaseLocked),
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{
+        memory_storage, memory_storage_with_key_file_dir, sample_entry, test_policy, TempDir, EVERY_SAVE, NO_BACKUPS,
+    };
+
+    fn names(entries: &[crate::models::TotpEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    // 锁定后重新解锁，读取后端中保存的内容
+    async fn reload(storage: &Storage, password: &str) -> Vec<crate::models::TotpEntry> {
+        storage.lock(false).await.unwrap();
+        storage.unlock(password).await.unwrap();
+        storage.get_totp_entries().await.unwrap()
+    }
+
+    #[actix_web::test]
+    async fn unlock_creates_vault_and_checks_password() {
+        let storage = memory_storage(NO_BACKUPS);
+        assert!(!storage.has_vault().await.unwrap());
+        let recovery_key = storage.unlock("pw").await.unwrap().expect("new vault returns a recovery key");
+        assert!(storage.has_vault().await.unwrap());
+        assert!(storage.is_unlocked().await);
+
+        storage.lock(false).await.unwrap();
+        assert!(!storage.is_unlocked().await);
+        assert!(matches!(storage.get_totp_entries().await, Err(AppError::DatabaseLocked)));
+        assert!(matches!(storage.unlock("wrong").await, Err(AppError::InvalidMasterPassword)));
+        assert!(!storage.is_unlocked().await);
+
+        assert!(storage.unlock("pw").await.unwrap().is_none());
+        storage.lock(false).await.unwrap();
+        assert!(storage.unlock(&recovery_key).await.unwrap().is_none());
+    }
+
+    #[actix_web::test]
+    async fn changes_survive_save_and_reload() {
+        let storage = memory_storage(NO_BACKUPS);
+        storage.unlock("pw").await.unwrap();
+        let alice = sample_entry("alice");
+        let bob = sample_entry("bob");
+        let (alice_id, bob_id) = (alice.id.clone(), bob.id.clone());
+        storage.add_totp_entry(alice).await.unwrap();
+        storage.add_totp_entry(bob).await.unwrap();
+        storage.add_totp_entry(sample_entry("carol")).await.unwrap();
+        storage.save().await.unwrap();
+
+        assert_eq!(storage.update_totp_entry(&alice_id, |e| e.counter = 7).await.unwrap(), Some(()));
+        assert!(storage.delete_totp_entry(&bob_id).await.unwrap());
+        assert!(!storage.delete_totp_entry(&bob_id).await.unwrap());
+        storage.save().await.unwrap();
+        assert!(!storage.save_status().pending);
+
+        let entries = reload(&storage, "pw").await;
+        assert_eq!(names(&entries), ["alice", "carol"]);
+        assert_eq!(entries[0].counter, 7);
+    }
+
+    // 锁定时默认写入尚未保存的修改，discard_unsaved 时丢弃
+    #[actix_web::test]
+    async fn lock_saves_or_discards_pending_changes() {
+        let storage = memory_storage(NO_BACKUPS);
+        storage.unlock("pw").await.unwrap();
+
+        storage.add_totp_entry(sample_entry("alice")).await.unwrap();
+        storage.schedule_save();
+        assert_eq!(names(&reload(&storage, "pw").await), ["alice"]);
+
+        storage.add_totp_entry(sample_entry("bob")).await.unwrap();
+        storage.schedule_save();
+        storage.lock(true).await.unwrap();
+        assert!(!storage.save_status().pending);
+        storage.unlock("pw").await.unwrap();
+        assert_eq!(names(&storage.get_totp_entries().await.unwrap()), ["alice"]);
+    }
+
+    #[actix_web::test]
+    async fn restore_backup_replaces_current_data() {
+        let storage = memory_storage(EVERY_SAVE);
+        storage.unlock("pw").await.unwrap();
+        storage.add_totp_entry(sample_entry("alice")).await.unwrap();
+        storage.save().await.unwrap();
+        // 备份 ID 精确到毫秒
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        storage.add_totp_entry(sample_entry("bob")).await.unwrap();
+        storage.save().await.unwrap();
+
+        // 每次保存前备份当前内容：最新的一份只有 alice，较早的一份为空
+        let backups = storage.list_backups().await.unwrap();
+        assert_eq!(backups.len(), 2);
+        let id = &backups[0].id;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        assert!(matches!(storage.restore_backup(id, "wrong", None).await, Err(AppError::InvalidMasterPassword)));
+        assert!(!storage.restore_backup("20000101T000000.000Z", "pw", None).await.unwrap());
+        assert!(storage.restore_backup(id, "pw", None).await.unwrap());
+        assert_eq!(names(&storage.get_totp_entries().await.unwrap()), ["alice"]);
+        assert_eq!(names(&reload(&storage, "pw").await), ["alice"]);
+
+        // 恢复前的数据也留下了一份备份
+        let backups = storage.list_backups().await.unwrap();
+        assert_eq!(backups.len(), 3);
+        assert!(storage.restore_backup(&backups[0].id, "pw", None).await.unwrap());
+        assert_eq!(names(&storage.get_totp_entries().await.unwrap()), ["alice", "bob"]);
+    }
+
+    // 用主密码确认时只替换该密码槽，恢复密钥仍然可用；用恢复密钥确认时重设主密码
+    #[actix_web::test]
+    async fn change_master_password_rewraps_password_slot() {
+        let storage = memory_storage(NO_BACKUPS);
+        let recovery_key = storage.unlock("old").await.unwrap().unwrap();
+        storage.add_totp_entry(sample_entry("alice")).await.unwrap();
+        storage.save().await.unwrap();
+
+        assert!(matches!(storage.change_master_password("wrong", "new").await, Err(AppError::InvalidMasterPassword)));
+        storage.change_master_password("old", "new").await.unwrap();
+        assert_eq!(storage.list_key_slots().await.unwrap().len(), 2);
+
+        storage.lock(false).await.unwrap();
+        assert!(matches!(storage.unlock("old").await, Err(AppError::InvalidMasterPassword)));
+        storage.unlock("new").await.unwrap();
+        assert_eq!(names(&storage.get_totp_entries().await.unwrap()), ["alice"]);
+
+        storage.change_master_password(&recovery_key, "reset").await.unwrap();
+        storage.lock(false).await.unwrap();
+        assert!(matches!(storage.unlock("new").await, Err(AppError::InvalidMasterPassword)));
+        assert_eq!(names(&reload(&storage, &recovery_key).await), ["alice"]);
+        storage.lock(false).await.unwrap();
+        storage.unlock("reset").await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn flush_writes_scheduled_changes() {
+        let storage = memory_storage(NO_BACKUPS);
+        storage.unlock("pw").await.unwrap();
+        storage.flush().await.unwrap();
+        assert!(!storage.save_status().pending);
+
+        storage.add_totp_entry(sample_entry("alice")).await.unwrap();
+        storage.schedule_save();
+        assert!(storage.save_status().pending);
+        storage.flush().await.unwrap();
+        let status = storage.save_status();
+        assert!(!status.pending && status.last_saved_at.is_some() && status.last_error.is_none());
+
+        // 丢弃内存中的数据，确认已经写入后端
+        storage.lock(true).await.unwrap();
+        storage.unlock("pw").await.unwrap();
+        assert_eq!(names(&storage.get_totp_entries().await.unwrap()), ["alice"]);
+    }
+
+    // 后台任务在 save_debounce 之后合并写入
+    #[actix_web::test]
+    async fn background_writer_saves_after_debounce() {
+        let backend = Box::new(crate::backend::MemoryBackend::new(NO_BACKUPS));
+        let debounce = Duration::from_millis(100);
+        let storage = Arc::new(Storage::new(backend, test_policy(), String::new(), None, debounce));
+        storage.unlock("pw").await.unwrap();
+        let writer = tokio::spawn({
+            let storage = storage.clone();
+            async move { storage.run_writer().await }
+        });
+
+        for name in ["alice", "bob"] {
+            storage.add_totp_entry(sample_entry(name)).await.unwrap();
+            storage.schedule_save();
+        }
+        assert!(storage.save_status().pending);
+        tokio::time::sleep(debounce * 3).await;
+        assert!(!storage.save_status().pending);
+        writer.abort();
+
+        storage.lock(true).await.unwrap();
+        storage.unlock("pw").await.unwrap();
+        assert_eq!(names(&storage.get_totp_entries().await.unwrap()), ["alice", "bob"]);
+    }
+
+    // 解开的密钥槽低于策略的最低参数时按策略重新包装并写回文件，其他槽不变
+    #[actix_web::test]
+    async fn unlock_upgrades_weak_slot_to_policy_params() {
+        use crate::backend::{FileBackend, StorageBackend};
+        use crate::crypto::{KdfPolicy, MIN_KDF_PARAMS};
+
+        let stronger = crypto::KdfParams { t_cost: MIN_KDF_PARAMS.t_cost + 1, ..MIN_KDF_PARAMS };
+        let policy = |params| KdfPolicy { fixed: Some(params), minimum: params, target: Duration::ZERO, max_m_cost: params.m_cost };
+        let open = |path: &str, params| {
+            let backend = Box::new(FileBackend::new(path, NO_BACKUPS).unwrap());
+            Storage::new(backend, policy(params), String::new(), None, Duration::ZERO)
+        };
+        let slot_params = |key: &MasterKey| key.slots.iter().map(|slot| (slot.kind, slot.kdf_params)).collect::<Vec<_>>();
+
+        let dir = TempDir::new();
+        let path = dir.file("data.enc");
+        let storage = open(&path, MIN_KDF_PARAMS);
+        storage.unlock("pw").await.unwrap();
+        storage.add_totp_entry(sample_entry("alice")).await.unwrap();
+        storage.save().await.unwrap();
+        drop(storage);
+
+        let storage = open(&path, stronger);
+        storage.unlock("pw").await.unwrap();
+        let expected = [(SlotKind::Password, stronger), (SlotKind::RecoveryKey, MIN_KDF_PARAMS)];
+        assert_eq!(slot_params(&storage.current_key().await.unwrap()), expected);
+        drop(storage);
+
+        let stored = FileBackend::new(&path, NO_BACKUPS).unwrap().load("pw").unwrap().unwrap();
+        assert_eq!(slot_params(&stored.key), expected);
+        assert_eq!(names(&stored.data.totp_entries), ["alice"]);
+    }
+
+    // 密钥文件只能位于密钥文件目录中，已有的文件必须只有所有者可以访问
+    #[actix_web::test]
+    async fn key_files_must_be_inside_key_file_dir() {
+        let dir = TempDir::new();
+        let keys = dir.file("keys");
+        let storage = memory_storage_with_key_file_dir(NO_BACKUPS, &keys);
+        storage.unlock("pw").await.unwrap();
+
+        let id = storage.add_key_file_slot("pw", "unlock.key").await.unwrap();
+        let slots = storage.list_key_slots().await.unwrap();
+        let path = &slots.iter().find(|slot| slot.id == id).unwrap().label;
+        assert!(path.ends_with("keys/unlock.key"), "{}", path);
+        let secret = keyfile::read(path).unwrap();
+        storage.lock(false).await.unwrap();
+        storage.unlock(&secret).await.unwrap();
+
+        std::fs::write(dir.file("outside.key"), "secret").unwrap();
+        let outside = [
+            dir.file("outside.key"),
+            "../outside.key".to_string(),
+            "/etc/passwd".to_string(),
+            "sub/../../outside.key".to_string(),
+        ];
+        for path in outside {
+            let result = storage.add_key_file_slot("pw", &path).await;
+            assert!(matches!(result, Err(AppError::InvalidKeyFilePath(_))), "{}: {:?}", path, result);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let shared = format!("{}/shared.key", keys);
+            std::fs::write(&shared, "secret").unwrap();
+            std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o644)).unwrap();
+            assert!(matches!(storage.add_key_file_slot("pw", "shared.key").await, Err(AppError::InsecureKeyFile(_))));
+
+            std::os::unix::fs::symlink(dir.file("outside.key"), format!("{}/link.key", keys)).unwrap();
+            assert!(matches!(storage.add_key_file_slot("pw", "link.key").await, Err(AppError::InvalidKeyFilePath(_))));
+        }
+        assert_eq!(storage.list_key_slots().await.unwrap().len(), 3);
+    }
+}
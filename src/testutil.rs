@@ -0,0 +1,69 @@
+// 各模块测试共用的辅助：临时目录、低成本的 KDF 参数，以及基于 MemoryBackend 的 Storage
+
+use crate::backend::{FileBackend, MemoryBackend};
+use crate::backup::BackupPolicy;
+use crate::crypto::{KdfParams, KdfPolicy};
+use crate::models::TotpEntry;
+use crate::storage::Storage;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// 测试中使用最低的 Argon2 参数，避免每次派生都耗时
+pub const TEST_PARAMS: KdfParams = KdfParams { m_cost: 8, t_cost: 1, p_cost: 1 };
+
+pub const NO_BACKUPS: BackupPolicy = BackupPolicy { count: 0, max_age: None, min_interval: None };
+
+// 每次保存前都备份，最多保留 5 份
+pub const EVERY_SAVE: BackupPolicy = BackupPolicy { count: 5, max_age: None, min_interval: None };
+
+pub fn test_policy() -> KdfPolicy {
+    KdfPolicy {
+        fixed: Some(TEST_PARAMS),
+        minimum: TEST_PARAMS,
+        target: Duration::ZERO,
+        max_m_cost: TEST_PARAMS.m_cost,
+    }
+}
+
+// 后台保存任务不运行，修改需要调用 save() 或 flush() 写入；密钥文件目录为 key_file_dir
+pub fn memory_storage_with_key_file_dir(backup_policy: BackupPolicy, key_file_dir: &str) -> Storage {
+    let backend = Box::new(MemoryBackend::new(backup_policy));
+    Storage::new(backend, test_policy(), key_file_dir.to_string(), None, Duration::ZERO)
+}
+
+// 不使用密钥文件的测试
+pub fn memory_storage(backup_policy: BackupPolicy) -> Storage {
+    memory_storage_with_key_file_dir(backup_policy, "/nonexistent/web-totp-keys")
+}
+
+// data.enc 位于 path 的 Storage，用于需要直接修改文件的测试
+pub fn file_storage(path: &str, backup_policy: BackupPolicy) -> Storage {
+    let backend = Box::new(FileBackend::new(path, backup_policy).unwrap());
+    Storage::new(backend, test_policy(), "/nonexistent/web-totp-keys".to_string(), None, Duration::ZERO)
+}
+
+pub fn sample_entry(name: &str) -> TotpEntry {
+    TotpEntry::new(name.to_string(), "GitHub".to_string(), "JBSWY3DPEHPK3PXP".to_string())
+}
+
+// 每个测试使用独立的临时目录，结束时删除
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("web-totp-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn file(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
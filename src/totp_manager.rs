@@ -1,3 +1,5 @@
+use crate::error::{AppError, Result as AppResult};
+use crate::models::{OtpAlgorithm, OtpType, TotpEntry, DEFAULT_DIGITS, DEFAULT_PERIOD};
 use totp_rs::{Algorithm, Secret, TOTP};
 use qrcode::QrCode;
 use base64::{Engine as _, engine::general_purpose};
@@ -9,6 +11,10 @@ pub fn generate_secret() -> String {
     // Generate random bytes
     let secret_bytes: Vec<u8> = (0..20).map(|_| rng.gen()).collect();
     
+    encode_base32(&secret_bytes)
+}
+
+pub fn encode_base32(bytes: &[u8]) -> String {
     // Encode to Base32 (only A-Z and 2-7)
     let base32_chars = "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
     let mut result = String::new();
@@ -16,7 +22,7 @@ pub fn generate_secret() -> String {
     let mut bits = 0u32;
     let mut bit_count = 0;
     
-    for &byte in &secret_bytes {
+    for &byte in bytes {
         bits = (bits << 8) | byte as u32;
         bit_count += 8;
         
@@ -36,35 +42,202 @@ pub fn generate_secret() -> String {
     result
 }
 
-pub fn generate_totp_code(secret: &str) -> Result<(String, u64), Box<dyn std::error::Error>> {
-    let secret_bytes = Secret::Encoded(secret.to_string())
+impl From<OtpAlgorithm> for Algorithm {
+    fn from(algorithm: OtpAlgorithm) -> Self {
+        match algorithm {
+            OtpAlgorithm::SHA1 => Algorithm::SHA1,
+            OtpAlgorithm::SHA256 => Algorithm::SHA256,
+            OtpAlgorithm::SHA512 => Algorithm::SHA512,
+        }
+    }
+}
+
+pub fn decode_base32(secret: &str) -> AppResult<Vec<u8>> {
+    Secret::Encoded(secret.to_string())
         .to_bytes()
-        .map_err(|e| format!("Failed to parse secret: {:?}", e))?;
+        .map_err(|e| AppError::Totp(format!("Failed to parse secret: {:?}", e)))
+}
+
+// 按条目自身的算法、位数和周期构造 TOTP（同时校验参数是否合法）
+// HOTP 条目使用步长 1，此时 generate(counter) 即为 RFC 4226 的 HOTP(counter)
+//
+// 使用 new_unchecked：TOTP::new 要求密钥至少 128 位，会拒绝大多数服务签发的 80 位密钥
+// （16 个 base32 字符），位数、周期和密钥在这里自行校验
+fn build_totp(entry: &TotpEntry) -> AppResult<TOTP> {
+    let step = match entry.otp_type {
+        OtpType::Totp => entry.period,
+        OtpType::Hotp => 1,
+    };
+    if step == 0 {
+        return Err(AppError::Totp("Period must be greater than zero".to_string()));
+    }
+    check_digits(entry.digits)?;
     
-    let totp = TOTP::new(
-        Algorithm::SHA1,
-        6,
+    let secret_bytes = decode_secret(entry.secret.expose_secret())?;
+    
+    Ok(TOTP::new_unchecked(
+        entry.algorithm.into(),
+        entry.digits,
         1,
-        30,
+        step,
         secret_bytes,
         None,
         String::from(""),
-    )?;
+    ))
+}
+
+fn check_digits(digits: usize) -> AppResult<()> {
+    if !(6..=8).contains(&digits) {
+        return Err(AppError::Totp(format!("Digits must be between 6 and 8, got {}", digits)));
+    }
+    Ok(())
+}
+
+// 解码并拒绝空密钥
+fn decode_secret(secret: &str) -> AppResult<Vec<u8>> {
+    let bytes = decode_base32(secret)?;
+    if bytes.is_empty() {
+        return Err(AppError::Totp("Secret must not be empty".to_string()));
+    }
+    Ok(bytes)
+}
+
+pub fn validate_entry(entry: &TotpEntry) -> AppResult<()> {
+    build_totp(entry).map(|_| ())
+}
+
+pub fn generate_totp_code(entry: &TotpEntry) -> Result<(String, u64), Box<dyn std::error::Error>> {
+    let totp = build_totp(entry)?;
     
     let code = totp.generate_current()?;
-    let remaining = 30 - (std::time::SystemTime::now()
+    let remaining = entry.period - (std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)?
-        .as_secs() % 30);
+        .as_secs() % entry.period);
     
     Ok((code, remaining))
 }
 
+pub fn generate_hotp_code(entry: &TotpEntry, counter: u64) -> AppResult<String> {
+    let hotp = build_totp(entry)?;
+    Ok(hotp.generate(counter))
+}
+
+// 在计数器前向窗口内查找两个连续的验证码，返回第一个验证码对应的计数器
+pub fn find_hotp_counter(
+    entry: &TotpEntry,
+    code1: &str,
+    code2: &str,
+    window: u64,
+) -> AppResult<Option<u64>> {
+    let hotp = build_totp(entry)?;
+    
+    for counter in entry.counter..entry.counter.saturating_add(window) {
+        if hotp.generate(counter) == code1 && hotp.generate(counter + 1) == code2 {
+            return Ok(Some(counter));
+        }
+    }
+    
+    Ok(None)
+}
+
+// 规范化 Base32 密钥：去除空格和填充，统一为大写
+pub fn normalize_secret(secret: &str) -> String {
+    secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+// 两个条目的密钥和类型都相同时视为同一账户
+pub fn is_duplicate(a: &TotpEntry, b: &TotpEntry) -> bool {
+    a.otp_type == b.otp_type && normalize_secret(a.secret.expose_secret()) == normalize_secret(b.secret.expose_secret())
+}
+
+pub fn parse_algorithm(value: &str) -> AppResult<OtpAlgorithm> {
+    match value.to_uppercase().as_str() {
+        "SHA1" => Ok(OtpAlgorithm::SHA1),
+        "SHA256" => Ok(OtpAlgorithm::SHA256),
+        "SHA512" => Ok(OtpAlgorithm::SHA512),
+        other => Err(AppError::Totp(format!("Unsupported algorithm: {}", other))),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> AppResult<T> {
+    value
+        .parse()
+        .map_err(|_| AppError::Totp(format!("Invalid {} value: {}", key, value)))
+}
+
+// 解析 otpauth://totp/ 或 otpauth://hotp/ URI（Google Authenticator Key Uri Format）
+pub fn parse_otpauth_uri(uri: &str) -> AppResult<TotpEntry> {
+    let url = url::Url::parse(uri.trim())
+        .map_err(|e| AppError::Totp(format!("Invalid URI: {}", e)))?;
+    
+    if url.scheme() != "otpauth" {
+        return Err(AppError::Totp(format!("Unsupported URI scheme: {}", url.scheme())));
+    }
+    
+    let otp_type = match url.host_str().map(|h| h.to_lowercase()).as_deref() {
+        Some("totp") => OtpType::Totp,
+        Some("hotp") => OtpType::Hotp,
+        Some(other) => return Err(AppError::Totp(format!("Unsupported OTP type: {}", other))),
+        None => return Err(AppError::Totp("Missing OTP type".to_string())),
+    };
+    
+    // 标签格式为 "issuer:account" 或 "account"
+    let label = urlencoding::decode(url.path().trim_start_matches('/'))
+        .map_err(|e| AppError::Totp(format!("Invalid label encoding: {}", e)))?;
+    let (label_issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (Some(issuer.trim().to_string()), account.trim().to_string()),
+        None => (None, label.trim().to_string()),
+    };
+    
+    let mut secret = None;
+    let mut issuer = None;
+    let mut algorithm = OtpAlgorithm::default();
+    let mut digits = DEFAULT_DIGITS;
+    let mut period = DEFAULT_PERIOD;
+    let mut counter = None;
+    
+    for (key, value) in url.query_pairs() {
+        match key.to_lowercase().as_str() {
+            "secret" => secret = Some(normalize_secret(&value)),
+            "issuer" => issuer = Some(value.trim().to_string()),
+            "algorithm" => algorithm = parse_algorithm(&value)?,
+            "digits" => digits = parse_number("digits", &value)?,
+            "period" => period = parse_number("period", &value)?,
+            "counter" => counter = Some(parse_number("counter", &value)?),
+            _ => {}
+        }
+    }
+    
+    let secret = secret
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| AppError::Totp("Missing secret parameter".to_string()))?;
+    if otp_type == OtpType::Hotp && counter.is_none() {
+        return Err(AppError::Totp("Missing counter parameter for HOTP".to_string()));
+    }
+    
+    // issuer 参数优先于标签前缀
+    let issuer = issuer.or(label_issuer).unwrap_or_default();
+    let name = if account.is_empty() { issuer.clone() } else { account };
+    
+    let mut entry = TotpEntry::new(name, issuer, secret);
+    entry.algorithm = algorithm;
+    entry.digits = digits;
+    entry.period = period;
+    entry.otp_type = otp_type;
+    entry.counter = counter.unwrap_or(0);
+    
+    validate_entry(&entry)?;
+    Ok(entry)
+}
+
 pub fn verify_totp_code(secret: &str, code: &str) -> Result<bool, Box<dyn std::error::Error>> {
-    let secret_bytes = Secret::Encoded(secret.to_string())
-        .to_bytes()
-        .map_err(|e| format!("Failed to parse secret: {:?}", e))?;
+    let secret_bytes = decode_secret(secret)?;
     
-    let totp = TOTP::new(
+    let totp = TOTP::new_unchecked(
         Algorithm::SHA1,
         6,
         1,
@@ -72,18 +245,54 @@ pub fn verify_totp_code(secret: &str, code: &str) -> Result<bool, Box<dyn std::e
         secret_bytes,
         None,
         String::from(""),
-    )?;
+    );
     
     Ok(totp.check_current(code)?)
 }
 
-pub fn generate_qr_code(secret: &str, username: &str, issuer: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let otpauth_url = format!(
-        "otpauth://totp/{}:{}?secret={}&issuer={}",
-        issuer, username, secret, issuer
+// 生成 otpauth URI，标签和 issuer 按 Key Uri Format 进行百分号编码
+pub fn build_otpauth_uri(entry: &TotpEntry) -> String {
+    let label = if entry.issuer.is_empty() {
+        urlencoding::encode(&entry.name).into_owned()
+    } else {
+        format!("{}:{}", urlencoding::encode(&entry.issuer), urlencoding::encode(&entry.name))
+    };
+    
+    let mut uri = format!(
+        "otpauth://{}/{}?secret={}",
+        match entry.otp_type {
+            OtpType::Totp => "totp",
+            OtpType::Hotp => "hotp",
+        },
+        label,
+        normalize_secret(entry.secret.expose_secret()),
     );
+    if !entry.issuer.is_empty() {
+        uri.push_str(&format!("&issuer={}", urlencoding::encode(&entry.issuer)));
+    }
+    uri.push_str(&format!("&algorithm={:?}&digits={}", entry.algorithm, entry.digits));
+    match entry.otp_type {
+        OtpType::Totp => uri.push_str(&format!("&period={}", entry.period)),
+        OtpType::Hotp => uri.push_str(&format!("&counter={}", entry.counter)),
+    }
     
-    let code = QrCode::new(otpauth_url.as_bytes())?;
+    uri
+}
+
+pub fn generate_otpauth_url(secret: &str, username: &str, issuer: &str) -> String {
+    build_otpauth_uri(&TotpEntry::new(
+        username.to_string(),
+        issuer.to_string(),
+        secret.to_string(),
+    ))
+}
+
+pub fn generate_qr_code(secret: &str, username: &str, issuer: &str) -> Result<String, Box<dyn std::error::Error>> {
+    render_qr_svg(&generate_otpauth_url(secret, username, issuer))
+}
+
+pub fn render_qr_svg(content: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let code = QrCode::new(content.as_bytes())?;
     
     // Render to SVG string for simplicity
     let svg_data = code.render()
@@ -97,3 +306,104 @@ pub fn generate_qr_code(secret: &str, username: &str, issuer: &str) -> Result<St
     Ok(format!("data:image/svg+xml;base64,{}", svg_base64))
 }
 
+pub fn render_qr_png(content: &str) -> Result<String, Box<dyn std::error::Error>> {
+    const MODULE_SIZE: u32 = 8;
+    const QUIET_ZONE: u32 = 4;
+    
+    let code = QrCode::new(content.as_bytes())?;
+    let width = code.width() as u32;
+    let colors = code.to_colors();
+    let size = (width + 2 * QUIET_ZONE) * MODULE_SIZE;
+    
+    let img = image::GrayImage::from_fn(size, size, |x, y| {
+        let mx = (x / MODULE_SIZE) as i64 - QUIET_ZONE as i64;
+        let my = (y / MODULE_SIZE) as i64 - QUIET_ZONE as i64;
+        let dark = mx >= 0 && my >= 0 && mx < width as i64 && my < width as i64
+            && colors[(my as u32 * width + mx as u32) as usize] == qrcode::Color::Dark;
+        image::Luma([if dark { 0 } else { 255 }])
+    });
+    
+    let mut png = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut png, image::ImageFormat::Png)?;
+    
+    let png_base64 = general_purpose::STANDARD.encode(png.into_inner());
+    Ok(format!("data:image/png;base64,{}", png_base64))
+}
+
+// 从 PNG/JPEG 截图中识别所有二维码，返回其文本内容
+pub fn decode_qr_codes(image_data: &[u8]) -> AppResult<Vec<String>> {
+    let format = image::guess_format(image_data)
+        .map_err(|e| AppError::Totp(format!("Unrecognized image format: {}", e)))?;
+    if !matches!(format, image::ImageFormat::Png | image::ImageFormat::Jpeg) {
+        return Err(AppError::Totp(format!("Unsupported image format: {:?}", format)));
+    }
+    
+    let img = image::load_from_memory_with_format(image_data, format)
+        .map_err(|e| AppError::Totp(format!("Failed to decode image: {}", e)))?
+        .to_luma8();
+    
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        img.width() as usize,
+        img.height() as usize,
+        |x, y| img.get_pixel(x as u32, y as u32).0[0],
+    );
+    
+    let mut contents = Vec::new();
+    for grid in prepared.detect_grids() {
+        match grid.decode() {
+            Ok((_, content)) => contents.push(content),
+            Err(e) => log::warn!("Failed to decode QR code: {}", e),
+        }
+    }
+    
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 大多数服务签发的 80 位密钥（16 个 base32 字符）
+    const SHORT_SECRET: &str = "JBSWY3DPEHPK3PXP";
+
+    #[test]
+    fn accepts_80_bit_secret() {
+        let entry = TotpEntry::new("a".to_string(), "b".to_string(), SHORT_SECRET.to_string());
+        validate_entry(&entry).unwrap();
+        let (code, remaining) = generate_totp_code(&entry).unwrap();
+        assert_eq!(code.len(), 6);
+        assert!((1..=30).contains(&remaining));
+    }
+
+    // RFC 4226 附录 D 的测试向量
+    #[test]
+    fn hotp_matches_rfc4226() {
+        let mut entry = TotpEntry::new("a".to_string(), "b".to_string(),
+            encode_base32(b"12345678901234567890"));
+        entry.otp_type = OtpType::Hotp;
+        assert_eq!(generate_hotp_code(&entry, 0).unwrap(), "755224");
+        assert_eq!(generate_hotp_code(&entry, 9).unwrap(), "520489");
+    }
+
+    #[test]
+    fn rejects_invalid_parameters() {
+        let mut entry = TotpEntry::new("a".to_string(), "b".to_string(), SHORT_SECRET.to_string());
+        entry.digits = 5;
+        assert!(validate_entry(&entry).is_err());
+        entry.digits = 6;
+        entry.period = 0;
+        assert!(validate_entry(&entry).is_err());
+        entry.period = 30;
+        entry.secret = String::new().into();
+        assert!(validate_entry(&entry).is_err());
+        entry.secret = "not base32!".to_string().into();
+        assert!(validate_entry(&entry).is_err());
+    }
+
+    #[test]
+    fn parses_uri_with_short_secret() {
+        let entry = parse_otpauth_uri(&format!("otpauth://totp/Example:alice?secret={}&issuer=Example", SHORT_SECRET)).unwrap();
+        assert_eq!(entry.issuer, "Example");
+        assert_eq!(entry.secret.expose_secret(), SHORT_SECRET);
+    }
+}
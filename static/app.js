@@ -231,6 +231,7 @@ function renderTotpEntries(entries) {
                     <span class="issuer">${escapeHtml(entry.issuer)}</span>
                 </div>
                 <div class="totp-actions">
+                    ${entry.otp_type === 'hotp' ? `<button class="icon-btn" onclick="generateCode('${entry.id}')" title="生成下一个验证码">🔄</button>` : ''}
                     <button class="icon-btn delete" onclick="showDeleteConfirm('${entry.id}')">🗑️</button>
                 </div>
             </div>
@@ -242,20 +243,22 @@ function renderTotpEntries(entries) {
                 <div class="timer-bar">
                     <div class="timer-fill" id="timer-${entry.id}"></div>
                 </div>
-                <span id="seconds-${entry.id}">30s</span>
+                <span id="seconds-${entry.id}">${entry.otp_type === 'hotp' ? `#${entry.counter}` : `${entry.period}s`}</span>
             </div>
         </div>
     `).join('');
     
     // Start generating codes for all entries
-    entries.forEach(entry => {
+    // HOTP entries advance their counter on every generation, so they are only generated on demand
+    entries.filter(entry => entry.otp_type !== 'hotp').forEach(entry => {
         generateCode(entry.id);
         // Clear existing interval if any
         if (totpIntervals[entry.id]) {
             clearInterval(totpIntervals[entry.id]);
         }
         // Update every second
-        totpIntervals[entry.id] = setInterval(() => generateCode(entry.id), 1000);
+        // Periodic refreshes are marked so they don't count as activity for the idle auto-lock
+        totpIntervals[entry.id] = setInterval(() => generateCode(entry.id, true), 1000);
     });
 }
 
@@ -278,18 +281,31 @@ function copyCode(entryId) {
     });
 }
 
-async function generateCode(entryId) {
+async function generateCode(entryId, autoRefresh = false) {
     try {
-        const response = await fetch(`/api/totp/generate/${entryId}`);
+        const response = await fetch(`/api/totp/generate/${entryId}`, {
+            headers: autoRefresh ? { 'X-Auto-Refresh': '1' } : {}
+        });
+        if (response.status === 503) {
+            // Database was locked (manually or after being idle)
+            Object.values(totpIntervals).forEach(interval => clearInterval(interval));
+            totpIntervals = {};
+            showUnlockPage();
+            return;
+        }
         const data = await response.json();
         
         const codeElement = document.getElementById(`code-${entryId}`);
         const timerElement = document.getElementById(`timer-${entryId}`);
         const secondsElement = document.getElementById(`seconds-${entryId}`);
         
-        if (codeElement && data.code) {
+        if (codeElement && data.code && data.otp_type === 'hotp') {
             codeElement.textContent = data.code;
-            const percentage = (data.remaining_seconds / 30) * 100;
+            timerElement.style.width = '100%';
+            secondsElement.textContent = `#${data.counter}`;
+        } else if (codeElement && data.code) {
+            codeElement.textContent = data.code;
+            const percentage = (data.remaining_seconds / data.period) * 100;
             timerElement.style.width = `${percentage}%`;
             secondsElement.textContent = `${data.remaining_seconds}s`;
             
@@ -314,23 +330,37 @@ addTotpBtn.addEventListener('click', () => {
     addTotpModal.classList.add('show');
 });
 
+// Show the period field for TOTP and the counter field for HOTP
+const entryTypeSelect = document.getElementById('entry-type');
+entryTypeSelect.addEventListener('change', () => {
+    const isHotp = entryTypeSelect.value === 'hotp';
+    document.getElementById('entry-period-group').style.display = isHotp ? 'none' : 'block';
+    document.getElementById('entry-counter-group').style.display = isHotp ? 'block' : 'none';
+});
+
 addTotpForm.addEventListener('submit', async (e) => {
     e.preventDefault();
     
     const name = document.getElementById('entry-name').value;
     const issuer = document.getElementById('entry-issuer').value;
     const secret = document.getElementById('entry-secret').value;
+    const algorithm = document.getElementById('entry-algorithm').value;
+    const digits = parseInt(document.getElementById('entry-digits').value, 10);
+    const period = parseInt(document.getElementById('entry-period').value, 10);
+    const otp_type = document.getElementById('entry-type').value;
+    const counter = parseInt(document.getElementById('entry-counter').value, 10) || 0;
     
     try {
         const response = await fetch('/api/totp/add', {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
-            body: JSON.stringify({ name, issuer, secret })
+            body: JSON.stringify({ name, issuer, secret, algorithm, digits, period, otp_type, counter })
         });
         
         if (response.ok) {
             addTotpModal.classList.remove('show');
             addTotpForm.reset();
+            entryTypeSelect.dispatchEvent(new Event('change'));
             loadTotpEntries();
         } else {
             alert('添加失败，请检查密钥是否正确');
@@ -663,6 +693,10 @@ if (unlockForm) {
             
             if (data.success) {
                 isUnlocked = true;
+                // 新建数据库时返回恢复密钥，只显示这一次
+                if (data.recovery_key) {
+                    alert(window.t('recovery_key_notice') + '\n\n' + data.recovery_key);
+                }
                 showLoginPage();
             } else {
                 unlockError.textContent = data.message || 'Invalid master password';
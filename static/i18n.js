@@ -35,6 +35,11 @@ const translations = {
         'name_placeholder': '例如: Google',
         'issuer_placeholder': '例如: Google',
         'secret_placeholder': '输入密钥',
+        'entry_type': '类型',
+        'entry_algorithm': '算法',
+        'entry_digits': '位数',
+        'entry_period': '周期（秒）',
+        'entry_counter': '计数器',
         'cancel': '取消',
         'add': '添加',
         
@@ -81,6 +86,7 @@ const translations = {
         'invalid_2fa': '2FA 验证码错误',
         'login_failed': '登录失败，请重试',
         'unlock_failed': '解锁失败，请重试',
+        'recovery_key_notice': '数据库已创建。请抄写并妥善保管以下恢复密钥，忘记主密码时可以用它解锁。它只显示这一次：',
     },
     'en-US': {
         // Common
@@ -117,6 +123,11 @@ const translations = {
         'name_placeholder': 'e.g. Google',
         'issuer_placeholder': 'e.g. Google',
         'secret_placeholder': 'Enter secret key',
+        'entry_type': 'Type',
+        'entry_algorithm': 'Algorithm',
+        'entry_digits': 'Digits',
+        'entry_period': 'Period (seconds)',
+        'entry_counter': 'Counter',
         'cancel': 'Cancel',
         'add': 'Add',
         
@@ -163,6 +174,7 @@ const translations = {
         'invalid_2fa': 'Invalid 2FA code',
         'login_failed': 'Login failed, please try again',
         'unlock_failed': 'Unlock failed, please try again',
+        'recovery_key_notice': 'Database created. Write down the recovery key below and keep it safe; it unlocks the database if you forget the master password. It is shown only once:',
     }
 };
 
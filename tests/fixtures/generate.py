@@ -0,0 +1,410 @@
+#!/usr/bin/env python3
+# 生成单元测试使用的样例文件
+#
+# 依赖：pip install cryptography
+# 用法：python3 tests/fixtures/generate.py（在仓库根目录运行，覆盖 tests/fixtures 下的文件）
+#
+# 其他应用的备份（src/formats）都包含同样三个账户（见 src/formats/mod.rs 中的 samples），
+# 加密样例的密码为 "<格式>-test"，例如 aegis-test。
+# 历史版本的 data.enc（src/backend）使用主密码 master-test。
+# Google Authenticator 的迁移导出（src/migration.rs）内容固定，重新生成不会改变。
+
+import base64
+import gzip
+import hashlib
+import hmac
+import json
+import os
+import struct
+import uuid
+
+from cryptography.hazmat.primitives import hashes, padding
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+from cryptography.hazmat.primitives.ciphers.aead import AESGCM
+from cryptography.hazmat.primitives.kdf.argon2 import Argon2id
+from cryptography.hazmat.primitives.kdf.hkdf import HKDFExpand
+
+OUT = os.path.dirname(os.path.abspath(__file__))
+
+GITHUB = dict(issuer="GitHub", name="alice@example.com", secret="JBSWY3DPEHPK3PXP",
+              algo="SHA1", digits=6, period=30)
+EXAMPLE = dict(issuer="Example", name="bob", secret="GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+               algo="SHA256", digits=8, period=60)
+BANK_RAW = b"hotp-secret-1234"
+BANK = dict(issuer="Bank", name="carol", secret=base64.b32encode(BANK_RAW).decode(),
+            algo="SHA1", digits=6, counter=5)
+
+
+def write(name, data):
+    mode = "wb" if isinstance(data, bytes) else "w"
+    with open(os.path.join(OUT, name), mode) as f:
+        f.write(data)
+
+
+def b64(data):
+    return base64.b64encode(data).decode()
+
+
+# ---------- Aegis ----------
+
+def aegis_entries():
+    def entry(kind, acct, info):
+        return {"type": kind, "uuid": str(uuid.uuid4()), "name": acct["name"], "issuer": acct["issuer"],
+                "note": "", "favorite": False, "icon": None, "icon_mime": None, "groups": [], "info": info}
+    return {"version": 3, "entries": [
+        entry("totp", GITHUB, {"secret": GITHUB["secret"], "algo": "SHA1", "digits": 6, "period": 30}),
+        entry("totp", EXAMPLE, {"secret": EXAMPLE["secret"], "algo": "SHA256", "digits": 8, "period": 60}),
+        entry("hotp", BANK, {"secret": BANK["secret"], "algo": "SHA1", "digits": 6, "counter": 5}),
+    ], "groups": []}
+
+
+def aegis_seal(key, plaintext):
+    nonce = os.urandom(12)
+    sealed = AESGCM(key).encrypt(nonce, plaintext, None)
+    return {"nonce": nonce.hex(), "tag": sealed[-16:].hex()}, sealed[:-16]
+
+
+def aegis():
+    write("aegis-plain.json", json.dumps(
+        {"version": 1, "header": {"slots": None, "params": None}, "db": aegis_entries()}, indent=4))
+
+    master_key = os.urandom(32)
+    salt = os.urandom(32)
+    n, r, p = 1 << 15, 8, 1
+    slot_key = hashlib.scrypt(b"aegis-test", salt=salt, n=n, r=r, p=p, maxmem=64 * 1024 * 1024, dklen=32)
+    key_params, wrapped = aegis_seal(slot_key, master_key)
+    db_params, ciphertext = aegis_seal(master_key, json.dumps(aegis_entries()).encode())
+    # 生物识别 slot（type 2）无法用密码解开，导入时应当跳过
+    bio_params, bio_wrapped = aegis_seal(os.urandom(32), master_key)
+    slots = [
+        {"type": 2, "uuid": str(uuid.uuid4()), "key": bio_wrapped.hex(), "key_params": bio_params},
+        {"type": 1, "uuid": str(uuid.uuid4()), "key": wrapped.hex(), "key_params": key_params,
+         "n": n, "r": r, "p": p, "salt": salt.hex(), "repaired": True, "is_backup": False},
+    ]
+    write("aegis-encrypted.json", json.dumps(
+        {"version": 1, "header": {"slots": slots, "params": db_params}, "db": b64(ciphertext)}, indent=4))
+
+
+# ---------- 2FAS ----------
+
+def twofas_services():
+    def service(acct, otp, position):
+        return {"name": acct["issuer"], "secret": acct["secret"], "updatedAt": 1700000000000,
+                "otp": dict(otp, account=acct["name"], issuer=acct["issuer"], source="Link"),
+                "order": {"position": position}, "icon": {"selected": "Label"}}
+    return [
+        service(GITHUB, {"label": "GitHub:alice@example.com", "digits": 6, "period": 30,
+                         "algorithm": "SHA1", "tokenType": "TOTP"}, 0),
+        service(EXAMPLE, {"label": "Example:bob", "digits": 8, "period": 60,
+                          "algorithm": "SHA256", "tokenType": "TOTP"}, 1),
+        service(BANK, {"label": "Bank:carol", "digits": 6, "algorithm": "SHA1",
+                       "tokenType": "HOTP", "counter": 5}, 2),
+    ]
+
+
+def twofas():
+    base = {"groups": [], "updatedAt": 1700000000000, "schemaVersion": 4, "appVersionCode": 5000000,
+            "appVersionName": "5.0.0", "appOrigin": "android"}
+    write("twofas-plain.2fas", json.dumps(dict(base, services=twofas_services()), indent=4))
+
+    salt = os.urandom(256)
+    iv = os.urandom(12)
+    key = hashlib.pbkdf2_hmac("sha256", b"2fas-test", salt, 10_000, 32)
+    sealed = AESGCM(key).encrypt(iv, json.dumps(twofas_services()).encode(), None)
+    encrypted = ":".join(b64(x) for x in (sealed, salt, iv))
+    write("twofas-encrypted.2fas", json.dumps(dict(base, services=[], servicesEncrypted=encrypted), indent=4))
+
+
+# ---------- andOTP ----------
+
+def andotp_entries():
+    def entry(acct, extra):
+        return dict({"secret": acct["secret"], "issuer": acct["issuer"], "label": acct["name"],
+                     "digits": acct["digits"], "algorithm": acct["algo"], "thumbnail": "Default",
+                     "last_used": 1700000000000, "used_frequency": 0, "tags": []}, **extra)
+    return [
+        entry(GITHUB, {"type": "TOTP", "period": 30}),
+        entry(EXAMPLE, {"type": "TOTP", "period": 60}),
+        entry(BANK, {"type": "HOTP", "counter": 5}),
+    ]
+
+
+def andotp():
+    write("andotp-plain.json", json.dumps(andotp_entries()))
+
+    iterations = 150_000
+    salt = os.urandom(12)
+    iv = os.urandom(12)
+    key = hashlib.pbkdf2_hmac("sha1", b"andotp-test", salt, iterations, 32)
+    sealed = AESGCM(key).encrypt(iv, json.dumps(andotp_entries()).encode(), None)
+    write("andotp-encrypted.json.aes", struct.pack(">I", iterations) + salt + iv + sealed)
+
+
+# ---------- FreeOTP+ ----------
+
+def freeotp():
+    def token(acct, extra):
+        raw = base64.b32decode(acct["secret"] + "=" * (-len(acct["secret"]) % 8))
+        return dict({"algo": acct["algo"], "digits": acct["digits"], "issuerExt": acct["issuer"],
+                     "issuerInt": acct["issuer"], "label": acct["name"],
+                     "secret": [b - 256 if b > 127 else b for b in raw]}, **extra)
+    tokens = [
+        token(GITHUB, {"counter": 0, "period": 30, "type": "TOTP"}),
+        token(EXAMPLE, {"counter": 0, "period": 60, "type": "TOTP"}),
+        token(BANK, {"counter": 5, "period": 30, "type": "HOTP"}),
+    ]
+    order = ["GitHub:alice@example.com", "Example:bob", "Bank:carol"]
+    write("freeotp-plus.json", json.dumps({"tokenOrder": order, "tokens": tokens}))
+
+
+# ---------- Bitwarden ----------
+
+def bitwarden_export():
+    def login(item_id, name, username, totp):
+        return {"id": item_id, "organizationId": None, "folderId": None, "type": 1, "reprompt": 0,
+                "name": name, "notes": None, "favorite": False,
+                "login": {"uris": [], "username": username, "password": "hunter2", "totp": totp},
+                "collectionIds": None}
+    return {"encrypted": False, "folders": [], "items": [
+        # 只填 Base32 密钥时按默认参数导入
+        login("1", "GitHub", GITHUB["name"], GITHUB["secret"]),
+        login("2", "Example", EXAMPLE["name"],
+              "otpauth://totp/Example:bob?secret=%s&issuer=Example&algorithm=SHA256&digits=8&period=60"
+              % EXAMPLE["secret"]),
+        login("3", "Steam", "dave", "steam://ABCDEFGHIJKLMNOP"),
+        login("4", "No TOTP", "erin", None),
+        {"id": "5", "type": 2, "name": "Note", "notes": "text", "secureNote": {"type": 0}},
+    ]}
+
+
+def bitwarden():
+    plain = bitwarden_export()
+    write("bitwarden-plain.json", json.dumps(plain, indent=2))
+
+    salt = b64(os.urandom(16))
+    iterations = 5_000
+    master = hashlib.pbkdf2_hmac("sha256", b"bitwarden-test", salt.encode(), iterations, 32)
+    enc_key = HKDFExpand(hashes.SHA256(), 32, b"enc").derive(master)
+    mac_key = HKDFExpand(hashes.SHA256(), 32, b"mac").derive(master)
+
+    def enc_string(data):
+        iv = os.urandom(16)
+        padder = padding.PKCS7(128).padder()
+        encryptor = Cipher(algorithms.AES(enc_key), modes.CBC(iv)).encryptor()
+        ciphertext = encryptor.update(padder.update(data) + padder.finalize()) + encryptor.finalize()
+        mac = hmac.new(mac_key, iv + ciphertext, "sha256").digest()
+        return "2." + "|".join(b64(x) for x in (iv, ciphertext, mac))
+
+    write("bitwarden-encrypted.json", json.dumps({
+        "encrypted": True, "passwordProtected": True, "salt": salt, "kdfType": 0,
+        "kdfIterations": iterations, "kdfMemory": None, "kdfParallelism": None,
+        "encKeyValidation_DO_NOT_EDIT": enc_string(str(uuid.uuid4()).encode()),
+        "data": enc_string(json.dumps(plain).encode()),
+    }, indent=2))
+
+
+# ---------- KeePass KDBX 4 ----------
+
+CIPHER_AES256 = bytes.fromhex("31c1f2e6bf714350be5805216afc5aff")
+CIPHER_CHACHA20 = bytes.fromhex("d6038a2b8b6f4cb5a524339a31dbb59a")
+KDF_AES = bytes.fromhex("c9d9f39a628a4460bf740d08c18a4fea")
+KDF_ARGON2ID = bytes.fromhex("9e298b1956db4773b23dfc3ec6f0a1e6")
+
+
+def variant_dict(items):
+    out = struct.pack("<H", 0x0100)
+    for value_type, key, value in items:
+        out += bytes([value_type]) + struct.pack("<i", len(key)) + key + struct.pack("<i", len(value)) + value
+    return out + b"\0"
+
+
+def field(field_id, data):
+    return bytes([field_id]) + struct.pack("<I", len(data)) + data
+
+
+def kdbx(name, password, cipher, kdf):
+    master_seed, kdf_salt, stream_key = os.urandom(32), os.urandom(32), os.urandom(64)
+    iv = os.urandom(16 if cipher == CIPHER_AES256 else 12)
+    composite = hashlib.sha256(hashlib.sha256(password).digest()).digest()
+
+    if kdf == KDF_AES:
+        rounds = 6_000
+        params = variant_dict([(0x42, b"$UUID", KDF_AES), (0x42, b"S", kdf_salt),
+                               (0x05, b"R", struct.pack("<Q", rounds))])
+        encryptor = Cipher(algorithms.AES(kdf_salt), modes.ECB()).encryptor()
+        transformed = composite
+        for _ in range(rounds):
+            transformed = encryptor.update(transformed)
+        transformed = hashlib.sha256(transformed).digest()
+    else:
+        memory, iterations, lanes = 1024 * 1024, 2, 2
+        params = variant_dict([(0x42, b"$UUID", KDF_ARGON2ID), (0x42, b"S", kdf_salt),
+                               (0x04, b"P", struct.pack("<I", lanes)), (0x05, b"M", struct.pack("<Q", memory)),
+                               (0x05, b"I", struct.pack("<Q", iterations)), (0x04, b"V", struct.pack("<I", 0x13))])
+        transformed = Argon2id(salt=kdf_salt, length=32, iterations=iterations, lanes=lanes,
+                               memory_cost=memory // 1024).derive(composite)
+
+    header = struct.pack("<IIHH", 0x9AA2D903, 0xB54BFB67, 1, 4)
+    header += field(2, cipher) + field(3, struct.pack("<I", 1)) + field(4, master_seed)
+    header += field(7, iv) + field(11, params) + field(0, b"\r\n\r\n")
+
+    cipher_key = hashlib.sha256(master_seed + transformed).digest()
+    hmac_key = hashlib.sha512(master_seed + transformed + b"\x01").digest()
+
+    def block_key(index):
+        return hashlib.sha512(struct.pack("<Q", index) + hmac_key).digest()
+
+    stream_hash = hashlib.sha512(stream_key).digest()
+    stream = Cipher(algorithms.ChaCha20(stream_hash[:32], b"\0" * 4 + stream_hash[32:44]), None).encryptor()
+
+    def string(key, value, protected=False):
+        if protected:
+            return '<String><Key>%s</Key><Value Protected="True">%s</Value></String>' % (
+                key, b64(stream.update(value.encode())))
+        return "<String><Key>%s</Key><Value>%s</Value></String>" % (key, value)
+
+    def entry(title, username, fields):
+        xml = "<Entry><UUID>%s</UUID>" % b64(os.urandom(16))
+        xml += string("Title", title) + string("UserName", username) + string("Password", "hunter2", True)
+        for key, value, protected in fields:
+            xml += string(key, value, protected)
+        # 历史版本中的旧密钥不应被导入
+        xml += "<History><Entry>%s</Entry></History>" % string(
+            "otp", "otpauth://totp/Old:old?secret=MFRGGZDFMZTWQ2LKNNWG23TPOBYXE43U")
+        return xml + "</Entry>"
+
+    recycle_bin = b64(b"B" * 16)
+    xml = '<?xml version="1.0" encoding="utf-8" standalone="yes"?><KeePassFile>'
+    xml += "<Meta><Generator>KeePassXC</Generator><RecycleBinUUID>%s</RecycleBinUUID></Meta>" % recycle_bin
+    xml += "<Root><Group><UUID>%s</UUID><Name>Root</Name>" % b64(b"R" * 16)
+    # KeePassXC 的 otp 字段
+    xml += entry("GitHub", GITHUB["name"], [
+        ("otp", "otpauth://totp/GitHub:alice%%40example.com?secret=%s&amp;period=30&amp;digits=6&amp;issuer=GitHub"
+         % GITHUB["secret"], True)])
+    xml += entry("Website", "nobody", [])
+    # 回收站中的条目应被跳过
+    xml += "<Group><UUID>%s</UUID><Name>Recycle Bin</Name>%s</Group>" % (recycle_bin, entry("Deleted", "mallory", [
+        ("otp", "otpauth://totp/Deleted:mallory?secret=MFRGGZDFMZTWQ2LKNNWG23TPOBYXE43U", False)]))
+    # 子分组中的 KeePass 内置 TimeOtp / HmacOtp 字段
+    xml += "<Group><UUID>%s</UUID><Name>Finance</Name>" % b64(b"F" * 16)
+    xml += entry("Example", EXAMPLE["name"], [
+        ("TimeOtp-Secret-Base32", EXAMPLE["secret"], True), ("TimeOtp-Length", "8", False),
+        ("TimeOtp-Period", "60", False), ("TimeOtp-Algorithm", "HMAC-SHA-256", False)])
+    xml += entry("Bank", BANK["name"], [
+        ("HmacOtp-Secret", BANK_RAW.decode(), True), ("HmacOtp-Counter", "5", False)])
+    xml += "</Group></Group></Root></KeePassFile>"
+
+    inner = field(1, struct.pack("<I", 3)) + field(2, stream_key) + field(0, b"") + xml.encode()
+    payload = gzip.compress(inner)
+    if cipher == CIPHER_AES256:
+        padder = padding.PKCS7(128).padder()
+        encryptor = Cipher(algorithms.AES(cipher_key), modes.CBC(iv)).encryptor()
+        ciphertext = encryptor.update(padder.update(payload) + padder.finalize()) + encryptor.finalize()
+    else:
+        encryptor = Cipher(algorithms.ChaCha20(cipher_key, b"\0" * 4 + iv), None).encryptor()
+        ciphertext = encryptor.update(payload)
+
+    out = header + hashlib.sha256(header).digest()
+    out += hmac.new(block_key(0xFFFF_FFFF_FFFF_FFFF), header, "sha256").digest()
+    for index, block in enumerate([ciphertext, b""]):
+        length = struct.pack("<i", len(block))
+        out += hmac.new(block_key(index), struct.pack("<Q", index) + length + block, "sha256").digest()
+        out += length + block
+    write(name, out)
+
+
+def keepass():
+    kdbx("keepass-aes-kdf.kdbx", b"keepass-test", CIPHER_AES256, KDF_AES)
+    kdbx("keepass-argon2id.kdbx", b"keepass-test", CIPHER_CHACHA20, KDF_ARGON2ID)
+
+
+# ---------- 历史版本的 data.enc ----------
+
+def vault_json(schema_version):
+    password_hash = hashlib.sha256(b"adminweb-totp-salt" * 100_000).hexdigest()
+    user = {"username": "admin", "password_hash": password_hash, "two_fa_enabled": False, "two_fa_secret": None}
+    entry = {"id": "5b0c7d1e-9f3a-4c2b-8e6d-1a2b3c4d5e6f", "name": "alice@example.com", "issuer": "GitHub",
+             "secret": GITHUB["secret"], "created_at": "2024-01-02T03:04:05+00:00"}
+    if schema_version == 1:
+        return json.dumps({"user": user, "totp_entries": [entry]}).encode()
+    entry.update(algorithm="SHA256", digits=8, period=60, otp_type="totp", counter=0)
+    return json.dumps({"schema_version": schema_version, "user": user, "totp_entries": [entry]}).encode()
+
+
+def vault_key(salt):
+    # 旧版本使用的 Argon2::default() 参数（argon2 0.3：m=4096, t=3, p=1）
+    return Argon2id(salt=salt, length=32, iterations=3, lanes=1, memory_cost=4096).derive(b"master-test")
+
+
+def vaults():
+    # 版本 1：没有文件头，salt(16) || nonce(12) || 密文，条目为结构版本 1
+    salt, nonce = os.urandom(16), os.urandom(12)
+    write("data-v1-headerless.enc", salt + nonce + AESGCM(vault_key(salt)).encrypt(nonce, vault_json(1), None))
+
+    # 版本 2：文件头带 KDF 参数，数据密钥由主密码派生，条目为结构版本 2
+    salt, nonce = os.urandom(16), os.urandom(12)
+    header = b"WEBTOTP\0" + struct.pack("<HBIIIB", 2, 1, 4096, 3, 1, len(salt)) + salt + bytes([1])
+    ciphertext = AESGCM(vault_key(salt)).encrypt(nonce, vault_json(2), header)
+    write("data-v2-kdf-header.enc", header + bytes([len(nonce)]) + nonce + ciphertext)
+
+
+# ---------- Google Authenticator（otpauth-migration） ----------
+
+def varint(value):
+    out = bytearray()
+    while True:
+        byte = value & 0x7F
+        value >>= 7
+        if value:
+            out.append(byte | 0x80)
+        else:
+            out.append(byte)
+            return bytes(out)
+
+
+def proto_bytes(field_id, data):
+    return varint(field_id << 3 | 2) + varint(len(data)) + data
+
+
+def proto_varint(field_id, value):
+    return varint(field_id << 3) + varint(value)
+
+
+def otp_parameters(account, raw_secret, algorithm=1, digits=1, otp_type=2, counter=0):
+    out = (proto_bytes(1, raw_secret)
+           + proto_bytes(2, f"{account['issuer']}:{account['name']}".encode())
+           + proto_bytes(3, account["issuer"].encode())
+           + proto_varint(4, algorithm) + proto_varint(5, digits) + proto_varint(6, otp_type))
+    if otp_type == 1:
+        out += proto_varint(7, counter)
+    return out
+
+
+def migration_uri(parameters, batch_size, batch_index, batch_id):
+    payload = b"".join(proto_bytes(1, p) for p in parameters)
+    payload += proto_varint(2, 1) + proto_varint(3, batch_size) + proto_varint(4, batch_index) + proto_varint(5, batch_id)
+    data = base64.b64encode(payload).decode()
+    return "otpauth-migration://offline?data=" + data.replace("+", "%2B").replace("/", "%2F").replace("=", "%3D")
+
+
+def migration():
+    github = otp_parameters(GITHUB, base64.b32decode(GITHUB["secret"]))
+    example = otp_parameters(EXAMPLE, base64.b32decode(EXAMPLE["secret"]), algorithm=2, digits=2)
+    bank = otp_parameters(BANK, BANK_RAW, otp_type=1, counter=BANK["counter"])
+    # 应用每个二维码最多放若干个账户，账户多时分成几批，同一次导出的 batch_id 相同
+    write("google-authenticator-single.txt", migration_uri([github, example, bank], 1, 0, 1234567890) + "\n")
+    write("google-authenticator-batches.txt", "\n".join([
+        migration_uri([github, example], 2, 0, 987654321),
+        migration_uri([bank], 2, 1, 987654321),
+    ]) + "\n")
+
+
+if __name__ == "__main__":
+    aegis()
+    twofas()
+    andotp()
+    freeotp()
+    bitwarden()
+    keepass()
+    vaults()
+    migration()